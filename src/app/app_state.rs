@@ -1,8 +1,18 @@
+use crate::app::key_ring::KeyRing;
+use crate::app::middleware::account_middleware::AccountMiddleware;
+use crate::app::middleware::battle_middleware::BattleMiddleware;
 use crate::app::middleware::cache_middleware::CacheMiddleware;
+use crate::app::middleware::chat_middleware::ChatMiddleware;
+use crate::app::middleware::gateway_middleware::GatewayMiddleware;
+use crate::app::middleware::guild_middleware::GuildMiddleware;
+use crate::app::middleware::inventory_middleware::InventoryMiddleware;
+use crate::app::middleware::moderation_middleware::ModerationMiddleware;
 use crate::app::middleware::player_middleware::PlayerMiddleware;
+use crate::app::middleware::rate_limit_middleware::RateLimitMiddleware;
 use crate::app::middleware::static_tables_cache_middleware::StaticTablesCacheMiddleware;
 use crate::app::redis::RedisConnectionManager;
 use crate::error::AppError;
+use crate::model::player::PlayerRole;
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use axum::RequestPartsExt;
@@ -10,13 +20,10 @@ use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use jsonwebtoken::Algorithm::HS512;
-use jsonwebtoken::{decode, DecodingKey, EncodingKey, Validation};
+use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-pub const JWT_AUTH_SECRET: &str = "1vTDxVKBx6UMSwvYoRGMokJy3dTPrhSVwsSu5yCoPexukstyMtSjEK3MPUpF9t1";
-
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
@@ -24,48 +31,118 @@ pub struct AppState {
     pub player_middleware: Arc<PlayerMiddleware>,
     pub cache_middleware: Arc<CacheMiddleware>,
     pub static_table_middleware: Arc<StaticTablesCacheMiddleware>,
+    pub battle_middleware: Arc<BattleMiddleware>,
+    pub gateway_middleware: Arc<GatewayMiddleware>,
+    pub chat_middleware: Arc<ChatMiddleware>,
+    pub inventory_middleware: Arc<InventoryMiddleware>,
+    pub guild_middleware: Arc<GuildMiddleware>,
+    pub rate_limit_middleware: Arc<RateLimitMiddleware>,
+    pub account_middleware: Arc<AccountMiddleware>,
+    pub moderation_middleware: Arc<ModerationMiddleware>,
+    pub key_ring: Arc<KeyRing>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    /// Player id.
     pub(crate) sub: String,
+    pub(crate) nickname: String,
     pub(crate) exp: usize,
 }
 
-impl<S> FromRequestParts<S> for Claims
-where
-    S: Send + Sync,
-{
+impl Claims {
+    pub fn player_id(&self) -> Result<i32, AppError> {
+        self.sub.parse().map_err(|_| AppError::InvalidToken)
+    }
+}
+
+impl FromRequestParts<AppState> for Claims {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Extract the token from the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| AppError::InvalidToken)?;
-        // Decode the user data
-        let token_data = decode::<Claims>(
-            bearer.token(),
-            &DecodingKey::from_secret(JWT_AUTH_SECRET.as_bytes()),
-            &Validation::new(HS512),
-        )
-        .map_err(|_| AppError::InvalidToken)?;
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (_, claims) = decode_bearer(parts, &state.key_ring).await?;
 
-        Ok(token_data.claims)
+        Ok(claims)
     }
 }
 
-pub struct Keys {
-    pub(crate) encoding: EncodingKey,
-    pub(crate) decoding: DecodingKey,
+/// Player behind a valid token of a live session. Unlike bare [`Claims`], a token is rejected
+/// once its session is ended, and banned players are rejected at all.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedPlayer {
+    pub player_id: i32,
+    pub nickname: String,
+    pub role: PlayerRole,
+    pub muted_until: Option<NaiveDateTime>,
 }
 
-impl Keys {
-    pub(crate) fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
+impl FromRequestParts<AppState> for AuthenticatedPlayer {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (token, claims) = decode_bearer(parts, &state.key_ring).await?;
+        let player_id = claims.player_id()?;
+        let player = state
+            .player_middleware
+            .get_player_by_id(player_id)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+        if player.is_banned(Utc::now().naive_utc()) {
+            return Err(AppError::PlayerBanned(player.nickname));
+        }
+        if !state
+            .cache_middleware
+            .check_session_token(player_id as i64, &token)
+            .await?
+        {
+            return Err(AppError::InvalidToken);
         }
+
+        Ok(AuthenticatedPlayer {
+            player_id,
+            nickname: player.nickname,
+            role: PlayerRole::from_id(player.role),
+            muted_until: player.muted_until,
+        })
     }
 }
+
+/// [`AuthenticatedPlayer`] with the admin role, anyone else is forbidden.
+#[derive(Debug, Clone)]
+pub struct AdminPlayer(pub AuthenticatedPlayer);
+
+impl FromRequestParts<AppState> for AdminPlayer {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let player = AuthenticatedPlayer::from_request_parts(parts, state).await?;
+        if player.role != PlayerRole::Admin {
+            return Err(AppError::Forbidden);
+        }
+
+        Ok(AdminPlayer(player))
+    }
+}
+
+/// Extracts the bearer token from the authorization header and verifies its claims.
+async fn decode_bearer(
+    parts: &mut Parts,
+    key_ring: &KeyRing,
+) -> Result<(String, Claims), AppError> {
+    let TypedHeader(Authorization(bearer)) = parts
+        .extract::<TypedHeader<Authorization<Bearer>>>()
+        .await
+        .map_err(|_| AppError::InvalidToken)?;
+    let claims = key_ring.verify::<Claims>(bearer.token())?;
+
+    Ok((bearer.token().to_owned(), claims))
+}
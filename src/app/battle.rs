@@ -0,0 +1,1320 @@
+use crate::app::grid::{Hex, HexGrid, Mobility, PathBudget};
+use crate::app::stats::{CombatStats, Vitals};
+use crate::model::battle::{Bot, NewBattleParticipant};
+use crate::model::item::WeaponItem;
+use bon::Builder;
+use chrono::naive::NaiveDateTime;
+use chrono::Utc;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+// Mirrors the comment on the `battle` table: 32 players/bots per battle at most.
+pub const MAX_PARTICIPANTS: usize = 32;
+pub const MOVE_COST_PER_HEX: i32 = 1;
+/// How far the participants see the battlefield.
+pub const VIEW_RADIUS: i32 = 8;
+/// Bots stop acting after this many actions in a row, so bots unable to reach each other
+/// can't keep the battle busy forever.
+pub const MAX_BOT_ACTIONS: usize = 256;
+
+pub const BOT_BASE_HEALTH: i32 = 20;
+pub const BOT_HEALTH_PER_LEVEL: i32 = 10;
+
+pub type ParticipantId = usize;
+
+/// Battle sides, the ids match the `factions` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    En = 0,
+    Fr = 1,
+    Bots = 2,
+}
+
+impl Faction {
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Combatant {
+    Player { player_id: i32, nickname: String },
+    Bot { bot_id: i32, name: String },
+}
+
+impl Combatant {
+    pub fn name(&self) -> &str {
+        match self {
+            Combatant::Player { nickname, .. } => nickname,
+            Combatant::Bot { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weapon {
+    pub action_points_to_use: i32,
+    pub basic_damage: i32,
+    pub range: i32,
+}
+
+pub const BARE_HANDS: Weapon = Weapon {
+    action_points_to_use: 2,
+    basic_damage: 1,
+    range: 1,
+};
+
+impl From<&WeaponItem> for Weapon {
+    fn from(weapon: &WeaponItem) -> Self {
+        Weapon {
+            action_points_to_use: weapon.action_points_to_use,
+            basic_damage: weapon.basic_damage,
+            range: weapon.range,
+        }
+    }
+}
+
+/// Everything the engine needs to know about a unit before it is placed on the battlefield.
+#[derive(Builder, Debug, Clone)]
+pub struct Fighter {
+    pub combatant: Combatant,
+    pub faction: Faction,
+    pub max_health: i32,
+    /// Health the fighter enters the battle with, full health if not set.
+    pub health: Option<i32>,
+    pub action_points: i32,
+    #[builder(default = BARE_HANDS)]
+    pub weapon: Weapon,
+    #[builder(default)]
+    pub damage_bonus: i32,
+    #[builder(default)]
+    pub mobility: Mobility,
+}
+
+impl Fighter {
+    pub fn from_player(
+        player_id: i32,
+        nickname: String,
+        faction: Faction,
+        stats: &CombatStats,
+        vitals: &Vitals,
+    ) -> Self {
+        Fighter {
+            combatant: Combatant::Player {
+                player_id,
+                nickname,
+            },
+            faction,
+            max_health: stats.max_health,
+            health: Some(vitals.health),
+            action_points: stats.action_points,
+            weapon: stats.weapon,
+            damage_bonus: stats.damage_bonus,
+            mobility: stats.mobility,
+        }
+    }
+
+    pub fn from_bot(bot: &Bot, weapon: &WeaponItem) -> Self {
+        Fighter {
+            combatant: Combatant::Bot {
+                bot_id: bot.id,
+                name: bot.name.clone(),
+            },
+            faction: Faction::Bots,
+            max_health: BOT_BASE_HEALTH + BOT_HEALTH_PER_LEVEL * bot.level,
+            health: None,
+            action_points: bot.action_points,
+            weapon: weapon.into(),
+            damage_bonus: 0,
+            mobility: Mobility::Foot,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Participant {
+    pub fighter: Fighter,
+    pub position: (i32, i32),
+    pub health: i32,
+    pub action_points: i32,
+    pub outcome_damage: i32,
+    pub income_damage: i32,
+}
+
+impl Participant {
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+}
+
+/// A hex the participant can move to and the action points it takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveOption {
+    pub position: (i32, i32),
+    pub action_points: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleState {
+    Preparing,
+    InProgress,
+    Finished(Faction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleAction {
+    Move { col: i32, row: i32 },
+    Attack { target: ParticipantId },
+    EndTurn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BattleEvent {
+    TurnStarted {
+        participant: ParticipantId,
+        round: u32,
+    },
+    Moved {
+        participant: ParticipantId,
+        path: Vec<(i32, i32)>,
+        action_points_left: i32,
+    },
+    Attacked {
+        attacker: ParticipantId,
+        target: ParticipantId,
+        damage: i32,
+        target_health: i32,
+    },
+    Died {
+        participant: ParticipantId,
+    },
+    Finished {
+        winner: Faction,
+    },
+}
+
+impl Display for BattleEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BattleEvent::TurnStarted { participant, round } => {
+                write!(f, "round {round}: turn of #{participant}")
+            }
+            BattleEvent::Moved {
+                participant, path, ..
+            } => match path.last() {
+                Some((col, row)) => write!(f, "#{participant} moved to ({col}, {row})"),
+                None => write!(f, "#{participant} stayed in place"),
+            },
+            BattleEvent::Attacked {
+                attacker,
+                target,
+                damage,
+                target_health,
+            } => write!(
+                f,
+                "#{attacker} hit #{target} for {damage}, {target_health} health left"
+            ),
+            BattleEvent::Died { participant } => write!(f, "#{participant} died"),
+            BattleEvent::Finished { winner } => write!(f, "battle won by {winner:?}"),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BattleError {
+    #[error("Battle has already started")]
+    AlreadyStarted,
+    #[error("Battle is not in progress")]
+    NotInProgress,
+    #[error("Battle needs at least two factions to start")]
+    NotEnoughFactions,
+    #[error("Battle can't have more than {MAX_PARTICIPANTS} participants")]
+    TooManyParticipants,
+    #[error("Unknown participant #{0}")]
+    UnknownParticipant(ParticipantId),
+    #[error("It is not the turn of participant #{0}")]
+    NotYourTurn(ParticipantId),
+    #[error("Hex ({0}, {1}) can't be entered")]
+    HexNotEnterable(i32, i32),
+    #[error("Hex ({0}, {1}) can't be reached with the action points left")]
+    HexUnreachable(i32, i32),
+    #[error("Not enough action points: {required} required, {left} left")]
+    NotEnoughActionPoints { required: i32, left: i32 },
+    #[error("Target is out of range: distance {distance}, range {range}")]
+    OutOfRange { distance: i32, range: i32 },
+    #[error("Participant #{0} can't be seen")]
+    NoLineOfSight(ParticipantId),
+    #[error("Participant #{0} can't be attacked")]
+    InvalidTarget(ParticipantId),
+}
+
+/// Events the faction may know of, given the participants it sees: the ones about a visible
+/// participant. A hit is known to both sides if either of them is seen.
+pub fn visible_events(events: &[BattleEvent], visible: &[ParticipantId]) -> Vec<BattleEvent> {
+    events
+        .iter()
+        .filter(|event| match event {
+            BattleEvent::TurnStarted { participant, .. }
+            | BattleEvent::Moved { participant, .. }
+            | BattleEvent::Died { participant } => visible.contains(participant),
+            BattleEvent::Attacked {
+                attacker, target, ..
+            } => visible.contains(attacker) || visible.contains(target),
+            BattleEvent::Finished { .. } => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Server-side state of a single fight.
+///
+/// Participants act one after another in the order they were placed. Each participant gets
+/// its action points back at the start of its turn, and the turn passes automatically when
+/// the points are exhausted. The battle ends once only one faction has living participants.
+#[derive(Debug)]
+pub struct Battle {
+    grid: HexGrid,
+    participants: Vec<Participant>,
+    state: BattleState,
+    current: ParticipantId,
+    round: u32,
+    started_at: Option<NaiveDateTime>,
+    finished_at: Option<NaiveDateTime>,
+    acted_at: Option<NaiveDateTime>,
+    stored_id: Option<i32>,
+    log: Vec<String>,
+}
+
+impl Battle {
+    pub fn new(grid: HexGrid) -> Self {
+        Battle {
+            grid,
+            participants: vec![],
+            state: BattleState::Preparing,
+            current: 0,
+            round: 0,
+            started_at: None,
+            finished_at: None,
+            acted_at: None,
+            stored_id: None,
+            log: vec![],
+        }
+    }
+
+    pub fn grid(&self) -> &HexGrid {
+        &self.grid
+    }
+
+    pub fn participants(&self) -> &[Participant] {
+        &self.participants
+    }
+
+    pub fn participant(&self, id: ParticipantId) -> Result<&Participant, BattleError> {
+        self.participants
+            .get(id)
+            .ok_or(BattleError::UnknownParticipant(id))
+    }
+
+    pub fn state(&self) -> BattleState {
+        self.state
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn current_participant(&self) -> Option<ParticipantId> {
+        match self.state {
+            BattleState::InProgress => Some(self.current),
+            _ => None,
+        }
+    }
+
+    pub fn winner(&self) -> Option<Faction> {
+        match self.state {
+            BattleState::Finished(winner) => Some(winner),
+            _ => None,
+        }
+    }
+
+    pub fn started_at(&self) -> Option<NaiveDateTime> {
+        self.started_at
+    }
+
+    pub fn finished_at(&self) -> Option<NaiveDateTime> {
+        self.finished_at
+    }
+
+    /// When the battle started or anyone last acted in it.
+    pub fn acted_at(&self) -> Option<NaiveDateTime> {
+        self.acted_at
+    }
+
+    /// Id of the `battle` row once the finished battle is stored.
+    pub fn stored_id(&self) -> Option<i32> {
+        self.stored_id
+    }
+
+    pub fn mark_stored(&mut self, battle_id: i32) {
+        self.stored_id = Some(battle_id);
+    }
+
+    pub fn log(&self) -> &[String] {
+        &self.log
+    }
+
+    /// Participant of the player, `None` if the player doesn't take part in the battle.
+    pub fn player_participant(&self, player_id: i32) -> Option<ParticipantId> {
+        self.participants.iter().position(|p| {
+            matches!(p.fighter.combatant, Combatant::Player { player_id: id, .. } if id == player_id)
+        })
+    }
+
+    /// Ids of the players taking part in the battle.
+    pub fn player_ids(&self) -> Vec<i32> {
+        self.participants
+            .iter()
+            .filter_map(|p| match p.fighter.combatant {
+                Combatant::Player { player_id, .. } => Some(player_id),
+                Combatant::Bot { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Factions taking part in the battle, in the order they were placed.
+    pub fn factions(&self) -> Vec<Faction> {
+        let mut factions: Vec<Faction> = vec![];
+        for participant in &self.participants {
+            if !factions.contains(&participant.fighter.faction) {
+                factions.push(participant.fighter.faction);
+            }
+        }
+        factions
+    }
+
+    /// Participants the faction knows about: its own ones, and the ones in the view of any of
+    /// its living participants. The dead stay where they fell, visible to everyone.
+    pub fn visible_participants(&self, faction: Faction) -> Vec<ParticipantId> {
+        let viewers: Vec<&Hex> = self
+            .participants
+            .iter()
+            .filter(|p| p.fighter.faction == faction && p.is_alive())
+            .filter_map(|p| self.hex_at(p.position.0, p.position.1).ok())
+            .collect();
+
+        (0..self.participants.len())
+            .filter(|id| {
+                let participant = &self.participants[*id];
+                if participant.fighter.faction == faction || !participant.is_alive() {
+                    return true;
+                }
+                let Ok(hex) = self.hex_at(participant.position.0, participant.position.1) else {
+                    return false;
+                };
+                viewers.iter().any(|viewer| {
+                    self.grid.distance(viewer, hex) <= VIEW_RADIUS
+                        && self.grid.line_of_sight(viewer, hex)
+                })
+            })
+            .collect()
+    }
+
+    /// Hexes the participant can move to with the action points left, with the points each
+    /// move costs.
+    pub fn movement_range(&self, id: ParticipantId) -> Result<Vec<MoveOption>, BattleError> {
+        let participant = self.participant(id)?;
+        let (col, row) = participant.position;
+        let reachable = self.grid.reachable(
+            self.hex_at(col, row)?,
+            participant.action_points / MOVE_COST_PER_HEX,
+            participant.fighter.mobility,
+        );
+
+        Ok(reachable
+            .hexes()
+            .into_iter()
+            .map(|(hex, cost)| MoveOption {
+                position: (hex.col, hex.row),
+                action_points: cost * MOVE_COST_PER_HEX,
+            })
+            .collect())
+    }
+
+    pub fn place(
+        &mut self,
+        fighter: Fighter,
+        col: i32,
+        row: i32,
+    ) -> Result<ParticipantId, BattleError> {
+        if self.state != BattleState::Preparing {
+            return Err(BattleError::AlreadyStarted);
+        }
+        if self.participants.len() >= MAX_PARTICIPANTS {
+            return Err(BattleError::TooManyParticipants);
+        }
+        self.occupy(col, row)?;
+
+        self.participants.push(Participant {
+            health: fighter
+                .health
+                .map_or(fighter.max_health, |health| health.min(fighter.max_health)),
+            action_points: fighter.action_points,
+            fighter,
+            position: (col, row),
+            outcome_damage: 0,
+            income_damage: 0,
+        });
+
+        Ok(self.participants.len() - 1)
+    }
+
+    /// Gives the first turn to the first living participant. A battle where only one faction
+    /// has living participants finishes at once.
+    pub fn start(&mut self) -> Result<Vec<BattleEvent>, BattleError> {
+        if self.state != BattleState::Preparing {
+            return Err(BattleError::AlreadyStarted);
+        }
+        let faction = self.participants.first().map(|p| p.fighter.faction);
+        if self
+            .participants
+            .iter()
+            .all(|p| Some(p.fighter.faction) == faction)
+        {
+            return Err(BattleError::NotEnoughFactions);
+        }
+        let Some(first) = self.participants.iter().position(Participant::is_alive) else {
+            return Err(BattleError::NotEnoughFactions);
+        };
+
+        self.state = BattleState::InProgress;
+        self.started_at = Some(Utc::now().naive_utc());
+        self.acted_at = self.started_at;
+        self.round = 1;
+
+        // A side which enters the battle with nobody alive loses right away.
+        if let Some(winner) = self.resolve_winner() {
+            self.state = BattleState::Finished(winner);
+            self.finished_at = self.started_at;
+            let mut events = vec![BattleEvent::Finished { winner }];
+            self.record(&mut events);
+            return Ok(events);
+        }
+
+        self.current = first;
+        let participant = &mut self.participants[first];
+        participant.action_points = participant.fighter.action_points;
+
+        let mut events = vec![BattleEvent::TurnStarted {
+            participant: first,
+            round: self.round,
+        }];
+        self.record(&mut events);
+        Ok(events)
+    }
+
+    pub fn act(
+        &mut self,
+        actor: ParticipantId,
+        action: BattleAction,
+    ) -> Result<Vec<BattleEvent>, BattleError> {
+        if self.state != BattleState::InProgress {
+            return Err(BattleError::NotInProgress);
+        }
+        self.participant(actor)?;
+        if actor != self.current {
+            return Err(BattleError::NotYourTurn(actor));
+        }
+
+        let mut events = match action {
+            BattleAction::Move { col, row } => self.move_to(actor, col, row)?,
+            BattleAction::Attack { target } => self.attack(actor, target)?,
+            BattleAction::EndTurn => vec![],
+        };
+
+        if let Some(winner) = self.resolve_winner() {
+            self.state = BattleState::Finished(winner);
+            self.finished_at = Some(Utc::now().naive_utc());
+            events.push(BattleEvent::Finished { winner });
+        } else if action == BattleAction::EndTurn || self.participants[actor].action_points == 0 {
+            events.push(self.next_turn());
+        }
+
+        self.acted_at = Some(Utc::now().naive_utc());
+        self.record(&mut events);
+        Ok(events)
+    }
+
+    /// Plays the turns of the bots until it is a player's turn or the battle is over.
+    pub fn play_bot_turns(&mut self) -> Vec<BattleEvent> {
+        let mut events = vec![];
+        for _ in 0..MAX_BOT_ACTIONS {
+            let Some(actor) = self.current_participant() else {
+                break;
+            };
+            if !matches!(
+                self.participants[actor].fighter.combatant,
+                Combatant::Bot { .. }
+            ) {
+                break;
+            }
+
+            let action = self.bot_action(actor);
+            match self
+                .act(actor, action)
+                .or_else(|_| self.act(actor, BattleAction::EndTurn))
+            {
+                Ok(new_events) => events.extend(new_events),
+                Err(_) => break,
+            }
+        }
+
+        events
+    }
+
+    /// The bot goes for the nearest enemy: hits it when it can, otherwise moves closer, at best
+    /// to a hex it can still hit from.
+    fn bot_action(&self, actor: ParticipantId) -> BattleAction {
+        let bot = &self.participants[actor];
+        let weapon = bot.fighter.weapon;
+        let Some((target, distance)) = self
+            .participants
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_alive() && p.fighter.faction != bot.fighter.faction)
+            .filter_map(|(id, p)| Some((id, self.distance(bot.position, p.position).ok()?)))
+            .min_by_key(|(id, distance)| (*distance, *id))
+        else {
+            return BattleAction::EndTurn;
+        };
+        let target_position = self.participants[target].position;
+
+        if distance <= weapon.range && self.in_sight(bot.position, target_position) {
+            return if bot.action_points >= weapon.action_points_to_use {
+                BattleAction::Attack { target }
+            } else {
+                BattleAction::EndTurn
+            };
+        }
+
+        let best_move = self
+            .movement_range(actor)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|option| {
+                let distance = self.distance(option.position, target_position).ok()?;
+                let can_hit = distance <= weapon.range
+                    && bot.action_points - option.action_points >= weapon.action_points_to_use;
+                Some((!can_hit, distance, option.action_points, option.position))
+            })
+            .min();
+        match best_move {
+            Some((_, new_distance, _, (col, row))) if new_distance < distance => {
+                BattleAction::Move { col, row }
+            }
+            _ => BattleAction::EndTurn,
+        }
+    }
+
+    /// Builds rows for `battle_participant`. `battle_id` is filled in once the `battle` row exists.
+    pub fn participant_records(&self) -> Vec<NewBattleParticipant> {
+        let winner = self.winner();
+        self.participants
+            .iter()
+            .map(|p| {
+                let (player_id, bot_id) = match p.fighter.combatant {
+                    Combatant::Player { player_id, .. } => (Some(player_id), None),
+                    Combatant::Bot { bot_id, .. } => (None, Some(bot_id)),
+                };
+                let won = winner == Some(p.fighter.faction);
+                NewBattleParticipant {
+                    battle_id: 0,
+                    player_id,
+                    bot_id,
+                    faction: p.fighter.faction.id(),
+                    outcome_damage: p.outcome_damage,
+                    income_damage: p.income_damage,
+                    // Winners get experience for the whole damage dealt, losers for a half of it.
+                    gained_exp: if won {
+                        p.outcome_damage
+                    } else {
+                        p.outcome_damage / 2
+                    },
+                    gained_valor: won && player_id.is_some() && p.outcome_damage > 0,
+                }
+            })
+            .collect()
+    }
+
+    fn move_to(
+        &mut self,
+        actor: ParticipantId,
+        col: i32,
+        row: i32,
+    ) -> Result<Vec<BattleEvent>, BattleError> {
+        let (from_col, from_row) = self.participants[actor].position;
+        let from = self.hex_at(from_col, from_row)?;
+        let to = self.hex_at(col, row)?;
+        if to.obstacle || to.busy {
+            return Err(BattleError::HexNotEnterable(col, row));
+        }
+
+        // The search never looks further than the action points left allow.
+        let left = self.participants[actor].action_points;
+        let mobility = self.participants[actor].fighter.mobility;
+        let budget = PathBudget::builder()
+            .max_cost(left / MOVE_COST_PER_HEX)
+            .build();
+        let path = self
+            .grid
+            .find_path(from, to, budget, |hex| hex.move_cost(mobility))
+            .ok_or(BattleError::HexUnreachable(col, row))?;
+        let required = path.cost * MOVE_COST_PER_HEX;
+        let path: Vec<(i32, i32)> = path.hexes.iter().map(|hex| (hex.col, hex.row)).collect();
+
+        self.release(from_col, from_row);
+        self.occupy(col, row)?;
+        let participant = &mut self.participants[actor];
+        participant.position = (col, row);
+        participant.action_points -= required;
+
+        Ok(vec![BattleEvent::Moved {
+            participant: actor,
+            path,
+            action_points_left: participant.action_points,
+        }])
+    }
+
+    fn attack(
+        &mut self,
+        actor: ParticipantId,
+        target: ParticipantId,
+    ) -> Result<Vec<BattleEvent>, BattleError> {
+        let attacker = &self.participants[actor];
+        let defender = self.participant(target)?;
+        if !defender.is_alive() || defender.fighter.faction == attacker.fighter.faction {
+            return Err(BattleError::InvalidTarget(target));
+        }
+
+        let weapon = attacker.fighter.weapon;
+        let left = attacker.action_points;
+        if weapon.action_points_to_use > left {
+            return Err(BattleError::NotEnoughActionPoints {
+                required: weapon.action_points_to_use,
+                left,
+            });
+        }
+
+        let distance = self.distance(attacker.position, defender.position)?;
+        if distance > weapon.range {
+            return Err(BattleError::OutOfRange {
+                distance,
+                range: weapon.range,
+            });
+        }
+        let (attacker_col, attacker_row) = attacker.position;
+        let (col, row) = defender.position;
+        if !self.grid.line_of_sight(
+            self.hex_at(attacker_col, attacker_row)?,
+            self.hex_at(col, row)?,
+        ) {
+            return Err(BattleError::NoLineOfSight(target));
+        }
+
+        // Cover reduces the damage, but every hit hurts a bit.
+        let defense = self.hex_at(col, row)?.terrain.defense_bonus();
+        let damage = (weapon.basic_damage + attacker.fighter.damage_bonus - defense)
+            .max(1)
+            .min(defender.health);
+        self.participants[actor].action_points -= weapon.action_points_to_use;
+        self.participants[actor].outcome_damage += damage;
+
+        let defender = &mut self.participants[target];
+        defender.health -= damage;
+        defender.income_damage += damage;
+
+        let mut events = vec![BattleEvent::Attacked {
+            attacker: actor,
+            target,
+            damage,
+            target_health: defender.health,
+        }];
+
+        if !defender.is_alive() {
+            let (col, row) = defender.position;
+            self.release(col, row);
+            events.push(BattleEvent::Died {
+                participant: target,
+            });
+        }
+
+        Ok(events)
+    }
+
+    fn next_turn(&mut self) -> BattleEvent {
+        let count = self.participants.len();
+        let mut next = self.current;
+        loop {
+            next = (next + 1) % count;
+            if next == 0 {
+                self.round += 1;
+            }
+            if self.participants[next].is_alive() {
+                break;
+            }
+        }
+
+        self.current = next;
+        let participant = &mut self.participants[next];
+        participant.action_points = participant.fighter.action_points;
+
+        BattleEvent::TurnStarted {
+            participant: next,
+            round: self.round,
+        }
+    }
+
+    fn resolve_winner(&self) -> Option<Faction> {
+        match self.alive_factions()[..] {
+            [winner] => Some(winner),
+            _ => None,
+        }
+    }
+
+    fn alive_factions(&self) -> Vec<Faction> {
+        let mut factions: Vec<Faction> = vec![];
+        self.participants
+            .iter()
+            .filter(|p| p.is_alive())
+            .for_each(|p| {
+                if !factions.contains(&p.fighter.faction) {
+                    factions.push(p.fighter.faction);
+                }
+            });
+        factions
+    }
+
+    fn distance(&self, from: (i32, i32), to: (i32, i32)) -> Result<i32, BattleError> {
+        Ok(self
+            .grid
+            .distance(self.hex_at(from.0, from.1)?, self.hex_at(to.0, to.1)?))
+    }
+
+    fn in_sight(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        match (self.hex_at(from.0, from.1), self.hex_at(to.0, to.1)) {
+            (Ok(from), Ok(to)) => self.grid.line_of_sight(from, to),
+            _ => false,
+        }
+    }
+
+    fn hex_at(&self, col: i32, row: i32) -> Result<&Hex, BattleError> {
+        if col < 0 || row < 0 {
+            return Err(BattleError::HexNotEnterable(col, row));
+        }
+        self.grid
+            .hex(col as usize, row as usize)
+            .ok_or(BattleError::HexNotEnterable(col, row))
+    }
+
+    fn occupy(&mut self, col: i32, row: i32) -> Result<(), BattleError> {
+        let hex = self.hex_at(col, row)?;
+        if hex.obstacle || hex.busy {
+            return Err(BattleError::HexNotEnterable(col, row));
+        }
+        self.grid.set_busy(col as usize, row as usize, true);
+        Ok(())
+    }
+
+    fn release(&mut self, col: i32, row: i32) {
+        if col >= 0 && row >= 0 {
+            self.grid.set_busy(col as usize, row as usize, false);
+        }
+    }
+
+    fn record(&mut self, events: &mut [BattleEvent]) {
+        self.log.extend(events.iter().map(|e| e.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::battle::{
+        visible_events, Battle, BattleAction, BattleError, BattleEvent, BattleState, Combatant,
+        Faction, Fighter, MoveOption, Weapon,
+    };
+    use crate::app::grid::{HexGrid, Mobility, Terrain};
+
+    fn fighter(name: &str, faction: Faction, weapon: Weapon) -> Fighter {
+        Fighter::builder()
+            .combatant(Combatant::Player {
+                player_id: 1,
+                nickname: name.to_owned(),
+            })
+            .faction(faction)
+            .max_health(10)
+            .action_points(6)
+            .weapon(weapon)
+            .build()
+    }
+
+    fn sword() -> Weapon {
+        Weapon {
+            action_points_to_use: 2,
+            basic_damage: 4,
+            range: 1,
+        }
+    }
+
+    fn duel() -> Battle {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(6, 4));
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 3, 1)
+            .unwrap();
+        battle.start().unwrap();
+        battle
+    }
+
+    #[test]
+    fn when_placed_on_busy_or_obstacle_hex_then_rejected() {
+        let mut battle = Battle::new(HexGrid::new_with_obstacles(4, 4, vec![(2, 2)]));
+        battle
+            .place(fighter("a", Faction::En, sword()), 1, 1)
+            .unwrap();
+
+        assert_eq!(
+            battle.place(fighter("b", Faction::Fr, sword()), 1, 1),
+            Err(BattleError::HexNotEnterable(1, 1))
+        );
+        assert_eq!(
+            battle.place(fighter("b", Faction::Fr, sword()), 2, 2),
+            Err(BattleError::HexNotEnterable(2, 2))
+        );
+        assert_eq!(
+            battle.place(fighter("b", Faction::Fr, sword()), 7, 0),
+            Err(BattleError::HexNotEnterable(7, 0))
+        );
+    }
+
+    #[test]
+    fn when_single_faction_then_battle_cannot_start() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(4, 4));
+        battle
+            .place(fighter("a", Faction::En, sword()), 0, 0)
+            .unwrap();
+        battle
+            .place(fighter("b", Faction::En, sword()), 1, 0)
+            .unwrap();
+
+        assert_eq!(battle.start(), Err(BattleError::NotEnoughFactions));
+    }
+
+    #[test]
+    fn when_first_participant_dead_then_next_living_one_starts() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(6, 4));
+        let fallen = Fighter {
+            health: Some(0),
+            ..fighter("en", Faction::En, sword())
+        };
+        battle.place(fallen, 0, 0).unwrap();
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 3, 1)
+            .unwrap();
+
+        let events = battle.start().unwrap();
+        assert_eq!(
+            events,
+            vec![BattleEvent::TurnStarted {
+                participant: 1,
+                round: 1
+            }]
+        );
+        assert_eq!(battle.current_participant(), Some(1));
+        assert_eq!(
+            battle.act(0, BattleAction::EndTurn),
+            Err(BattleError::NotYourTurn(0))
+        );
+    }
+
+    #[test]
+    fn when_one_side_has_nobody_alive_then_battle_finishes_at_start() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(6, 4));
+        let fallen = Fighter {
+            health: Some(0),
+            ..fighter("en", Faction::En, sword())
+        };
+        battle.place(fallen, 0, 1).unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 3, 1)
+            .unwrap();
+
+        assert_eq!(
+            battle.start(),
+            Ok(vec![BattleEvent::Finished {
+                winner: Faction::Fr
+            }])
+        );
+        assert_eq!(battle.winner(), Some(Faction::Fr));
+        assert!(battle.finished_at().is_some());
+    }
+
+    #[test]
+    fn when_moving_then_action_points_spent_and_hexes_updated() {
+        let mut battle = duel();
+
+        let events = battle
+            .act(0, BattleAction::Move { col: 2, row: 1 })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![BattleEvent::Moved {
+                participant: 0,
+                path: vec![(1, 1), (2, 1)],
+                action_points_left: 4,
+            }]
+        );
+        assert!(!battle.grid().hex(0, 1).unwrap().busy);
+        assert!(battle.grid().hex(2, 1).unwrap().busy);
+        assert_eq!(
+            battle.act(1, BattleAction::EndTurn),
+            Err(BattleError::NotYourTurn(1))
+        );
+    }
+
+    #[test]
+    fn when_target_hex_unreachable_then_move_rejected() {
+        let mut battle = Battle::new(HexGrid::new_with_obstacles(
+            6,
+            4,
+            vec![(4, 0), (4, 1), (4, 2), (5, 2)],
+        ));
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 0, 3)
+            .unwrap();
+        battle.start().unwrap();
+
+        assert_eq!(
+            battle.act(0, BattleAction::Move { col: 5, row: 0 }),
+            Err(BattleError::HexUnreachable(5, 0))
+        );
+        assert_eq!(battle.participants()[0].action_points, 6);
+    }
+
+    #[test]
+    fn when_target_hex_too_far_then_move_rejected() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(12, 4));
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 0, 3)
+            .unwrap();
+        battle.start().unwrap();
+
+        assert_eq!(
+            battle.act(0, BattleAction::Move { col: 7, row: 1 }),
+            Err(BattleError::HexUnreachable(7, 1))
+        );
+        assert!(battle.act(0, BattleAction::Move { col: 6, row: 1 }).is_ok());
+        assert_eq!(battle.participants()[0].action_points, 0);
+    }
+
+    #[test]
+    fn when_mounted_then_forest_costs_more() {
+        let mut grid = HexGrid::new_no_obstacles(6, 4);
+        grid.set_terrain(1, 1, Terrain::Forest);
+        let mut battle = Battle::new(grid);
+        let lancer = Fighter {
+            mobility: Mobility::Mounted,
+            ..fighter("en", Faction::En, sword())
+        };
+        battle.place(lancer, 0, 1).unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 3, 3)
+            .unwrap();
+        battle.start().unwrap();
+
+        let events = battle
+            .act(0, BattleAction::Move { col: 1, row: 1 })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![BattleEvent::Moved {
+                participant: 0,
+                path: vec![(1, 1)],
+                action_points_left: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn when_asked_for_movement_range_then_action_points_limit_it() {
+        let mut grid = HexGrid::new_no_obstacles(6, 4);
+        grid.set_terrain(1, 1, Terrain::Forest);
+        let mut battle = Battle::new(grid);
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 1, 0)
+            .unwrap();
+        battle.start().unwrap();
+        battle
+            .act(0, BattleAction::Move { col: 0, row: 2 })
+            .unwrap();
+        battle
+            .act(0, BattleAction::Move { col: 0, row: 1 })
+            .unwrap();
+
+        let range = battle.movement_range(0).unwrap();
+        let option = |col, row| MoveOption {
+            position: (col, row),
+            action_points: 2,
+        };
+        assert!(range.contains(&option(1, 1)));
+        assert!(range.contains(&option(0, 3)));
+        assert!(range.iter().all(|option| option.action_points <= 4));
+        assert!(range.iter().all(|option| option.position != (1, 0)));
+        assert!(range.iter().all(|option| option.position != (0, 1)));
+
+        battle
+            .act(0, BattleAction::Move { col: 1, row: 1 })
+            .unwrap();
+        assert_eq!(battle.participants()[0].action_points, 2);
+    }
+
+    #[test]
+    fn when_defender_in_cover_then_damage_reduced() {
+        let mut grid = HexGrid::new_no_obstacles(6, 4);
+        grid.set_terrain(1, 1, Terrain::Forest);
+        let mut battle = Battle::new(grid);
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 1, 1)
+            .unwrap();
+        battle.start().unwrap();
+
+        let events = battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+        assert_eq!(
+            events,
+            vec![BattleEvent::Attacked {
+                attacker: 0,
+                target: 1,
+                damage: 2,
+                target_health: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn when_obstacle_in_the_way_then_ranged_attack_rejected() {
+        let bow = Weapon {
+            action_points_to_use: 2,
+            basic_damage: 3,
+            range: 5,
+        };
+        let mut battle = Battle::new(HexGrid::new_with_obstacles(7, 5, vec![(3, 2)]));
+        battle.place(fighter("en", Faction::En, bow), 1, 2).unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 5, 2)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 4, 0)
+            .unwrap();
+        battle.start().unwrap();
+
+        assert_eq!(
+            battle.act(0, BattleAction::Attack { target: 1 }),
+            Err(BattleError::NoLineOfSight(1))
+        );
+        assert!(battle.act(0, BattleAction::Attack { target: 2 }).is_ok());
+    }
+
+    #[test]
+    fn when_hidden_behind_obstacle_then_not_visible_to_enemies() {
+        let mut battle = Battle::new(HexGrid::new_with_obstacles(12, 5, vec![(3, 2)]));
+        battle
+            .place(fighter("en", Faction::En, sword()), 1, 2)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 5, 2)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 4, 0)
+            .unwrap();
+        battle
+            .place(fighter("fr", Faction::Fr, sword()), 11, 0)
+            .unwrap();
+
+        assert_eq!(battle.visible_participants(Faction::En), vec![0, 2]);
+        assert_eq!(battle.visible_participants(Faction::Fr), vec![0, 1, 2, 3]);
+    }
+
+    fn bot(faction: Faction) -> Fighter {
+        Fighter::builder()
+            .combatant(Combatant::Bot {
+                bot_id: 1,
+                name: "bot".to_owned(),
+            })
+            .faction(faction)
+            .max_health(10)
+            .action_points(4)
+            .weapon(sword())
+            .build()
+    }
+
+    #[test]
+    fn when_bots_turn_then_bot_closes_in_and_hits() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(8, 4));
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle.place(bot(Faction::Bots), 3, 1).unwrap();
+        battle.start().unwrap();
+        battle.act(0, BattleAction::EndTurn).unwrap();
+
+        let events = battle.play_bot_turns();
+        assert!(matches!(
+            events[0],
+            BattleEvent::Moved { participant: 1, .. }
+        ));
+        assert!(events.contains(&BattleEvent::Attacked {
+            attacker: 1,
+            target: 0,
+            damage: 4,
+            target_health: 6,
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&BattleEvent::TurnStarted {
+                participant: 0,
+                round: 2
+            })
+        );
+        assert_eq!(battle.current_participant(), Some(0));
+    }
+
+    #[test]
+    fn when_players_turn_then_bots_dont_act() {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(8, 4));
+        battle
+            .place(fighter("en", Faction::En, sword()), 0, 1)
+            .unwrap();
+        battle.place(bot(Faction::Bots), 3, 1).unwrap();
+        battle.start().unwrap();
+
+        assert!(battle.play_bot_turns().is_empty());
+        assert_eq!(battle.player_participant(1), Some(0));
+        assert_eq!(battle.player_participant(2), None);
+        assert_eq!(battle.player_ids(), vec![1]);
+    }
+
+    #[test]
+    fn when_participant_hidden_then_its_events_filtered_out() {
+        let events = vec![
+            BattleEvent::TurnStarted {
+                participant: 2,
+                round: 1,
+            },
+            BattleEvent::Moved {
+                participant: 2,
+                path: vec![(1, 1)],
+                action_points_left: 5,
+            },
+            BattleEvent::Attacked {
+                attacker: 2,
+                target: 0,
+                damage: 3,
+                target_health: 7,
+            },
+            BattleEvent::Moved {
+                participant: 1,
+                path: vec![(2, 1)],
+                action_points_left: 5,
+            },
+            BattleEvent::Finished {
+                winner: Faction::Fr,
+            },
+        ];
+
+        assert_eq!(
+            visible_events(&events, &[0, 1]),
+            vec![events[2].clone(), events[3].clone(), events[4].clone()]
+        );
+        assert_eq!(visible_events(&events, &[0, 1, 2]), events);
+    }
+
+    #[test]
+    fn when_target_out_of_range_then_attack_rejected() {
+        let mut battle = duel();
+
+        assert_eq!(
+            battle.act(0, BattleAction::Attack { target: 1 }),
+            Err(BattleError::OutOfRange {
+                distance: 3,
+                range: 1
+            })
+        );
+        assert_eq!(
+            battle.act(0, BattleAction::Attack { target: 0 }),
+            Err(BattleError::InvalidTarget(0))
+        );
+    }
+
+    #[test]
+    fn when_action_points_exhausted_then_turn_passes() {
+        let mut battle = duel();
+        battle
+            .act(0, BattleAction::Move { col: 2, row: 1 })
+            .unwrap();
+        battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+
+        let events = battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+        assert_eq!(
+            events.last(),
+            Some(&BattleEvent::TurnStarted {
+                participant: 1,
+                round: 1
+            })
+        );
+        assert_eq!(battle.current_participant(), Some(1));
+        assert_eq!(battle.participants()[1].health, 2);
+        assert_eq!(battle.participants()[1].action_points, 6);
+    }
+
+    #[test]
+    fn when_last_enemy_dies_then_winner_resolved() {
+        let mut battle = duel();
+        battle
+            .act(0, BattleAction::Move { col: 2, row: 1 })
+            .unwrap();
+        battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+        battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+        battle.act(1, BattleAction::EndTurn).unwrap();
+        battle.act(0, BattleAction::Attack { target: 1 }).unwrap();
+
+        assert_eq!(battle.state(), BattleState::Finished(Faction::En));
+        assert_eq!(battle.round(), 2);
+        assert!(!battle.grid().hex(3, 1).unwrap().busy);
+        assert_eq!(
+            battle.act(0, BattleAction::EndTurn),
+            Err(BattleError::NotInProgress)
+        );
+
+        let records = battle.participant_records();
+        assert_eq!(records[0].outcome_damage, 10);
+        assert_eq!(records[0].gained_exp, 10);
+        assert!(records[0].gained_valor);
+        assert_eq!(records[1].income_damage, 10);
+        assert!(!records[1].gained_valor);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::app::battle::{
+    visible_events, Battle, BattleAction, BattleEvent, Faction, ParticipantId,
+};
+use crate::app::middleware::battle_middleware::LiveBattle;
+use crate::app::rewards::award_battle_rewards;
+use crate::app_state::AppState;
+use crate::error::{AppError, Result};
+use chrono::{NaiveDateTime, Utc};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// How often the battle registry is swept.
+pub const BATTLE_SWEEP_INTERVAL_SECS: u64 = 60;
+/// Battles nobody acted in for this long are dropped.
+pub const BATTLE_IDLE_TTL_SECS: i64 = 15 * 60;
+
+/// Battle the player takes part in, with its id.
+pub async fn current_battle(state: &AppState, player_id: i32) -> Result<(String, LiveBattle)> {
+    let battle_id = state
+        .cache_middleware
+        .get_session(player_id as i64)
+        .await?
+        .filter(|session| session.is_in_battle)
+        .and_then(|session| session.link_to_battle)
+        .ok_or(AppError::NotInBattle)?;
+    let battle = state
+        .battle_middleware
+        .get_battle(&battle_id)
+        .ok_or_else(|| AppError::BattleNotFound(battle_id.clone()))?;
+
+    Ok((battle_id, battle))
+}
+
+/// Plays the action of the player, then the bot turns up to the next player turn. Every
+/// faction gets the events it can see, a battle which is over gets finished. Returns the events
+/// the player can see.
+pub async fn play_turn(
+    state: &AppState,
+    battle_id: &str,
+    battle: &mut Battle,
+    player_id: i32,
+    action: BattleAction,
+) -> Result<Vec<BattleEvent>> {
+    let actor = battle
+        .player_participant(player_id)
+        .ok_or(AppError::Forbidden)?;
+    let seen_before = sight(battle);
+    let mut events = battle.act(actor, action)?;
+    events.extend(battle.play_bot_turns());
+
+    let faction = battle.participants()[actor].fighter.faction;
+    let mut seen: Vec<ParticipantId> = seen_before
+        .iter()
+        .filter(|(seen_by, _)| *seen_by == faction)
+        .flat_map(|(_, seen)| seen.iter().copied())
+        .collect();
+    seen.extend(battle.visible_participants(faction));
+
+    publish_battle_events(state, battle_id, battle, &events, seen_before);
+    if battle.winner().is_some() {
+        if let Err(e) = finish_battle(state, battle_id, battle).await {
+            error!("Can't finish battle {}, retrying later: {:?}", battle_id, e);
+        }
+    }
+
+    Ok(visible_events(&events, &seen))
+}
+
+/// Participants every faction of the battle sees.
+pub fn sight(battle: &Battle) -> Vec<(Faction, Vec<ParticipantId>)> {
+    battle
+        .factions()
+        .into_iter()
+        .map(|faction| (faction, battle.visible_participants(faction)))
+        .collect()
+}
+
+/// Sends every faction the events about the participants it saw before or sees after them.
+pub fn publish_battle_events(
+    state: &AppState,
+    battle_id: &str,
+    battle: &Battle,
+    events: &[BattleEvent],
+    seen_before: Vec<(Faction, Vec<ParticipantId>)>,
+) {
+    for (faction, mut seen) in seen_before {
+        seen.extend(battle.visible_participants(faction));
+        state.gateway_middleware.publish_battle_events(
+            battle_id,
+            faction,
+            &visible_events(events, &seen),
+        );
+    }
+}
+
+/// Stores the finished battle, rewards its players and drops it from the registry. A battle
+/// which can't be stored stays registered, so the sweeper retries it.
+pub async fn finish_battle(state: &AppState, battle_id: &str, battle: &mut Battle) -> Result<()> {
+    if battle.stored_id().is_none() {
+        let stored_id = state.battle_middleware.save_finished_battle(battle).await?;
+        battle.mark_stored(stored_id);
+    }
+    award_battle_rewards(state, battle_id, battle).await?;
+    state.battle_middleware.remove_battle(battle_id);
+
+    Ok(())
+}
+
+/// Periodically finishes the battles which are over but couldn't be finished and drops the
+/// battles nobody acted in for [`BATTLE_IDLE_TTL_SECS`].
+pub async fn run_battle_sweeper(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(BATTLE_SWEEP_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        sweep_battles(&state).await;
+    }
+}
+
+async fn sweep_battles(state: &AppState) {
+    let now = Utc::now().naive_utc();
+    for (battle_id, battle) in state.battle_middleware.battles() {
+        let mut battle = battle.lock().await;
+        if battle.winner().is_some() {
+            if let Err(e) = finish_battle(state, &battle_id, &mut battle).await {
+                error!("Can't finish battle {}: {:?}", battle_id, e);
+            }
+        } else if is_idle(&battle, now) {
+            debug!("Dropping idle battle {}", battle_id);
+            state.battle_middleware.remove_battle(&battle_id);
+        }
+    }
+}
+
+/// Whether nobody acted in the battle for [`BATTLE_IDLE_TTL_SECS`].
+pub fn is_idle(battle: &Battle, now: NaiveDateTime) -> bool {
+    battle
+        .acted_at()
+        .is_none_or(|acted_at| (now - acted_at).num_seconds() >= BATTLE_IDLE_TTL_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::battle::{Battle, Combatant, Faction, Fighter, Weapon};
+    use crate::app::combat::{is_idle, BATTLE_IDLE_TTL_SECS};
+    use crate::app::grid::HexGrid;
+    use chrono::{Duration, Utc};
+
+    fn fighter(name: &str, faction: Faction) -> Fighter {
+        Fighter::builder()
+            .combatant(Combatant::Player {
+                player_id: 1,
+                nickname: name.to_owned(),
+            })
+            .faction(faction)
+            .max_health(10)
+            .action_points(6)
+            .weapon(Weapon {
+                action_points_to_use: 2,
+                basic_damage: 4,
+                range: 1,
+            })
+            .build()
+    }
+
+    fn duel() -> Battle {
+        let mut battle = Battle::new(HexGrid::new_no_obstacles(6, 4));
+        battle.place(fighter("en", Faction::En), 0, 1).unwrap();
+        battle.place(fighter("fr", Faction::Fr), 3, 1).unwrap();
+        battle.start().unwrap();
+        battle
+    }
+
+    #[test]
+    fn when_battle_just_started_then_it_is_not_idle() {
+        let battle = duel();
+
+        assert!(!is_idle(&battle, Utc::now().naive_utc()));
+    }
+
+    #[test]
+    fn when_nobody_acted_for_the_ttl_then_battle_is_idle() {
+        let battle = duel();
+        let later = Utc::now().naive_utc() + Duration::seconds(BATTLE_IDLE_TTL_SECS);
+
+        assert!(is_idle(&battle, later));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::app::battle::{visible_events, Battle, BattleError, BattleEvent, Faction, Fighter};
+use crate::app::combat::sight;
+use crate::app::grid::HexGrid;
+use crate::app::rewards::player_stats;
+use crate::app_state::AppState;
+use crate::error::Result;
+use crate::model::battle::Bot;
+use crate::model::item::WeaponItem;
+use crate::model::r#static::MapLocation;
+use chrono::Utc;
+use rand::Rng;
+use std::time::Duration;
+use tracing::error;
+
+/// How often the players staying at a location may be attacked.
+pub const ENCOUNTER_ROLL_INTERVAL_SECS: u64 = 60;
+/// Bots up to this many levels away from the encounter level may show up.
+pub const ENCOUNTER_LEVEL_SPREAD: i32 = 2;
+pub const MAX_ENCOUNTER_BOTS: usize = 4;
+pub const ENCOUNTER_GRID_WIDTH: usize = 10;
+pub const ENCOUNTER_GRID_HEIGHT: usize = 8;
+
+/// Bots are as strong as the player, but never weaker than the location.
+pub fn encounter_level(player_level: i32, location_difficulty: i32) -> i32 {
+    player_level.max(location_difficulty)
+}
+
+/// Rolls whether the player gets attacked at the location and by which bots, no bots means no
+/// encounter. The chance is the aggression of the location, harder locations send more bots.
+pub fn roll_encounter<R: Rng>(
+    rng: &mut R,
+    location: &MapLocation,
+    player_level: i32,
+    bots: &[(Bot, WeaponItem)],
+) -> Vec<(Bot, WeaponItem)> {
+    let chance = f64::from(location.aggression_prob).clamp(0.0, 1.0);
+    if !rng.random_bool(chance) {
+        return vec![];
+    }
+
+    let level = encounter_level(player_level, location.location_difficulty);
+    let candidates: Vec<&(Bot, WeaponItem)> = bots
+        .iter()
+        .filter(|(bot, _)| (bot.level - level).abs() <= ENCOUNTER_LEVEL_SPREAD)
+        .collect();
+    if candidates.is_empty() {
+        return vec![];
+    }
+
+    let max_count = (location.location_difficulty.max(1) as usize).min(MAX_ENCOUNTER_BOTS);
+    (0..rng.random_range(1..=max_count))
+        .map(|_| candidates[rng.random_range(0..candidates.len())].clone())
+        .collect()
+}
+
+/// Sets up the fight of the player against the bots: the player on the left edge of the grid,
+/// the bots on the right one. The player has the first turn.
+pub fn encounter_battle(
+    player: Fighter,
+    bots: &[(Bot, WeaponItem)],
+) -> std::result::Result<(Battle, Vec<BattleEvent>), BattleError> {
+    let mut battle = Battle::new(HexGrid::new_no_obstacles(
+        ENCOUNTER_GRID_WIDTH,
+        ENCOUNTER_GRID_HEIGHT,
+    ));
+    battle.place(player, 0, ENCOUNTER_GRID_HEIGHT as i32 / 2)?;
+    for (row, (bot, weapon)) in bots.iter().take(ENCOUNTER_GRID_HEIGHT).enumerate() {
+        battle.place(
+            Fighter::from_bot(bot, weapon),
+            ENCOUNTER_GRID_WIDTH as i32 - 1,
+            row as i32,
+        )?;
+    }
+    let events = battle.start()?;
+
+    Ok((battle, events))
+}
+
+/// Rolls an encounter for the player at the location. If bots show up, a PvE battle starts and
+/// the session gets linked to it. Returns the id of the battle.
+///
+/// Only the players online and out of battle can be attacked.
+pub async fn roll_encounter_for(
+    state: &AppState,
+    player_id: i32,
+    location_id: i32,
+) -> Result<Option<String>> {
+    let Some(session) = state.cache_middleware.get_session(player_id as i64).await? else {
+        return Ok(None);
+    };
+    if state.battle_middleware.is_in_live_battle(&session) {
+        return Ok(None);
+    }
+    let Some(location) = state
+        .static_table_middleware
+        .get_map_location(location_id)
+        .await?
+    else {
+        return Ok(None);
+    };
+    if location.aggression_prob <= 0.0 {
+        return Ok(None);
+    }
+
+    let player_level = session.level as i32;
+    let level = encounter_level(player_level, location.location_difficulty);
+    let bots = state
+        .battle_middleware
+        .get_bots_by_level(
+            level - ENCOUNTER_LEVEL_SPREAD,
+            level + ENCOUNTER_LEVEL_SPREAD,
+        )
+        .await?;
+    let bots = roll_encounter(&mut rand::rng(), &location, player_level, &bots);
+    if bots.is_empty() {
+        return Ok(None);
+    }
+
+    let stats = player_stats(state, player_id).await?;
+    let vitals = state
+        .cache_middleware
+        .get_vitals(player_id as i64, &stats, Utc::now().timestamp())
+        .await?;
+    if vitals.health <= 0 {
+        return Ok(None);
+    }
+    let player = Fighter::from_player(
+        player_id,
+        session.nickname.clone(),
+        Faction::En,
+        &stats,
+        &vitals,
+    );
+    let (battle, events) = encounter_battle(player, &bots)?;
+    let seen = sight(&battle);
+    let started = visible_events(&events, &battle.visible_participants(Faction::En));
+
+    // The player may have got into another battle or logged out in the meantime.
+    let battle_id = state.battle_middleware.register_battle(battle);
+    let linked = state
+        .cache_middleware
+        .update_session(player_id as i64, |session| {
+            if state.battle_middleware.is_in_live_battle(session) {
+                return false;
+            }
+            session.is_in_battle = true;
+            session.link_to_battle = Some(battle_id.clone());
+            true
+        })
+        .await;
+    if !matches!(linked, Ok(Some(_))) {
+        state.battle_middleware.remove_battle(&battle_id);
+        return linked.map(|_| None);
+    }
+
+    state
+        .gateway_middleware
+        .publish_battle_started(session.id, &battle_id, &started);
+    for (faction, seen) in seen {
+        state.gateway_middleware.publish_battle_events(
+            &battle_id,
+            faction,
+            &visible_events(&events, &seen),
+        );
+    }
+
+    Ok(Some(battle_id))
+}
+
+/// Periodically rolls encounters for the players staying at the map locations. The travelling
+/// players roll at the locations they pass by and on arrival.
+pub async fn run_encounter_roller(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(ENCOUNTER_ROLL_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = roll_encounters(&state).await {
+            error!("Can't roll encounters: {:?}", e);
+        }
+    }
+}
+
+async fn roll_encounters(state: &AppState) -> Result<()> {
+    for location in state.static_table_middleware.get_map_locations().await? {
+        if location.aggression_prob <= 0.0 {
+            continue;
+        }
+
+        for player in state
+            .cache_middleware
+            .get_players_in_zone(location.id as i64)
+            .await?
+        {
+            if state
+                .cache_middleware
+                .get_travel(player.id)
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+            if let Err(e) = roll_encounter_for(state, player.id as i32, location.id).await {
+                error!("Can't roll an encounter for player {}: {:?}", player.id, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::battle::{BattleEvent, BattleState, Combatant, Faction, Fighter, BARE_HANDS};
+    use crate::app::encounter::{encounter_battle, roll_encounter};
+    use crate::model::battle::Bot;
+    use crate::model::item::WeaponItem;
+    use crate::model::r#static::MapLocation;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn location(location_difficulty: i32, aggression_prob: f32) -> MapLocation {
+        MapLocation {
+            id: 1,
+            name: "Forest".to_owned(),
+            location: 1,
+            location_difficulty,
+            movement_accel: 1.0,
+            aggression_prob,
+        }
+    }
+
+    fn bot(id: i32, level: i32) -> (Bot, WeaponItem) {
+        (
+            Bot {
+                id,
+                weapon_id: 1,
+                name: format!("Bot {id}"),
+                level,
+                action_points: 4,
+            },
+            WeaponItem {
+                id: 1,
+                item_id: 1,
+                action_points_to_use: 2,
+                basic_damage: 3,
+                range: 1,
+            },
+        )
+    }
+
+    fn player() -> Fighter {
+        Fighter::builder()
+            .combatant(Combatant::Player {
+                player_id: 1,
+                nickname: "player".to_owned(),
+            })
+            .faction(Faction::En)
+            .max_health(30)
+            .action_points(6)
+            .weapon(BARE_HANDS)
+            .build()
+    }
+
+    #[test]
+    fn when_location_peaceful_then_never_attacked() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bots = vec![bot(1, 1)];
+
+        assert!((0..100).all(|_| roll_encounter(&mut rng, &location(1, 0.0), 1, &bots).is_empty()));
+    }
+
+    #[test]
+    fn when_encounter_rolled_then_bots_match_its_level() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let bots = vec![bot(1, 1), bot(2, 5), bot(3, 12)];
+
+        for _ in 0..100 {
+            let spawned = roll_encounter(&mut rng, &location(4, 1.0), 5, &bots);
+            assert!(!spawned.is_empty());
+            assert!(spawned.len() <= 4);
+            assert!(spawned.iter().all(|(bot, _)| bot.id == 2));
+        }
+    }
+
+    #[test]
+    fn when_no_suitable_bots_then_no_encounter() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        assert!(roll_encounter(&mut rng, &location(1, 1.0), 20, &[bot(1, 1)]).is_empty());
+    }
+
+    #[test]
+    fn when_location_easy_then_single_bot_attacks() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let bots = vec![bot(1, 1), bot(2, 2)];
+
+        assert!((0..100).all(|_| roll_encounter(&mut rng, &location(1, 1.0), 1, &bots).len() == 1));
+    }
+
+    #[test]
+    fn when_encounter_battle_starts_then_player_acts_first() {
+        let (battle, events) = encounter_battle(player(), &[bot(1, 1), bot(2, 1)]).unwrap();
+
+        assert_eq!(battle.state(), BattleState::InProgress);
+        assert_eq!(battle.current_participant(), Some(0));
+        assert_eq!(battle.participants().len(), 3);
+        assert!(battle.participants()[1..]
+            .iter()
+            .all(|p| p.fighter.faction == Faction::Bots));
+        assert_eq!(
+            events,
+            vec![BattleEvent::TurnStarted {
+                participant: 0,
+                round: 1
+            }]
+        );
+    }
+}
@@ -1,15 +1,23 @@
+use crate::app::battle::BattleError;
+use crate::app::validation::FieldError;
+use axum::http::header::RETRY_AFTER;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum::Json;
 use bb8::RunError;
+use chrono::NaiveDateTime;
 use deadpool_diesel::sqlite::PoolError;
 use deadpool_diesel::InteractError;
 use prost::DecodeError;
 use redis::RedisError;
+use serde::Serialize;
+use strum::IntoStaticStr;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, AppError>;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum AppError {
     #[error("Cannot log in due to invalid credentials")]
     MissedCredentials,
@@ -19,12 +27,56 @@ pub enum AppError {
     InvalidToken,
     #[error("Invalid token was generate during login")]
     TokenCreation,
+    #[error("The action is not allowed for this player")]
+    Forbidden,
+    #[error("Player {0} is banned")]
+    PlayerBanned(String),
+    #[error("The player is muted in chat until {0}")]
+    PlayerMuted(NaiveDateTime),
+    #[error("Player {0} has to verify the email first")]
+    EmailNotVerified(String),
 
     //region entity handlers errors
     #[error("Cannot register a new player with the nickname {0}")]
     PlayerCannotRegister(String),
+    #[error("The request has invalid fields")]
+    Validation(Vec<FieldError>),
+    #[error("{} is already taken", .0.field)]
+    AlreadyTaken(FieldError),
     #[error("Player with nickname {0} not found")]
     PlayerNotFound(String),
+    #[error("Can't whisper to {0}, no such player")]
+    RecipientNotFound(String),
+    #[error("The player is not in zone {0}")]
+    NotInZone(i64),
+    #[error("Chat message rejected: {0}")]
+    ChatMessageRejected(String),
+    #[error("Too many chat messages, slow down")]
+    ChatThrottled,
+    #[error("Too many requests, retry in {0} seconds")]
+    RateLimited(u64),
+    #[error("Inventory item {0} not found")]
+    ItemNotFound(i32),
+    #[error("Item requirements are not met: {0}")]
+    ItemRequirementsNotMet(String),
+    #[error("Attributes can't be changed: {0}")]
+    AttributesRejected(String),
+    #[error("Class or spec can't be changed: {0}")]
+    ClassRejected(String),
+    #[error("Guild {0} not found")]
+    GuildNotFound(i32),
+    #[error("Guild action rejected: {0}")]
+    GuildRejected(String),
+    #[error("Map location {0} not found")]
+    MapLocationNotFound(i32),
+    #[error("Travel rejected: {0}")]
+    TravelRejected(String),
+    #[error("Battle action rejected: {0}")]
+    Battle(#[from] BattleError),
+    #[error("Battle {0} not found")]
+    BattleNotFound(String),
+    #[error("The player is not in a battle")]
+    NotInBattle,
     //endregion
 
     //region database errors
@@ -40,39 +92,101 @@ pub enum AppError {
     BB8CacheError(#[from] RunError<RedisError>),
     #[error("Can't cooperate with cache")]
     EntryDecodeError(#[from] DecodeError),
+    #[error("Can't deliver mail: {0}")]
+    MailDelivery(String),
+    #[error("The session of player {0} kept changing, the update is given up")]
+    SessionConflict(i64),
     //endregion
     #[error("Can't parse request body: {0}")]
     BodyParsingError(String),
 }
 
-// TODO: remove the detailed error explanation from IntoResponse. Just StatusCode is enough.
-//  Also, all errors leading to 500 should not be exposed.
-impl IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
+impl From<diesel::result::Error> for AppError {
+    fn from(e: diesel::result::Error) -> Self {
+        AppError::QueryError(e.to_string())
+    }
+}
+
+/// Body of every error response. `code` is the snake_case name of the error for clients to
+/// match on, `fields` lists the rejected request fields, if any.
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
         match self {
-            Self::PlayerCannotRegister(_) => (StatusCode::OK, self.to_string()).into_response(),
-            Self::WrongCredentials(_) | Self::PlayerNotFound(_) => {
-                (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
-            }
-            Self::MissedCredentials | Self::TokenCreation => {
-                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
-            }
-            Self::QueryError(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
-            Self::TransactionError(e) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-            }
-            AppError::EntryDecodeError(_)
+            Self::WrongCredentials(_) | Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::MissedCredentials
+            | Self::Battle(_)
+            | Self::ChatMessageRejected(_)
+            | Self::ItemRequirementsNotMet(_)
+            | Self::AttributesRejected(_)
+            | Self::ClassRejected(_)
+            | Self::GuildRejected(_)
+            | Self::TravelRejected(_)
+            | Self::BodyParsingError(_) => StatusCode::BAD_REQUEST,
+            Self::Forbidden
+            | Self::PlayerBanned(_)
+            | Self::PlayerMuted(_)
+            | Self::EmailNotVerified(_)
+            | Self::NotInZone(_) => StatusCode::FORBIDDEN,
+            Self::PlayerNotFound(_)
+            | Self::ItemNotFound(_)
+            | Self::RecipientNotFound(_)
+            | Self::GuildNotFound(_)
+            | Self::MapLocationNotFound(_)
+            | Self::BattleNotFound(_)
+            | Self::NotInBattle => StatusCode::NOT_FOUND,
+            Self::AlreadyTaken(_) | Self::SessionConflict(_) => StatusCode::CONFLICT,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::ChatThrottled | Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::PlayerCannotRegister(_)
+            | Self::TokenCreation
+            | Self::QueryError(_)
+            | Self::PoolError(_)
+            | Self::TransactionError(_)
             | Self::CacheError(_)
             | Self::BB8CacheError(_)
-            | Self::InvalidToken
-            | Self::PoolError(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                StatusCode::INTERNAL_SERVER_ERROR.to_string(),
-            )
-                .into_response(),
-            Self::BodyParsingError(_) => StatusCode::BAD_REQUEST.into_response(),
+            | Self::EntryDecodeError(_)
+            | Self::MailDelivery(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        // Internal errors are logged, not exposed.
+        let message = if status.is_server_error() {
+            tracing::error!("Request failed: {self:?}");
+            "Internal server error".to_owned()
+        } else {
+            self.to_string()
+        };
+        let retry_after = match &self {
+            Self::RateLimited(retry_after) => Some(retry_after.to_string()),
+            _ => None,
+        };
+        let code = (&self).into();
+        let fields = match self {
+            Self::Validation(fields) => fields,
+            Self::AlreadyTaken(field) => vec![field],
+            _ => vec![],
+        };
+
+        let body = Json(ErrorResponse {
+            code,
+            message,
+            fields,
+        });
+        match retry_after {
+            Some(retry_after) => (status, [(RETRY_AFTER, retry_after)], body).into_response(),
+            None => (status, body).into_response(),
         }
     }
 }
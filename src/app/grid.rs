@@ -1,3 +1,4 @@
+use bon::Builder;
 use grid::{grid, Grid};
 use lazy_static::lazy_static;
 use std::cmp::Ordering;
@@ -10,6 +11,52 @@ lazy_static! {
     ];
 }
 
+// region Terrain
+
+/// How a unit gets around, it decides what the terrain costs to cross.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub enum Mobility {
+    #[default]
+    Foot,
+    Mounted,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub enum Terrain {
+    #[default]
+    Plain,
+    Forest,
+    Swamp,
+    Water,
+    Road,
+    Hill,
+}
+
+impl Terrain {
+    /// Cost of entering a hex of the terrain, `None` if the unit can't enter it at all.
+    pub fn move_cost(self, mobility: Mobility) -> Option<i32> {
+        match (self, mobility) {
+            (Terrain::Plain | Terrain::Road, _) => Some(1),
+            (Terrain::Forest, Mobility::Foot) => Some(2),
+            (Terrain::Forest, Mobility::Mounted) => Some(3),
+            (Terrain::Hill, _) => Some(2),
+            (Terrain::Swamp, Mobility::Foot) => Some(3),
+            (Terrain::Swamp, Mobility::Mounted) | (Terrain::Water, _) => None,
+        }
+    }
+
+    /// Damage a unit standing on the terrain doesn't take from every hit.
+    pub fn defense_bonus(self) -> i32 {
+        match self {
+            Terrain::Forest => 2,
+            Terrain::Hill => 1,
+            Terrain::Plain | Terrain::Swamp | Terrain::Water | Terrain::Road => 0,
+        }
+    }
+}
+
+// endregion Terrain
+
 // region Hex
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
@@ -18,26 +65,26 @@ pub struct Hex {
     pub row: i32,
     pub obstacle: bool,
     pub busy: bool,
+    pub terrain: Terrain,
 }
 
 impl PartialOrd for Hex {
-    #[allow(clippy::non_canonical_partial_ord_impl)]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Hex {
+    fn cmp(&self, other: &Self) -> Ordering {
         let cmp_result = self.col + self.row - other.col - other.row;
         if cmp_result == 0 {
-            return Some(Ordering::Equal);
+            return Ordering::Equal;
         }
-        Some(if cmp_result > 0 {
+        if cmp_result > 0 {
             Ordering::Greater
         } else {
             Ordering::Less
-        })
-    }
-}
-
-impl Ord for Hex {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        }
     }
 }
 
@@ -48,6 +95,7 @@ impl Hex {
             row,
             obstacle: false,
             busy: false,
+            terrain: Terrain::Plain,
         }
     }
 
@@ -57,7 +105,17 @@ impl Hex {
             row,
             obstacle,
             busy,
+            terrain: Terrain::Plain,
+        }
+    }
+
+    /// Cost for the unit to enter the hex, obstacles and busy hexes can't be entered.
+    pub fn move_cost(&self, mobility: Mobility) -> Option<i32> {
+        if self.obstacle || self.busy {
+            return None;
         }
+
+        self.terrain.move_cost(mobility)
     }
 }
 
@@ -72,26 +130,25 @@ pub struct HexWithPriority<'a> {
 }
 
 impl PartialOrd for HexWithPriority<'_> {
-    #[allow(clippy::non_canonical_partial_ord_impl)]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HexWithPriority<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
         if self.priority == other.priority {
-            return Some(Ordering::Equal);
+            return Ordering::Equal;
         }
 
         if self.priority < other.priority {
-            Some(Ordering::Greater)
+            Ordering::Greater
         } else {
-            Some(Ordering::Less)
+            Ordering::Less
         }
     }
 }
 
-impl Ord for HexWithPriority<'_> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
 impl<'a> HexWithPriority<'a> {
     pub fn new(hex: &'a Hex, priority: i32) -> Self {
         HexWithPriority { hex, priority }
@@ -100,12 +157,157 @@ impl<'a> HexWithPriority<'a> {
 
 // endregion HexWithPriority
 
+// region Path
+
+/// Hexes to go through, excluding the starting one, and the total cost of entering them.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Path<'a> {
+    pub hexes: Vec<&'a Hex>,
+    pub cost: i32,
+}
+
+/// Limits of a path search, so an enclosed target can't make it scan a huge grid.
+#[derive(Builder, Clone, Copy, Debug)]
+pub struct PathBudget {
+    /// Paths costing more are not considered.
+    #[builder(default = i32::MAX)]
+    pub max_cost: i32,
+    /// The search gives up after expanding this many hexes.
+    #[builder(default = DEFAULT_MAX_PATH_NODES)]
+    pub max_nodes: usize,
+}
+
+pub const DEFAULT_MAX_PATH_NODES: usize = 4096;
+
+/// Result of [`HexGrid::reachable`]. Keeps the way to every hex, so a path to any of them
+/// doesn't need another search.
+#[derive(Debug)]
+pub struct Reachable<'a> {
+    grid: &'a HexGrid,
+    from: Option<&'a Hex>,
+    cost_so_far: HashMap<&'a Hex, i32>,
+    came_from: HashMap<&'a Hex, &'a Hex>,
+}
+
+impl<'a> Reachable<'a> {
+    /// Cost of getting to the hex, `None` if it can't be reached. The starting hex costs 0.
+    pub fn cost(&self, col: i32, row: i32) -> Option<i32> {
+        self.cost_so_far.get(self.grid.grid_hex(col, row)?).copied()
+    }
+
+    /// The reachable hexes with their costs, the cheapest first. The starting hex is not
+    /// included.
+    pub fn hexes(&self) -> Vec<(&'a Hex, i32)> {
+        let mut hexes: Vec<(&Hex, i32)> = self
+            .cost_so_far
+            .iter()
+            .filter(|(hex, _)| Some(**hex) != self.from)
+            .map(|(hex, cost)| (*hex, *cost))
+            .collect();
+        hexes.sort_by_key(|(hex, cost)| (*cost, hex.row, hex.col));
+
+        hexes
+    }
+
+    /// The cheapest path to a reachable hex, excluding the starting one.
+    pub fn path_to(&self, col: i32, row: i32) -> Option<Path<'a>> {
+        let to = self.grid.grid_hex(col, row)?;
+        let cost = *self.cost_so_far.get(to)?;
+
+        Some(Path {
+            hexes: HexGrid::reconstruct_path(&self.came_from, self.from?, to),
+            cost,
+        })
+    }
+}
+
+// endregion Path
+
+/// Shift of the lines tested for the line of sight, small enough to never change a hex the
+/// line crosses, and summing up to 0 to keep the cube coordinates valid.
+const LINE_NUDGE: (f32, f32, f32) = (1e-3, 2e-3, -3e-3);
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Cube {
     pub x: i32,
     pub y: i32,
     pub z: i32,
 }
 
+impl Cube {
+    fn add(self, other: Cube) -> Cube {
+        Cube {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    fn sub(self, other: Cube) -> Cube {
+        self.add(other.scale(-1))
+    }
+
+    fn scale(self, factor: i32) -> Cube {
+        Cube {
+            x: self.x * factor,
+            y: self.y * factor,
+            z: self.z * factor,
+        }
+    }
+
+    /// Turns the cube around the origin by sixths of a circle, in the order of [`HexDirection`].
+    fn rotate(self, steps: i32) -> Cube {
+        (0..steps.rem_euclid(6)).fold(self, |cube, _| Cube {
+            x: -cube.z,
+            y: -cube.x,
+            z: -cube.y,
+        })
+    }
+
+    /// Mirrors the cube across the axis going from the origin to the east and the west.
+    fn reflect(self) -> Cube {
+        Cube {
+            x: -self.z,
+            y: -self.y,
+            z: -self.x,
+        }
+    }
+
+    fn length(self) -> i32 {
+        (self.x.abs() + self.y.abs() + self.z.abs()) / 2
+    }
+}
+
+// region Shapes
+
+/// The six directions to the neighbours of a hex, in the order of `DIRECTIONS`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum HexDirection {
+    East,
+    NorthEast,
+    NorthWest,
+    West,
+    SouthWest,
+    SouthEast,
+}
+
+impl HexDirection {
+    pub const ALL: [HexDirection; 6] = [
+        HexDirection::East,
+        HexDirection::NorthEast,
+        HexDirection::NorthWest,
+        HexDirection::West,
+        HexDirection::SouthWest,
+        HexDirection::SouthEast,
+    ];
+
+    fn cube(self) -> Cube {
+        Cube { x: 1, y: 0, z: -1 }.rotate(self as i32)
+    }
+}
+
+// endregion Shapes
+
 pub struct FloatCube {
     pub x: f32,
     pub y: f32,
@@ -129,11 +331,13 @@ impl HexGrid {
                     row: j,
                     obstacle: obstacles.contains(&(i, j)),
                     busy: false,
+                    terrain: Terrain::Plain,
                 })
             }
         }
+        // Hexes are pushed column by column, so the inner grid is addressed as (col, row).
         HexGrid {
-            grid: Grid::from_vec(vec, width),
+            grid: Grid::from_vec(vec, height),
             width,
             height,
         }
@@ -158,6 +362,36 @@ impl HexGrid {
         self.grid.get(col, row)
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Marks the hex as occupied (or released) by a unit. Returns `false` if the hex doesn't exist.
+    pub fn set_busy(&mut self, col: usize, row: usize, busy: bool) -> bool {
+        match self.grid.get_mut(col, row) {
+            Some(hex) => {
+                hex.busy = busy;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the terrain of the hex. Returns `false` if the hex doesn't exist.
+    pub fn set_terrain(&mut self, col: usize, row: usize, terrain: Terrain) -> bool {
+        match self.grid.get_mut(col, row) {
+            Some(hex) => {
+                hex.terrain = terrain;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn are_neighbours(hex1: &Hex, hex2: &Hex) -> bool {
         let parity: usize = (hex1.row & 1) as usize;
         let directions = &DIRECTIONS[parity];
@@ -178,7 +412,7 @@ impl HexGrid {
     pub fn pick_all_enterable_neighbours(&self, hex: &Hex) -> Vec<&Hex> {
         (0..6)
             .map(|i: usize| -> Option<&Hex> { self.pick_neighbour(hex, i) })
-            .filter(|hex| -> bool { hex.is_some() && !hex.unwrap().obstacle })
+            .filter(|hex| -> bool { hex.is_some_and(|hex| !hex.obstacle && !hex.busy) })
             .map(|hex| -> &Hex { hex.unwrap() })
             .collect()
     }
@@ -210,6 +444,68 @@ impl HexGrid {
         self.cube_distance(x, y, z)
     }
 
+    /// Whether a unit standing on `from` sees `to`. Obstacles between them block the view,
+    /// units don't. A line passing exactly along the edge of an obstacle is not blocked.
+    pub fn line_of_sight(&self, from: &Hex, to: &Hex) -> bool {
+        let (Some(from), Some(to)) = (
+            self.grid_hex(from.col, from.row),
+            self.grid_hex(to.col, to.row),
+        ) else {
+            return false;
+        };
+
+        [LINE_NUDGE, (-LINE_NUDGE.0, -LINE_NUDGE.1, -LINE_NUDGE.2)]
+            .into_iter()
+            .any(|nudge| {
+                let line = self.nudged_line(from, to, nudge);
+                line.iter()
+                    .skip(1)
+                    .take(line.len().saturating_sub(2))
+                    .all(|cube| {
+                        self.cube_hex(cube)
+                            .is_some_and(|hex| !Self::blocks_sight(hex))
+                    })
+            })
+    }
+
+    /// Every hex within the radius a unit standing on `from` sees, including its own one.
+    pub fn field_of_view(&self, from: &Hex, radius: i32) -> Vec<&Hex> {
+        let Some(from) = self.grid_hex(from.col, from.row) else {
+            return vec![];
+        };
+
+        self.grid
+            .iter()
+            .filter(|hex| self.distance(from, hex) <= radius && self.line_of_sight(from, hex))
+            .collect()
+    }
+
+    fn blocks_sight(hex: &Hex) -> bool {
+        hex.obstacle
+    }
+
+    /// Cubes of the straight line between two hexes, shifted a bit off the edges of the hexes
+    /// so rounding doesn't have to pick between two of them.
+    fn nudged_line(&self, from: &Hex, to: &Hex, nudge: (f32, f32, f32)) -> Vec<Cube> {
+        let cube_from = self.offset_to_cube(from);
+        let cube_to = self.offset_to_cube(to);
+        let distance = self.distance(from, to);
+        if distance == 0 {
+            return vec![cube_from];
+        }
+
+        (0..distance + 1)
+            .map(|i| {
+                let cube = self.cube_lerp(&cube_from, &cube_to, i as f32 / distance as f32);
+                self.cube_round(FloatCube {
+                    x: cube.x + nudge.0,
+                    y: cube.y + nudge.1,
+                    z: cube.z + nudge.2,
+                })
+            })
+            .collect()
+    }
+
     pub fn direct_path(&self, from: &Hex, to: &Hex) -> Vec<&Hex> {
         let mut path: Vec<Cube> = vec![];
 
@@ -225,50 +521,142 @@ impl HexGrid {
         path.iter().map(|cube| self.cube_to_offset(cube)).collect()
     }
 
-    pub fn smart_path<'a>(&'a self, from: &'a Hex, to: &'a Hex) -> Vec<&'a Hex> {
+    /// Cheapest path for the unit over the terrain, see [`HexGrid::find_path`].
+    pub fn smart_path<'a>(&'a self, from: &Hex, to: &Hex, mobility: Mobility) -> Option<Path<'a>> {
+        self.find_path(from, to, PathBudget::builder().build(), |hex| {
+            hex.move_cost(mobility)
+        })
+    }
+
+    /// Cost for the unit to walk from one hex to the other, unlike [`HexGrid::distance`] it
+    /// goes around obstacles and counts the terrain. `None` if the unit can't get there.
+    pub fn move_distance(&self, from: &Hex, to: &Hex, mobility: Mobility) -> Option<i32> {
+        self.smart_path(from, to, mobility).map(|path| path.cost)
+    }
+
+    /// A* search of the cheapest path. `move_cost` is the cost of entering a hex, `None` for the
+    /// hexes which can't be entered; costs below 1 count as 1. The path excludes the starting
+    /// hex. Returns `None` if the target can't be reached within the budget.
+    pub fn find_path<'a, F>(
+        &'a self,
+        from: &Hex,
+        to: &Hex,
+        budget: PathBudget,
+        move_cost: F,
+    ) -> Option<Path<'a>>
+    where
+        F: Fn(&Hex) -> Option<i32>,
+    {
+        let from = self.grid_hex(from.col, from.row)?;
+        let to = self.grid_hex(to.col, to.row)?;
+
         let mut frontier: BinaryHeap<HexWithPriority> = BinaryHeap::new();
-        let mut path: Vec<&Hex> = vec![];
         let mut came_from: HashMap<&Hex, &Hex> = HashMap::new();
-        let mut cost_map: HashMap<&Hex, i32> = HashMap::new();
-        let mut end_reached = false;
+        let mut cost_so_far: HashMap<&Hex, i32> = HashMap::from([(from, 0)]);
+        let mut closed: HashSet<&Hex> = HashSet::new();
 
         frontier.push(HexWithPriority::new(from, 0));
+        while let Some(candidate) = frontier.pop() {
+            let current = candidate.hex;
+            let current_cost = cost_so_far[current];
+            if current == to {
+                return Some(Path {
+                    hexes: Self::reconstruct_path(&came_from, from, to),
+                    cost: current_cost,
+                });
+            }
+            if !closed.insert(current) {
+                continue;
+            }
+            if closed.len() > budget.max_nodes {
+                return None;
+            }
+
+            for next in self.pick_all_neighbours(current) {
+                let Some(step_cost) = move_cost(next) else {
+                    continue;
+                };
+                let new_cost = current_cost.saturating_add(step_cost.max(1));
+                if new_cost > budget.max_cost {
+                    continue;
+                }
+                if cost_so_far.get(next).is_none_or(|cost| new_cost < *cost) {
+                    cost_so_far.insert(next, new_cost);
+                    came_from.insert(next, current);
+                    frontier.push(HexWithPriority::new(
+                        next,
+                        new_cost.saturating_add(self.distance(next, to)),
+                    ));
+                }
+            }
+        }
+
+        None
+    }
 
-        println!("Start: {:?}", from);
-        println!("End: {:?}", to);
-        while !frontier.is_empty() {
-            let candidate = frontier.pop().unwrap();
-            if candidate.hex.eq(to) {
-                end_reached = true;
-                break;
+    /// Every hex the unit can reach from `from` spending at most `max_cost`, with the cost of
+    /// the cheapest way there. Obstacles, busy hexes and terrain are respected.
+    pub fn reachable<'a>(&'a self, from: &Hex, max_cost: i32, mobility: Mobility) -> Reachable<'a> {
+        let mut reachable = Reachable {
+            grid: self,
+            from: None,
+            cost_so_far: HashMap::new(),
+            came_from: HashMap::new(),
+        };
+        let Some(from) = self.grid_hex(from.col, from.row) else {
+            return reachable;
+        };
+
+        let mut frontier: BinaryHeap<HexWithPriority> = BinaryHeap::new();
+        let mut closed: HashSet<&Hex> = HashSet::new();
+        reachable.from = Some(from);
+        reachable.cost_so_far.insert(from, 0);
+        frontier.push(HexWithPriority::new(from, 0));
+        while let Some(candidate) = frontier.pop() {
+            let current = candidate.hex;
+            if !closed.insert(current) {
+                continue;
             }
 
-            let neighbours = self.pick_all_enterable_neighbours(candidate.hex);
-            neighbours.iter().for_each(|next| {
-                let new_cost = self.distance(next, to);
-                if new_cost < 0 {
-                    panic!("Negative cost");
+            let current_cost = reachable.cost_so_far[current];
+            for next in self.pick_all_neighbours(current) {
+                let Some(step_cost) = next.move_cost(mobility) else {
+                    continue;
+                };
+                let new_cost = current_cost.saturating_add(step_cost.max(1));
+                if new_cost > max_cost {
+                    continue;
                 }
-                if !cost_map.contains_key(next) || *cost_map.get(next).unwrap() < new_cost {
-                    cost_map.insert(next, new_cost);
-                    came_from.insert(next, candidate.hex);
+                if reachable
+                    .cost_so_far
+                    .get(next)
+                    .is_none_or(|cost| new_cost < *cost)
+                {
+                    reachable.cost_so_far.insert(next, new_cost);
+                    reachable.came_from.insert(next, current);
                     frontier.push(HexWithPriority::new(next, new_cost));
                 }
-            });
+            }
         }
-        assert!(end_reached, "A* algorithm didn't reach the end.");
 
+        reachable
+    }
+
+    /// The hex of the grid at the offset coordinates, which may be out of the grid.
+    fn grid_hex(&self, col: i32, row: i32) -> Option<&Hex> {
+        self.hex(col.try_into().ok()?, row.try_into().ok()?)
+    }
+
+    fn reconstruct_path<'a>(
+        came_from: &HashMap<&'a Hex, &'a Hex>,
+        from: &'a Hex,
+        to: &'a Hex,
+    ) -> Vec<&'a Hex> {
+        let mut path: Vec<&Hex> = vec![];
         let mut current = to;
         while current != from {
             path.push(current);
-            match came_from.get(current) {
-                Some(next) => {
-                    current = *next;
-                }
-                None => {
-                    break;
-                }
-            }
+            current = came_from[current];
         }
         path.reverse();
 
@@ -298,6 +686,99 @@ impl HexGrid {
         }
     }
 
+    /// Hexes exactly `radius` hexes away from the centre.
+    pub fn ring(&self, center: &Hex, radius: i32) -> Vec<&Hex> {
+        let center = self.offset_to_cube(center);
+        self.cube_ring(center, radius)
+            .iter()
+            .filter_map(|cube| self.cube_hex(cube))
+            .collect()
+    }
+
+    /// The centre and the rings around it up to the radius, the closest hexes first.
+    pub fn spiral(&self, center: &Hex, radius: i32) -> Vec<&Hex> {
+        (0..=radius)
+            .flat_map(|ring_radius| self.ring(center, ring_radius))
+            .collect()
+    }
+
+    /// The hexes going straight from the centre in the direction, the centre excluded.
+    pub fn straight_line(&self, center: &Hex, direction: HexDirection, length: i32) -> Vec<&Hex> {
+        let center = self.offset_to_cube(center);
+        (1..=length)
+            .filter_map(|step| self.cube_hex(&center.add(direction.cube().scale(step))))
+            .collect()
+    }
+
+    /// A 60 degrees wide wedge from the centre in the direction, the centre excluded.
+    pub fn cone(&self, center: &Hex, direction: HexDirection, radius: i32) -> Vec<&Hex> {
+        let center = self.offset_to_cube(center);
+        (1..=radius)
+            .flat_map(|ring_radius| {
+                let axis = direction.cube().scale(ring_radius);
+                self.cube_ring(center, ring_radius)
+                    .into_iter()
+                    .filter(move |cube| cube.sub(center).sub(axis).length() <= ring_radius / 2)
+            })
+            .filter_map(|cube| self.cube_hex(&cube))
+            .collect()
+    }
+
+    /// Turns the `(col, row)` pattern around the centre by sixths of a circle, from the east
+    /// towards the north-east for positive steps. The pattern may stick out of the grid, only
+    /// the result is clipped.
+    pub fn rotate(&self, center: &Hex, pattern: &[(i32, i32)], steps: i32) -> Vec<&Hex> {
+        self.transform(center, pattern, |cube| cube.rotate(steps))
+    }
+
+    /// Mirrors the `(col, row)` pattern across the axis going through the centre in the
+    /// direction. The pattern may stick out of the grid, only the result is clipped.
+    pub fn reflect(&self, center: &Hex, pattern: &[(i32, i32)], axis: HexDirection) -> Vec<&Hex> {
+        let steps = axis as i32;
+        self.transform(center, pattern, |cube| {
+            cube.rotate(-steps).reflect().rotate(steps)
+        })
+    }
+
+    fn transform<F>(&self, center: &Hex, pattern: &[(i32, i32)], transform: F) -> Vec<&Hex>
+    where
+        F: Fn(Cube) -> Cube,
+    {
+        let center = self.offset_to_cube(center);
+        pattern
+            .iter()
+            .map(|(col, row)| self.offset_to_cube(&Hex::new_default(*col, *row)))
+            .filter_map(|cube| self.cube_hex(&center.add(transform(cube.sub(center)))))
+            .collect()
+    }
+
+    /// Cubes of the ring around the centre, starting at the south-west corner and going
+    /// counterclockwise. Not clipped to the grid.
+    fn cube_ring(&self, center: Cube, radius: i32) -> Vec<Cube> {
+        if radius <= 0 {
+            return if radius == 0 { vec![center] } else { vec![] };
+        }
+
+        let mut cube = center.add(HexDirection::SouthWest.cube().scale(radius));
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in HexDirection::ALL {
+            for _ in 0..radius {
+                ring.push(cube);
+                cube = cube.add(direction.cube());
+            }
+        }
+
+        ring
+    }
+
+    /// Like [`HexGrid::cube_to_offset`], but `None` for the cubes out of the grid.
+    fn cube_hex(&self, cube: &Cube) -> Option<&Hex> {
+        // As we are using odd-r offset hexagonal game.grid, standard offset is -1.
+        let offset = -1;
+
+        self.grid_hex(cube.x + ((cube.y + offset * (cube.y & 1)) / 2), cube.y)
+    }
+
     fn cube_distance(&self, x: i32, y: i32, z: i32) -> i32 {
         (x + y + z) / 2
     }
@@ -338,7 +819,7 @@ impl HexGrid {
 
 #[cfg(test)]
 mod tests {
-    use crate::app::grid::{Hex, HexGrid};
+    use crate::app::grid::{Hex, HexDirection, HexGrid, Mobility, PathBudget, Terrain};
     use std::cmp::Ordering;
     // region Hex
 
@@ -349,12 +830,14 @@ mod tests {
             row: 2,
             obstacle: false,
             busy: false,
+            terrain: Terrain::Plain,
         };
         let hex2: Hex = Hex {
             col: 0,
             row: 2,
             obstacle: false,
             busy: false,
+            terrain: Terrain::Plain,
         };
         assert_eq!(hex.partial_cmp(&hex2).unwrap(), Ordering::Greater);
 
@@ -363,6 +846,7 @@ mod tests {
             row: 2,
             obstacle: false,
             busy: false,
+            terrain: Terrain::Plain,
         };
         assert_eq!(hex.partial_cmp(&hex3).unwrap(), Ordering::Less);
     }
@@ -439,21 +923,370 @@ mod tests {
         let from = Hex::new_default(0, 3);
         let to = Hex::new_default(4, 3);
 
-        let path = hex_grid.smart_path(&from, &to);
-        assert_eq!(path.len(), 6);
+        let path = hex_grid.smart_path(&from, &to, Mobility::Foot).unwrap();
+        assert_eq!(path.cost, 5);
 
+        let path = path.hexes;
         let expected_path = [
-            Hex::new_default(1, 2),
-            Hex::new_default(2, 2),
-            Hex::new_default(2, 1),
-            Hex::new_default(3, 1),
-            Hex::new_default(4, 2),
+            Hex::new_default(1, 4),
+            Hex::new_default(2, 4),
+            Hex::new_default(3, 4),
+            Hex::new_default(4, 4),
             Hex::new_default(4, 3),
         ];
+        assert_eq!(path.len(), expected_path.len());
         (0..path.len()).for_each(|i| {
             assert_eq!(path[i], &expected_path[i]);
         });
     }
 
+    #[test]
+    fn hexgrid_test_smart_path_to_enclosed_hex() {
+        let hex_grid =
+            HexGrid::new_with_obstacles(5, 5, vec![(3, 2), (2, 1), (1, 2), (2, 3), (1, 1), (1, 3)]);
+
+        assert_eq!(
+            hex_grid.smart_path(
+                &Hex::new_default(0, 0),
+                &Hex::new_default(2, 2),
+                Mobility::Foot
+            ),
+            None
+        );
+        assert_eq!(
+            hex_grid.smart_path(
+                &Hex::new_default(0, 0),
+                &Hex::new_default(9, 9),
+                Mobility::Foot
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn hexgrid_test_find_path_avoids_expensive_hexes() {
+        let hex_grid = HexGrid::new_no_obstacles(5, 3);
+        let swamp = |hex: &Hex| Some(if hex.row == 1 && hex.col > 0 { 5 } else { 1 });
+
+        let path = hex_grid
+            .find_path(
+                &Hex::new_default(0, 1),
+                &Hex::new_default(4, 1),
+                PathBudget::builder().build(),
+                swamp,
+            )
+            .unwrap();
+        assert_eq!(path.cost, 9);
+        assert_eq!(path.hexes.len(), 5);
+        assert_eq!(path.hexes.last(), Some(&&Hex::new_default(4, 1)));
+        assert!(path.hexes[..4].iter().all(|hex| hex.row != 1));
+
+        let unit_path = hex_grid
+            .smart_path(
+                &Hex::new_default(0, 1),
+                &Hex::new_default(4, 1),
+                Mobility::Foot,
+            )
+            .unwrap();
+        assert_eq!(unit_path.cost, 4);
+    }
+
+    #[test]
+    fn hexgrid_test_find_path_respects_budget() {
+        let hex_grid = HexGrid::new_no_obstacles(10, 10);
+        let from = Hex::new_default(0, 0);
+        let to = Hex::new_default(9, 9);
+        let foot = |hex: &Hex| hex.move_cost(Mobility::Foot);
+
+        let max_cost = PathBudget::builder().max_cost(5).build();
+        assert_eq!(hex_grid.find_path(&from, &to, max_cost, foot), None);
+
+        let max_nodes = PathBudget::builder().max_nodes(3).build();
+        assert_eq!(hex_grid.find_path(&from, &to, max_nodes, foot), None);
+
+        let enough = PathBudget::builder().max_cost(14).build();
+        assert_eq!(
+            hex_grid
+                .find_path(&from, &to, enough, foot)
+                .map(|path| path.cost),
+            Some(14)
+        );
+    }
+
+    #[test]
+    fn hexgrid_test_terrain_slows_mounted_units() {
+        let mut hex_grid = HexGrid::new_no_obstacles(3, 1);
+        assert!(hex_grid.set_terrain(1, 0, Terrain::Forest));
+        let from = hex_grid.hex(0, 0).unwrap().clone();
+        let to = hex_grid.hex(2, 0).unwrap().clone();
+
+        assert_eq!(hex_grid.move_distance(&from, &to, Mobility::Foot), Some(3));
+        assert_eq!(
+            hex_grid.move_distance(&from, &to, Mobility::Mounted),
+            Some(4)
+        );
+        assert_eq!(hex_grid.distance(&from, &to), 2);
+
+        assert!(hex_grid.set_terrain(1, 0, Terrain::Swamp));
+        assert_eq!(hex_grid.move_distance(&from, &to, Mobility::Foot), Some(4));
+        assert_eq!(hex_grid.move_distance(&from, &to, Mobility::Mounted), None);
+
+        assert!(hex_grid.set_terrain(1, 0, Terrain::Water));
+        assert_eq!(hex_grid.move_distance(&from, &to, Mobility::Foot), None);
+        assert!(!hex_grid.set_terrain(3, 0, Terrain::Road));
+    }
+
+    #[test]
+    fn hexgrid_test_path_follows_the_road() {
+        let mut hex_grid = HexGrid::new_no_obstacles(4, 3);
+        (0..4).for_each(|col| {
+            hex_grid.set_terrain(col, 1, Terrain::Forest);
+        });
+        (0..4).for_each(|col| {
+            hex_grid.set_terrain(col, 0, Terrain::Road);
+            hex_grid.set_terrain(col, 2, Terrain::Swamp);
+        });
+        let from = Hex::new_default(0, 1);
+        let to = hex_grid.hex(3, 1).unwrap().clone();
+
+        let path = hex_grid.smart_path(&from, &to, Mobility::Mounted).unwrap();
+        assert_eq!(path.cost, 6);
+        assert!(path.hexes[..path.hexes.len() - 1]
+            .iter()
+            .all(|hex| hex.terrain == Terrain::Road));
+    }
+
+    #[test]
+    fn hexgrid_test_reachable_within_budget() {
+        let mut hex_grid = HexGrid::new_with_obstacles(5, 5, vec![(3, 2)]);
+        hex_grid.set_busy(2, 1, true);
+        hex_grid.set_terrain(1, 2, Terrain::Forest);
+        let from = Hex::new_default(2, 2);
+
+        let reachable = hex_grid.reachable(&from, 1, Mobility::Foot);
+        let neighbours: Vec<(i32, i32)> = reachable
+            .hexes()
+            .iter()
+            .map(|(hex, _)| (hex.col, hex.row))
+            .collect();
+        assert_eq!(neighbours, vec![(1, 1), (1, 3), (2, 3)]);
+        assert_eq!(reachable.cost(2, 2), Some(0));
+        assert_eq!(reachable.cost(1, 2), None);
+        assert_eq!(reachable.cost(3, 2), None);
+
+        let reachable = hex_grid.reachable(&from, 3, Mobility::Foot);
+        assert_eq!(reachable.cost(1, 2), Some(2));
+        assert_eq!(reachable.cost(2, 1), None);
+        assert_eq!(reachable.cost(4, 2), Some(3));
+        assert!(reachable.hexes().iter().all(|(_, cost)| *cost <= 3));
+        assert!(reachable.hexes().windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn hexgrid_test_reachable_path_matches_smart_path() {
+        let hex_grid = HexGrid::new_with_obstacles(5, 5, vec![(1, 3), (2, 3), (3, 2)]);
+        let from = Hex::new_default(0, 3);
+
+        let reachable = hex_grid.reachable(&from, 10, Mobility::Foot);
+        let path = reachable.path_to(4, 3).unwrap();
+        let smart_path = hex_grid
+            .smart_path(&from, &Hex::new_default(4, 3), Mobility::Foot)
+            .unwrap();
+        assert_eq!(path.cost, smart_path.cost);
+        assert_eq!(path.hexes.len(), 5);
+        assert_eq!(path.hexes.last(), Some(&&Hex::new_default(4, 3)));
+        assert!(path
+            .hexes
+            .windows(2)
+            .all(|pair| HexGrid::are_neighbours(pair[0], pair[1])));
+        assert_eq!(
+            reachable.path_to(0, 3).map(|path| path.hexes.len()),
+            Some(0)
+        );
+        assert_eq!(reachable.path_to(1, 3), None);
+        assert_eq!(reachable.path_to(9, 9), None);
+    }
+
+    #[test]
+    fn hexgrid_test_obstacle_blocks_line_of_sight() {
+        let hex_grid = HexGrid::new_with_obstacles(7, 5, vec![(3, 2)]);
+        let from = Hex::new_default(1, 2);
+
+        assert!(!hex_grid.line_of_sight(&from, &Hex::new_default(5, 2)));
+        assert!(!hex_grid.line_of_sight(&Hex::new_default(5, 2), &from));
+        assert!(hex_grid.line_of_sight(&from, &Hex::new_default(3, 2)));
+        assert!(hex_grid.line_of_sight(&from, &Hex::new_default(5, 0)));
+        assert!(hex_grid.line_of_sight(&from, &Hex::new_default(2, 2)));
+        assert!(hex_grid.line_of_sight(&from, &from));
+        assert!(!hex_grid.line_of_sight(&from, &Hex::new_default(7, 2)));
+    }
+
+    #[test]
+    fn hexgrid_test_line_along_obstacle_edge_is_not_blocked() {
+        // The line passes exactly between (1, 1) and (1, 2).
+        let from = Hex::new_default(0, 1);
+        let to = Hex::new_default(2, 2);
+
+        let hex_grid = HexGrid::new_with_obstacles(4, 4, vec![(1, 1)]);
+        assert!(hex_grid.line_of_sight(&from, &to));
+        assert!(hex_grid.line_of_sight(&to, &from));
+
+        let hex_grid = HexGrid::new_with_obstacles(4, 4, vec![(1, 2)]);
+        assert!(hex_grid.line_of_sight(&from, &to));
+
+        let hex_grid = HexGrid::new_with_obstacles(4, 4, vec![(1, 1), (1, 2)]);
+        assert!(!hex_grid.line_of_sight(&from, &to));
+        assert!(!hex_grid.line_of_sight(&to, &from));
+    }
+
+    #[test]
+    fn hexgrid_test_field_of_view() {
+        let hex_grid = HexGrid::new_with_obstacles(7, 5, vec![(3, 2)]);
+        let from = Hex::new_default(1, 2);
+
+        let visible: Vec<(i32, i32)> = hex_grid
+            .field_of_view(&from, 4)
+            .iter()
+            .map(|hex| (hex.col, hex.row))
+            .collect();
+        assert!(visible.contains(&(1, 2)));
+        assert!(visible.contains(&(3, 2)));
+        assert!(visible.contains(&(4, 0)));
+        assert!(!visible.contains(&(4, 2)));
+        assert!(!visible.contains(&(5, 2)));
+        assert!(!visible.contains(&(6, 2)));
+
+        let open = HexGrid::new_no_obstacles(7, 5);
+        assert_eq!(open.field_of_view(&from, 1).len(), 7);
+        assert_eq!(
+            open.field_of_view(&Hex::new_default(9, 9), 3),
+            Vec::<&Hex>::new()
+        );
+    }
+
+    fn coords(hexes: &[&Hex]) -> Vec<(i32, i32)> {
+        hexes.iter().map(|hex| (hex.col, hex.row)).collect()
+    }
+
+    #[test]
+    fn hexgrid_test_directions_match_neighbours() {
+        let hex_grid = HexGrid::new_no_obstacles(5, 5);
+
+        for center in [Hex::new_default(2, 2), Hex::new_default(2, 1)] {
+            let lines: Vec<&Hex> = HexDirection::ALL
+                .iter()
+                .flat_map(|direction| hex_grid.straight_line(&center, *direction, 1))
+                .collect();
+            assert_eq!(lines, hex_grid.pick_all_neighbours(&center));
+        }
+    }
+
+    #[test]
+    fn hexgrid_test_ring_and_spiral() {
+        let hex_grid = HexGrid::new_no_obstacles(9, 9);
+        let center = Hex::new_default(4, 4);
+
+        let mut ring = coords(&hex_grid.ring(&center, 1));
+        let mut neighbours = coords(&hex_grid.pick_all_neighbours(&center));
+        ring.sort();
+        neighbours.sort();
+        assert_eq!(ring, neighbours);
+
+        let ring = hex_grid.ring(&center, 3);
+        assert_eq!(ring.len(), 18);
+        assert!(ring.iter().all(|hex| hex_grid.distance(&center, hex) == 3));
+        assert_eq!(hex_grid.ring(&center, 0), vec![&center]);
+
+        let spiral = hex_grid.spiral(&center, 2);
+        assert_eq!(spiral.len(), 19);
+        assert_eq!(spiral[0], &center);
+        assert!(
+            spiral
+                .windows(2)
+                .all(|pair| hex_grid.distance(&center, pair[0])
+                    <= hex_grid.distance(&center, pair[1]))
+        );
+    }
+
+    #[test]
+    fn hexgrid_test_shapes_are_clipped() {
+        let hex_grid = HexGrid::new_no_obstacles(4, 4);
+        let corner = Hex::new_default(0, 0);
+
+        assert_eq!(coords(&hex_grid.ring(&corner, 1)), vec![(0, 1), (1, 0)]);
+        assert_eq!(hex_grid.spiral(&corner, 1).len(), 3);
+        assert_eq!(
+            coords(&hex_grid.straight_line(&corner, HexDirection::East, 6)),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert!(hex_grid.cone(&corner, HexDirection::West, 3).is_empty());
+    }
+
+    #[test]
+    fn hexgrid_test_cone() {
+        let hex_grid = HexGrid::new_no_obstacles(9, 9);
+        let center = Hex::new_default(4, 4);
+
+        let cone = hex_grid.cone(&center, HexDirection::East, 3);
+        assert_eq!(cone.len(), 7);
+        assert_eq!(cone[0], &Hex::new_default(5, 4));
+        assert!(cone.iter().all(|hex| hex.col > center.col));
+        assert!(cone.contains(&&Hex::new_default(7, 4)));
+
+        let west = hex_grid.cone(&center, HexDirection::West, 3);
+        assert_eq!(west.len(), 7);
+        assert!(west.iter().all(|hex| hex.col < center.col));
+    }
+
+    #[test]
+    fn hexgrid_test_rotate_and_reflect() {
+        let hex_grid = HexGrid::new_no_obstacles(9, 9);
+        let center = Hex::new_default(4, 4);
+        let east = coords(&hex_grid.straight_line(&center, HexDirection::East, 2));
+        let north_east = coords(&hex_grid.straight_line(&center, HexDirection::NorthEast, 2));
+        let south_east = coords(&hex_grid.straight_line(&center, HexDirection::SouthEast, 2));
+
+        assert_eq!(coords(&hex_grid.rotate(&center, &east, 1)), north_east);
+        assert_eq!(coords(&hex_grid.rotate(&center, &east, -1)), south_east);
+        assert_eq!(coords(&hex_grid.rotate(&center, &east, 6)), east);
+        assert_eq!(
+            coords(&hex_grid.reflect(&center, &north_east, HexDirection::East)),
+            south_east
+        );
+        assert_eq!(
+            coords(&hex_grid.reflect(&center, &east, HexDirection::East)),
+            east
+        );
+        assert_eq!(
+            coords(&hex_grid.reflect(&center, &east, HexDirection::NorthEast)),
+            coords(&hex_grid.straight_line(&center, HexDirection::NorthWest, 2))
+        );
+
+        let edge = Hex::new_default(8, 4);
+        assert_eq!(hex_grid.rotate(&edge, &[(9, 4), (10, 4)], 3).len(), 2);
+        assert!(hex_grid.rotate(&edge, &[(7, 4)], 3).is_empty());
+    }
+
+    #[test]
+    fn hexgrid_test_non_square_grid_addressing() {
+        let hex_grid = HexGrid::new_with_obstacles(5, 3, vec![(4, 1)]);
+
+        assert_eq!(hex_grid.hex(4, 2), Some(&Hex::new_default(4, 2)));
+        assert_eq!(hex_grid.hex(4, 1), Some(&Hex::new(4, 1, true, false)));
+        assert_eq!(hex_grid.hex(2, 3), None);
+        assert_eq!(hex_grid.hex(5, 0), None);
+    }
+
+    #[test]
+    fn hexgrid_test_busy_hex_is_not_enterable() {
+        let mut hex_grid = HexGrid::new_no_obstacles(4, 4);
+        assert!(hex_grid.set_busy(3, 2, true));
+
+        let base_hex = Hex::new_default(2, 2);
+        let neighbours = hex_grid.pick_all_enterable_neighbours(&base_hex);
+        assert_eq!(neighbours.len(), 5);
+        assert!(neighbours.iter().all(|hex| (hex.col, hex.row) != (3, 2)));
+    }
+
     // endregion HexGrid
 }
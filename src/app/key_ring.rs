@@ -0,0 +1,317 @@
+use crate::error::{AppError, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::{env, fs};
+use thiserror::Error;
+
+/// Comma separated `kid:algorithm:path` entries, oldest key first. The newest key signs tokens.
+/// An HS512 file holds the secret, an EdDSA or RS256 file holds a PKCS#8 PEM private key.
+pub const JWT_KEYS_VAR: &str = "JWT_KEYS";
+/// HS512 secrets as long as the hash output at least, shorter ones are easy to brute force.
+pub const HS512_MIN_SECRET_BYTES: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum KeyRingError {
+    #[error("{JWT_KEYS_VAR} must be set")]
+    NotConfigured,
+    #[error("Malformed key entry {0}, expected kid:algorithm:path")]
+    MalformedEntry(String),
+    #[error("Key {0} uses an unsupported algorithm {1}")]
+    UnsupportedAlgorithm(String, String),
+    #[error("Key id {0} is used twice")]
+    DuplicateKid(String),
+    #[error("Can't read key {0}: {1}")]
+    Unreadable(String, String),
+    #[error("The secret of key {0} is {1} bytes long, at least {HS512_MIN_SECRET_BYTES} needed")]
+    WeakSecret(String, usize),
+}
+
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Public part of an asymmetric key, HMAC secrets are never published.
+    jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    pub fn hs512(kid: &str, secret: &[u8]) -> std::result::Result<Self, KeyRingError> {
+        if secret.len() < HS512_MIN_SECRET_BYTES {
+            return Err(KeyRingError::WeakSecret(kid.to_owned(), secret.len()));
+        }
+
+        Ok(JwtKey {
+            kid: kid.to_owned(),
+            algorithm: Algorithm::HS512,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            jwk: None,
+        })
+    }
+
+    /// Builds an EdDSA (Ed25519) or RS256 key from a PEM private key.
+    pub fn from_pem(
+        kid: &str,
+        algorithm: Algorithm,
+        pem: &[u8],
+    ) -> std::result::Result<Self, KeyRingError> {
+        let unreadable = |e: String| KeyRingError::Unreadable(kid.to_owned(), e);
+        let der = pem::parse(pem).map_err(|e| unreadable(e.to_string()))?;
+
+        let (encoding, parameters, key_algorithm) = match algorithm {
+            Algorithm::EdDSA => {
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der.contents())
+                    .map_err(|e| unreadable(e.to_string()))?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key_pair.public_key()),
+                });
+                (
+                    EncodingKey::from_ed_der(der.contents()),
+                    parameters,
+                    KeyAlgorithm::EdDSA,
+                )
+            }
+            Algorithm::RS256 => {
+                let key_pair = match der.tag() {
+                    "RSA PRIVATE KEY" => RsaKeyPair::from_der(der.contents()),
+                    _ => RsaKeyPair::from_pkcs8(der.contents()),
+                }
+                .map_err(|e| unreadable(e.to_string()))?;
+                let components = PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+                let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(components.n),
+                    e: URL_SAFE_NO_PAD.encode(components.e),
+                });
+                (
+                    EncodingKey::from_rsa_pem(pem).map_err(|e| unreadable(e.to_string()))?,
+                    parameters,
+                    KeyAlgorithm::RS256,
+                )
+            }
+            _ => {
+                return Err(KeyRingError::UnsupportedAlgorithm(
+                    kid.to_owned(),
+                    format!("{algorithm:?}"),
+                ))
+            }
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_owned()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(JwtKey {
+            kid: kid.to_owned(),
+            algorithm,
+            encoding,
+            decoding: DecodingKey::from_jwk(&jwk).map_err(|e| unreadable(e.to_string()))?,
+            jwk: Some(jwk),
+        })
+    }
+}
+
+/// Keys the tokens are signed and verified with. Tokens are signed with the newest key and
+/// verified with the key named by their `kid` header, so a rotation doesn't log anybody out.
+pub struct KeyRing {
+    keys: Vec<JwtKey>,
+}
+
+impl KeyRing {
+    /// Keys are ordered from the oldest to the newest one.
+    pub fn new(keys: Vec<JwtKey>) -> std::result::Result<Self, KeyRingError> {
+        if keys.is_empty() {
+            return Err(KeyRingError::NotConfigured);
+        }
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|other| other.kid == key.kid) {
+                return Err(KeyRingError::DuplicateKid(key.kid.clone()));
+            }
+        }
+
+        Ok(KeyRing { keys })
+    }
+
+    pub fn from_env() -> std::result::Result<Self, KeyRingError> {
+        let entries = env::var(JWT_KEYS_VAR).map_err(|_| KeyRingError::NotConfigured)?;
+
+        Self::new(
+            entries
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(Self::load_key)
+                .collect::<std::result::Result<_, _>>()?,
+        )
+    }
+
+    fn load_key(entry: &str) -> std::result::Result<JwtKey, KeyRingError> {
+        let mut parts = entry.splitn(3, ':');
+        let (Some(kid), Some(algorithm), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(KeyRingError::MalformedEntry(entry.to_owned()));
+        };
+        let algorithm = algorithm.parse::<Algorithm>().map_err(|_| {
+            KeyRingError::UnsupportedAlgorithm(kid.to_owned(), algorithm.to_owned())
+        })?;
+        let content =
+            fs::read(path).map_err(|e| KeyRingError::Unreadable(kid.to_owned(), e.to_string()))?;
+
+        match algorithm {
+            Algorithm::HS512 => JwtKey::hs512(kid, content.trim_ascii()),
+            _ => JwtKey::from_pem(kid, algorithm, &content),
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let key = self.keys.last().ok_or(AppError::TokenCreation)?;
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+
+        encode(&header, claims, &key.encoding).map_err(|_| AppError::TokenCreation)
+    }
+
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let header = decode_header(token).map_err(|_| AppError::InvalidToken)?;
+        let key = header
+            .kid
+            .and_then(|kid| self.keys.iter().find(|key| key.kid == kid))
+            .ok_or(AppError::InvalidToken)?;
+
+        decode::<T>(token, &key.decoding, &Validation::new(key.algorithm))
+            .map(|token_data| token_data.claims)
+            .map_err(|_| AppError::InvalidToken)
+    }
+
+    /// Public keys of the ring for the services verifying the tokens.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::key_ring::{JwtKey, KeyRing, KeyRingError, HS512_MIN_SECRET_BYTES};
+    use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+    use ring::rand::SystemRandom;
+    use ring::signature::Ed25519KeyPair;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "1".to_owned(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        }
+    }
+
+    fn ed25519_pem() -> Vec<u8> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref())).into_bytes()
+    }
+
+    fn hs512(kid: &str, secret: u8) -> JwtKey {
+        JwtKey::hs512(kid, &[secret; HS512_MIN_SECRET_BYTES]).unwrap()
+    }
+
+    #[test]
+    fn when_keys_rotated_then_tokens_of_old_keys_stay_valid() {
+        let old_ring = KeyRing::new(vec![hs512("old", 1)]).unwrap();
+        let old_token = old_ring.sign(&claims()).unwrap();
+
+        let ring = KeyRing::new(vec![
+            hs512("old", 1),
+            JwtKey::from_pem("new", Algorithm::EdDSA, &ed25519_pem()).unwrap(),
+        ])
+        .unwrap();
+        let new_token = ring.sign(&claims()).unwrap();
+
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("new")
+        );
+        assert_eq!(ring.verify::<TestClaims>(&old_token).unwrap(), claims());
+        assert_eq!(ring.verify::<TestClaims>(&new_token).unwrap(), claims());
+        assert!(old_ring.verify::<TestClaims>(&new_token).is_err());
+    }
+
+    #[test]
+    fn when_key_unknown_or_forged_then_token_rejected() {
+        let ring = KeyRing::new(vec![hs512("main", 1)]).unwrap();
+        let forged = KeyRing::new(vec![hs512("main", 2)]).unwrap();
+        let unknown = KeyRing::new(vec![hs512("other", 1)]).unwrap();
+
+        assert!(ring
+            .verify::<TestClaims>(&forged.sign(&claims()).unwrap())
+            .is_err());
+        assert!(ring
+            .verify::<TestClaims>(&unknown.sign(&claims()).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn when_jwks_requested_then_only_asymmetric_keys_published() {
+        let ring = KeyRing::new(vec![
+            hs512("hmac", 1),
+            JwtKey::from_pem("ed", Algorithm::EdDSA, &ed25519_pem()).unwrap(),
+        ])
+        .unwrap();
+        let jwks = ring.jwks();
+
+        assert_eq!(jwks.keys.len(), 1);
+        let jwk = jwks.find("ed").unwrap();
+        let token = ring.sign(&claims()).unwrap();
+        let verified = decode::<TestClaims>(
+            &token,
+            &DecodingKey::from_jwk(jwk).unwrap(),
+            &Validation::new(Algorithm::EdDSA),
+        );
+        assert!(verified.is_ok());
+    }
+
+    #[test]
+    fn when_kid_used_twice_then_ring_rejected() {
+        let ring = KeyRing::new(vec![hs512("main", 1), hs512("main", 2)]);
+
+        assert!(matches!(ring, Err(KeyRingError::DuplicateKid(_))));
+    }
+
+    #[test]
+    fn when_hs512_secret_short_then_key_rejected() {
+        let empty = JwtKey::hs512("empty", b"");
+        let short = JwtKey::hs512("short", &[1; HS512_MIN_SECRET_BYTES - 1]);
+
+        assert!(matches!(empty, Err(KeyRingError::WeakSecret(_, 0))));
+        assert!(matches!(
+            short,
+            Err(KeyRingError::WeakSecret(_, len)) if len == HS512_MIN_SECRET_BYTES - 1
+        ));
+    }
+}
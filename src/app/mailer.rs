@@ -0,0 +1,89 @@
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::PathBuf;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers mails to the players. An SMTP or API backed mailer plugs in here.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<()>;
+}
+
+/// Default mailer that doesn't deliver anything. Mails are appended to the outbox file,
+/// or only logged without one, which is enough for development and tests.
+#[derive(Debug, Default, Clone)]
+pub struct OutboxMailer {
+    pub outbox: Option<PathBuf>,
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, mail: Mail) -> Result<()> {
+        let Some(outbox) = &self.outbox else {
+            info!("Mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+            return Ok(());
+        };
+
+        let entry = format!(
+            "Date: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+            Utc::now().to_rfc2822(),
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(outbox)
+            .await
+            .map_err(|e| AppError::MailDelivery(e.to_string()))?;
+        file.write_all(entry.as_bytes())
+            .await
+            .map_err(|e| AppError::MailDelivery(e.to_string()))?;
+        file.flush()
+            .await
+            .map_err(|e| AppError::MailDelivery(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::mailer::{Mail, Mailer, OutboxMailer};
+
+    #[tokio::test]
+    async fn when_mails_sent_then_outbox_mailer_appends_them() {
+        let outbox = std::env::temp_dir().join(format!("outbox-{}.txt", std::process::id()));
+        let mailer = OutboxMailer {
+            outbox: Some(outbox.clone()),
+        };
+
+        for subject in ["First", "Second"] {
+            mailer
+                .send(Mail {
+                    to: "player@example.com".to_owned(),
+                    subject: subject.to_owned(),
+                    body: "Hello".to_owned(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let content = std::fs::read_to_string(&outbox).unwrap();
+        std::fs::remove_file(&outbox).unwrap();
+        assert!(content.contains("Subject: First"));
+        assert!(content.contains("Subject: Second"));
+        assert_eq!(content.matches("To: player@example.com").count(), 2);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::app::mailer::{Mail, Mailer};
+use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::error::Result;
+use bon::Builder;
+use rand::Rng;
+use redis::AsyncCommands;
+use std::sync::Arc;
+
+pub const EMAIL_VERIFICATION_TTL_SECS: u64 = 60 * 60 * 24;
+pub const PASSWORD_RESET_TTL_SECS: u64 = 60 * 60;
+
+/// Email verification and password reset. Both go through single-use tokens mailed to the
+/// player, which expire on their own.
+#[derive(Builder)]
+pub struct AccountMiddleware {
+    pub cache_pool: Arc<bb8::Pool<RedisConnectionManager>>,
+    pub mailer: Arc<dyn Mailer>,
+    /// Whether players have to verify the email before the first login.
+    #[builder(default)]
+    pub require_verified_email: bool,
+}
+
+impl AccountMiddleware {
+    pub async fn send_email_verification(&self, player_id: i32, email: &str) -> Result<()> {
+        let token = self
+            .issue_token(
+                CacheKey::EmailVerification,
+                player_id,
+                EMAIL_VERIFICATION_TTL_SECS,
+            )
+            .await?;
+
+        self.mailer
+            .send(Mail {
+                to: email.to_owned(),
+                subject: "Confirm your email".to_owned(),
+                body: format!(
+                    "Your email verification token is {token}. It is valid for 24 hours."
+                ),
+            })
+            .await
+    }
+
+    /// Returns the player the token was issued for, `None` if it's unknown, used or expired.
+    pub async fn consume_email_verification(&self, token: &str) -> Result<Option<i32>> {
+        self.consume_token(CacheKey::EmailVerification, token).await
+    }
+
+    pub async fn send_password_reset(&self, player_id: i32, email: &str) -> Result<()> {
+        let token = self
+            .issue_token(CacheKey::PasswordReset, player_id, PASSWORD_RESET_TTL_SECS)
+            .await?;
+
+        self.mailer
+            .send(Mail {
+                to: email.to_owned(),
+                subject: "Reset your password".to_owned(),
+                body: format!(
+                    "Your password reset token is {token}. It is valid for 1 hour. \
+                    Ignore this mail if you didn't ask for a password reset."
+                ),
+            })
+            .await
+    }
+
+    /// Returns the player the token was issued for, `None` if it's unknown, used or expired.
+    /// Player the password reset token was issued for, the token stays valid.
+    pub async fn peek_password_reset(&self, token: &str) -> Result<Option<i32>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .get::<&str, Option<i32>>(Self::get_token_key(CacheKey::PasswordReset, token).as_str())
+            .await?)
+    }
+
+    pub async fn consume_password_reset(&self, token: &str) -> Result<Option<i32>> {
+        self.consume_token(CacheKey::PasswordReset, token).await
+    }
+
+    async fn issue_token(&self, key: CacheKey, player_id: i32, ttl: u64) -> Result<String> {
+        let token = random_token();
+        let mut conn = self.cache_pool.get().await?;
+        conn.set_ex::<&str, i32, ()>(Self::get_token_key(key, &token).as_str(), player_id, ttl)
+            .await?;
+
+        Ok(token)
+    }
+
+    async fn consume_token(&self, key: CacheKey, token: &str) -> Result<Option<i32>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .get_del::<&str, Option<i32>>(Self::get_token_key(key, token).as_str())
+            .await?)
+    }
+
+    pub(crate) fn get_token_key(key: CacheKey, token: &str) -> String {
+        format!("{}_{token}", key.as_ref())
+    }
+}
+
+/// 256 random bits, hex encoded.
+pub fn random_token() -> String {
+    rand::rng()
+        .random::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,163 @@
+use crate::app::battle::{Battle, BattleError};
+use crate::app::middleware::account_middleware::random_token;
+use crate::app::protos::messages::PlayerSession;
+use crate::error::{AppError, Result};
+use crate::model::battle::{BattleLog, Bot, NewBattle};
+use crate::model::item::WeaponItem;
+use bon::Builder;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// A battle in progress, shared between the requests of its participants.
+pub type LiveBattle = Arc<tokio::sync::Mutex<Battle>>;
+
+#[derive(Builder)]
+pub struct BattleMiddleware {
+    pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
+    /// Battles in progress by id. They live in memory only, a restart drops them.
+    #[builder(default)]
+    battles: Mutex<HashMap<String, LiveBattle>>,
+}
+
+impl BattleMiddleware {
+    /// Keeps the battle until it's finished. Returns the id of the battle.
+    pub fn register_battle(&self, battle: Battle) -> String {
+        let battle_id = random_token();
+        self.battles
+            .lock()
+            .expect("battle registry lock poisoned")
+            .insert(battle_id.clone(), Arc::new(tokio::sync::Mutex::new(battle)));
+
+        battle_id
+    }
+
+    pub fn get_battle(&self, battle_id: &str) -> Option<LiveBattle> {
+        self.battles
+            .lock()
+            .expect("battle registry lock poisoned")
+            .get(battle_id)
+            .cloned()
+    }
+
+    /// Whether the session is linked to a battle still being fought. The registry doesn't
+    /// outlive the server, so a link to a battle it doesn't know is stale.
+    pub fn is_in_live_battle(&self, session: &PlayerSession) -> bool {
+        session.is_in_battle
+            && session
+                .link_to_battle
+                .as_deref()
+                .is_some_and(|battle_id| self.get_battle(battle_id).is_some())
+    }
+
+    /// Battles in progress with their ids.
+    pub fn battles(&self) -> Vec<(String, LiveBattle)> {
+        self.battles
+            .lock()
+            .expect("battle registry lock poisoned")
+            .iter()
+            .map(|(battle_id, battle)| (battle_id.clone(), battle.clone()))
+            .collect()
+    }
+
+    pub fn remove_battle(&self, battle_id: &str) {
+        self.battles
+            .lock()
+            .expect("battle registry lock poisoned")
+            .remove(battle_id);
+    }
+
+    /// Bots of the level range, both ends included, with their weapons.
+    pub async fn get_bots_by_level(
+        &self,
+        min_level: i32,
+        max_level: i32,
+    ) -> Result<Vec<(Bot, WeaponItem)>> {
+        use crate::schema::bot::dsl::*;
+        use crate::schema::weapon_item::dsl::weapon_item;
+
+        let conn = self.db_pool.clone().get().await?;
+        Ok(conn
+            .interact(move |conn| {
+                bot.filter(level.between(min_level, max_level))
+                    .inner_join(weapon_item)
+                    .select((Bot::as_select(), WeaponItem::as_select()))
+                    .load::<(Bot, WeaponItem)>(conn)
+            })
+            .await??)
+    }
+
+    pub async fn get_bot_with_weapon(&self, b_id: i32) -> Result<(Bot, WeaponItem)> {
+        use crate::schema::bot::dsl::*;
+        use crate::schema::weapon_item::dsl::weapon_item;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            bot.filter(id.eq(b_id))
+                .inner_join(weapon_item)
+                .select((Bot::as_select(), WeaponItem::as_select()))
+                .first::<(Bot, WeaponItem)>(conn)
+        })
+        .await?
+        .map_err(|e| AppError::QueryError(e.to_string()))
+    }
+
+    /// Persists a finished battle: the `battle` row, one `battle_participant` row per
+    /// participant and the `battle_log`. Returns the id of the stored battle.
+    pub async fn save_finished_battle(&self, finished: &Battle) -> Result<i32> {
+        use crate::schema::battle::dsl::{battle, id};
+        use crate::schema::battle_log::dsl::battle_log;
+        use crate::schema::battle_participant::dsl::battle_participant;
+
+        let (Some(winner), Some(start_time), Some(end_time)) = (
+            finished.winner(),
+            finished.started_at(),
+            finished.finished_at(),
+        ) else {
+            return Err(AppError::Battle(BattleError::NotInProgress));
+        };
+
+        let new_battle = NewBattle {
+            start_time,
+            end_time,
+            winner: winner.id(),
+        };
+        let participants = finished.participant_records();
+        let log = finished.log().join("\n");
+
+        let conn = self.db_pool.clone().get().await?;
+        let tx_res = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    let battle_id: i32 = diesel::insert_into(battle)
+                        .values(new_battle)
+                        .returning(id)
+                        .get_result(conn)?;
+
+                    let participants: Vec<_> = participants
+                        .into_iter()
+                        .map(|mut p| {
+                            p.battle_id = battle_id;
+                            p
+                        })
+                        .collect();
+                    diesel::insert_into(battle_participant)
+                        .values(participants)
+                        .execute(conn)?;
+
+                    diesel::insert_into(battle_log)
+                        .values(BattleLog { id: battle_id, log })
+                        .execute(conn)?;
+
+                    Ok::<i32, diesel::result::Error>(battle_id)
+                })
+            })
+            .await?;
+
+        tx_res.map_err(|e| {
+            error!("Error during storing the battle results: {:?}", e);
+            AppError::QueryError(e.to_string())
+        })
+    }
+}
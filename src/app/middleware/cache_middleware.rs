@@ -1,29 +1,32 @@
-use crate::app::protos::messages::PlayerSession;
-use crate::app::redis::RedisConnectionManager;
-use crate::error::Result;
+use crate::app::protos::messages::{PlayerSession, RefreshToken, Travel};
+use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::app::stats::{CombatStats, Vitals};
+use crate::error::{AppError, Result};
 use crate::model::cache::PlayerInZone;
 use bon::Builder;
 use prost::Message;
-use redis::AsyncCommands;
-use std::sync::Arc;
-use strum::AsRefStr;
+use redis::{AsyncCommands, ExistenceCheck, Script, SetExpiry, SetOptions};
+use std::sync::{Arc, LazyLock};
 
-#[derive(Debug, PartialEq, Eq, Clone, AsRefStr)]
-pub enum CacheKey {
-    // container: Hash: player_id -> session
-    #[strum(serialize = "session")]
-    Session,
+/// Sessions last since login regardless of refreshes, so do the refresh tokens issued for them.
+pub const SESSION_TTL_SECONDS: u64 = 60 * 60 * 24 * 30; // 30 days
+pub const TRAVEL_GRACE_SECONDS: u64 = 60;
+// A session changed concurrently this many times in a row is given up on.
+pub const SESSION_UPDATE_ATTEMPTS: usize = 8;
 
-    // multiple containers, zone_{zone_id} -> player_id
-    #[strum(serialize = "zone")]
-    ZonePlayers,
-
-    #[strum(serialize = "rank_table")]
-    RankTable,
-
-    #[strum(serialize = "class_table")]
-    ClassTable,
-}
+/// Replaces the value with ARGV[2] keeping its TTL, if it's still ARGV[1].
+/// Returns 1 if it's replaced, 0 if the value has changed or is gone.
+static COMPARE_AND_SET: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+            return 1
+        end
+        return 0
+        ",
+    )
+});
 
 #[derive(Builder)]
 pub struct CacheMiddleware {
@@ -32,50 +35,307 @@ pub struct CacheMiddleware {
 
 impl CacheMiddleware {
     pub async fn get_players_in_zone(&self, zone_id: i64) -> Result<Vec<PlayerInZone>> {
-        let mut conn = self.cache_pool.get().await.unwrap();
+        let mut conn = self.cache_pool.get().await?;
         let members = conn
             .smembers::<&str, Vec<i64>>(Self::get_zone(zone_id).as_str())
             .await?;
 
         let mut vec = Vec::with_capacity(members.len());
         for player_id in members {
-            let buf = conn
-                .hget::<&str, i64, Vec<u8>>(CacheKey::Session.as_ref(), player_id)
-                .await?;
+            // Players whose session has expired are not shown.
+            let Some(buf) = conn
+                .get::<&str, Option<Vec<u8>>>(Self::get_session_key(player_id).as_str())
+                .await?
+            else {
+                continue;
+            };
             let PlayerSession {
                 id,
                 nickname,
                 level,
                 ..
             } = PlayerSession::decode(&buf[..])?;
+            let clan_link = conn
+                .hget::<&str, i64, Option<String>>(CacheKey::PlayerGuild.as_ref(), player_id)
+                .await?;
             vec.push(PlayerInZone {
                 id,
                 nickname,
                 level,
-                clan_link: None,
+                clan_link,
             })
         }
 
         Ok(vec)
     }
 
+    /// Moves the player between the zones, it joins the new zone even if it wasn't listed in
+    /// the old one.
     pub async fn move_player_to_zone(
         &self,
         old_zone_id: i64,
         new_zone_id: i64,
         player_id: i64,
-    ) -> eyre::Result<()> {
-        let from = Self::get_zone(old_zone_id);
-        let to = Self::get_zone(new_zone_id);
+    ) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        redis::pipe()
+            .atomic()
+            .srem(Self::get_zone(old_zone_id), player_id)
+            .sadd(Self::get_zone(new_zone_id), player_id)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds the player to the zone. Returns `false` if they were in it already.
+    pub async fn enter_zone(&self, zone_id: i64, player_id: i64) -> Result<bool> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .sadd::<&str, i64, bool>(Self::get_zone(zone_id).as_str(), player_id)
+            .await?)
+    }
+
+    /// Removes the player from the zone. Returns `false` if they weren't in it.
+    pub async fn leave_zone(&self, zone_id: i64, player_id: i64) -> Result<bool> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .srem::<&str, i64, bool>(Self::get_zone(zone_id).as_str(), player_id)
+            .await?)
+    }
+
+    pub async fn is_in_zone(&self, zone_id: i64, player_id: i64) -> Result<bool> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .sismember::<&str, i64, bool>(Self::get_zone(zone_id).as_str(), player_id)
+            .await?)
+    }
+
+    /// Stores the travel unless the player is travelling already. Returns whether it's stored.
+    /// The entry outlives the arrival a bit, so a lost arrival doesn't lock the player forever.
+    pub async fn start_travel(&self, travel: &Travel) -> Result<bool> {
+        let ttl = (travel.arrives_at - travel.started_at).max(0) as u64 + TRAVEL_GRACE_SECONDS;
+        let mut conn = self.cache_pool.get().await?;
+        let stored = conn
+            .set_options::<&str, Vec<u8>, Option<String>>(
+                Self::get_travel_key(travel.player_id).as_str(),
+                travel.encode_to_vec(),
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(ttl)),
+            )
+            .await?;
+
+        Ok(stored.is_some())
+    }
+
+    pub async fn get_travel(&self, player_id: i64) -> Result<Option<Travel>> {
+        let mut conn = self.cache_pool.get().await?;
+        let buf = conn
+            .get::<&str, Option<Vec<u8>>>(Self::get_travel_key(player_id).as_str())
+            .await?;
+
+        Ok(buf.map(|buf| Travel::decode(&buf[..])).transpose()?)
+    }
 
+    pub async fn finish_travel(&self, player_id: i64) -> Result<()> {
         let mut conn = self.cache_pool.get().await?;
-        conn.smove::<&str, &str, i64, ()>(from.as_str(), to.as_str(), player_id)
+        conn.del::<&str, ()>(Self::get_travel_key(player_id).as_str())
             .await?;
 
         Ok(())
     }
 
-    fn get_zone(zone_id: i64) -> String {
+    /// Current health and stamina of the player, regenerated up to now.
+    /// A player without stored vitals is at full health.
+    pub async fn get_vitals(
+        &self,
+        player_id: i64,
+        stats: &CombatStats,
+        now: i64,
+    ) -> Result<Vitals> {
+        let vitals = match self.get_session(player_id).await? {
+            Some(session) if session.vitals_updated_at > 0 => Vitals {
+                health: session.health,
+                stamina: session.stamina,
+                updated_at: session.vitals_updated_at,
+            }
+            .regenerate(stats, now),
+            _ => Vitals::full(stats, now),
+        };
+
+        Ok(vitals)
+    }
+
+    /// Starts a new session of the player replacing the previous one, if any.
+    pub async fn start_session(&self, session: &PlayerSession) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        conn.set_ex::<&str, Vec<u8>, ()>(
+            Self::get_session_key(session.id).as_str(),
+            session.encode_to_vec(),
+            SESSION_TTL_SECONDS,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Changes the session of the player keeping its TTL, an expired session stays expired.
+    /// The change is applied to the latest session and stored only if nobody has changed the
+    /// session in the meantime, otherwise it's applied again. A change returning `false` leaves
+    /// the session as it is. Returns the changed session, `None` if it's gone or unchanged.
+    pub async fn update_session<F>(
+        &self,
+        player_id: i64,
+        mut change: F,
+    ) -> Result<Option<PlayerSession>>
+    where
+        F: FnMut(&mut PlayerSession) -> bool,
+    {
+        let key = Self::get_session_key(player_id);
+        let mut conn = self.cache_pool.get().await?;
+        for _ in 0..SESSION_UPDATE_ATTEMPTS {
+            let Some(current) = conn.get::<&str, Option<Vec<u8>>>(key.as_str()).await? else {
+                return Ok(None);
+            };
+            let mut session = PlayerSession::decode(&current[..])?;
+            if !change(&mut session) {
+                return Ok(None);
+            }
+
+            let stored = COMPARE_AND_SET
+                .key(key.as_str())
+                .arg(current)
+                .arg(session.encode_to_vec())
+                .invoke_async::<bool>(&mut *conn)
+                .await?;
+            if stored {
+                return Ok(Some(session));
+            }
+        }
+
+        Err(AppError::SessionConflict(player_id))
+    }
+
+    pub async fn get_session(&self, player_id: i64) -> Result<Option<PlayerSession>> {
+        let mut conn = self.cache_pool.get().await?;
+        let buf = conn
+            .get::<&str, Option<Vec<u8>>>(Self::get_session_key(player_id).as_str())
+            .await?;
+
+        Ok(buf.map(|buf| PlayerSession::decode(&buf[..])).transpose()?)
+    }
+
+    /// Whether the player has a live session started with the token.
+    pub async fn check_session_token(&self, player_id: i64, token: &str) -> Result<bool> {
+        Ok(self
+            .get_session(player_id)
+            .await?
+            .is_some_and(|session| session.token == token))
+    }
+
+    /// Ends the session started with the token. Returns `false` if there was no such session.
+    pub async fn end_session(&self, player_id: i64, token: &str) -> Result<bool> {
+        if !self.check_session_token(player_id, token).await? {
+            return Ok(false);
+        }
+        self.drop_session(player_id).await?;
+
+        Ok(true)
+    }
+
+    /// Ends the session of the player whatever token it was started with.
+    pub async fn drop_session(&self, player_id: i64) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        conn.del::<&str, ()>(Self::get_session_key(player_id).as_str())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn save_refresh_token(
+        &self,
+        token: &str,
+        refresh_token: &RefreshToken,
+    ) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        conn.set_ex::<&str, Vec<u8>, ()>(
+            Self::get_refresh_token_key(token).as_str(),
+            refresh_token.encode_to_vec(),
+            SESSION_TTL_SECONDS,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>> {
+        let mut conn = self.cache_pool.get().await?;
+        let buf = conn
+            .get::<&str, Option<Vec<u8>>>(Self::get_refresh_token_key(token).as_str())
+            .await?;
+
+        Ok(buf.map(|buf| RefreshToken::decode(&buf[..])).transpose()?)
+    }
+
+    /// Marks the refresh token as used. Returns `false` if it was used already, even by a
+    /// concurrent request, which means the token was reused.
+    pub async fn consume_refresh_token(
+        &self,
+        token: &str,
+        refresh_token: &RefreshToken,
+    ) -> Result<bool> {
+        let mut conn = self.cache_pool.get().await?;
+        let previous = conn
+            .set_options::<&str, Vec<u8>, Option<Vec<u8>>>(
+                Self::get_refresh_token_key(token).as_str(),
+                RefreshToken {
+                    used: true,
+                    ..refresh_token.clone()
+                }
+                .encode_to_vec(),
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::XX)
+                    .with_expiration(SetExpiry::KEEPTTL)
+                    .get(true),
+            )
+            .await?
+            .map(|buf| RefreshToken::decode(&buf[..]))
+            .transpose()?;
+
+        Ok(previous.is_some_and(|previous| !previous.used))
+    }
+
+    /// Revokes every token of the family by ending the session it belongs to. Returns whether
+    /// a session was ended.
+    pub async fn revoke_refresh_family(&self, player_id: i64, family: &str) -> Result<bool> {
+        let Some(session) = self.get_session(player_id).await? else {
+            return Ok(false);
+        };
+        if session.refresh_family != family {
+            return Ok(false);
+        }
+        self.drop_session(player_id).await?;
+
+        Ok(true)
+    }
+
+    pub(crate) fn get_refresh_token_key(token: &str) -> String {
+        format!("{}_{token}", CacheKey::RefreshToken.as_ref())
+    }
+
+    pub(crate) fn get_session_key(player_id: i64) -> String {
+        format!("{}_{player_id}", CacheKey::Session.as_ref())
+    }
+
+    pub(crate) fn get_travel_key(player_id: i64) -> String {
+        format!("{}_{player_id}", CacheKey::Travel.as_ref())
+    }
+
+    pub(crate) fn get_zone(zone_id: i64) -> String {
         format!("{}_{zone_id}", CacheKey::ZonePlayers.as_ref())
     }
 }
@@ -0,0 +1,226 @@
+use crate::app::middleware::cache_middleware::CacheMiddleware;
+use crate::app::protos::messages::ChatMessage;
+use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::error::{AppError, Result};
+use bon::Builder;
+use chrono::Utc;
+use prost::Message;
+use redis::{AsyncCommands, SetExpiry, SetOptions};
+use std::sync::Arc;
+
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 300;
+pub const ZONE_CHAT_HISTORY_SIZE: isize = 100;
+pub const WHISPER_HISTORY_SIZE: isize = 50;
+
+// Every player can send up to CHAT_THROTTLE_MAX_MESSAGES messages per CHAT_THROTTLE_WINDOW_SECS.
+pub const CHAT_THROTTLE_MAX_MESSAGES: i64 = 5;
+pub const CHAT_THROTTLE_WINDOW_SECS: i64 = 10;
+// The very same message can't be repeated within this period.
+pub const CHAT_DUPLICATE_WINDOW_SECS: u64 = 30;
+
+const PROFANITY: [&str; 6] = ["damn", "shit", "fuck", "bitch", "bastard", "asshole"];
+
+/// Addressee of a whisper. Whisper histories are kept by player id, so a rename doesn't lose
+/// them.
+#[derive(Debug, Clone)]
+pub struct WhisperRecipient {
+    pub player_id: i64,
+    pub nickname: String,
+}
+
+#[derive(Builder)]
+pub struct ChatMiddleware {
+    pub cache_pool: Arc<bb8::Pool<RedisConnectionManager>>,
+}
+
+impl ChatMiddleware {
+    /// Validates, censors and stores a message, either into the zone history or into the
+    /// whisper histories of both sender and recipient.
+    pub async fn post_message(
+        &self,
+        zone_id: i64,
+        sender_id: i64,
+        sender: &str,
+        content: &str,
+        recipient: Option<WhisperRecipient>,
+    ) -> Result<ChatMessage> {
+        let content = censor(validate_content(content)?);
+        self.throttle(sender_id, &content).await?;
+
+        let mut conn = self.cache_pool.get().await?;
+        let id = conn
+            .incr::<&str, i64, i64>(CacheKey::ChatSequence.as_ref(), 1)
+            .await?;
+        let message = ChatMessage {
+            id,
+            zone_id,
+            sender: sender.to_owned(),
+            content,
+            sent_at: Utc::now().timestamp_millis(),
+            whisper_to: recipient.as_ref().map(|r| r.nickname.clone()),
+        };
+        let encoded = message.encode_to_vec();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match &recipient {
+            Some(recipient) => {
+                let mut player_ids = vec![sender_id, recipient.player_id];
+                // Whispering oneself keeps a single copy.
+                player_ids.dedup();
+                for player_id in player_ids {
+                    let key = Self::whisper_key(player_id);
+                    pipe.rpush(&key, &encoded)
+                        .ltrim(&key, -WHISPER_HISTORY_SIZE, -1);
+                }
+            }
+            None => {
+                let key = Self::zone_chat_key(zone_id);
+                pipe.rpush(&key, &encoded)
+                    .ltrim(&key, -ZONE_CHAT_HISTORY_SIZE, -1);
+            }
+        }
+        pipe.query_async::<()>(&mut *conn).await?;
+
+        Ok(message)
+    }
+
+    /// Returns zone messages and whispers of the player with an id greater than `since`,
+    /// ordered by id.
+    pub async fn get_messages(
+        &self,
+        zone_id: i64,
+        player_id: i64,
+        since: i64,
+    ) -> Result<Vec<ChatMessage>> {
+        let mut conn = self.cache_pool.get().await?;
+        let mut buffers = conn
+            .lrange::<&str, Vec<Vec<u8>>>(Self::zone_chat_key(zone_id).as_str(), 0, -1)
+            .await?;
+        buffers.extend(
+            conn.lrange::<&str, Vec<Vec<u8>>>(Self::whisper_key(player_id).as_str(), 0, -1)
+                .await?,
+        );
+
+        let mut messages = Vec::with_capacity(buffers.len());
+        for buf in buffers {
+            let message = ChatMessage::decode(&buf[..])?;
+            if message.id > since {
+                messages.push(message);
+            }
+        }
+        messages.sort_by_key(|message| message.id);
+
+        Ok(messages)
+    }
+
+    async fn throttle(&self, sender_id: i64, content: &str) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+
+        // The counter is created with its expiry in the same transaction, so it can't be left
+        // without one.
+        let counter_key = format!("{}_{sender_id}", CacheKey::ChatThrottle.as_ref());
+        let (sent,) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&counter_key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(CHAT_THROTTLE_WINDOW_SECS)
+            .ignore()
+            .incr(&counter_key, 1)
+            .query_async::<(i64,)>(&mut *conn)
+            .await?;
+        if sent > CHAT_THROTTLE_MAX_MESSAGES {
+            return Err(AppError::ChatThrottled);
+        }
+
+        // The message replaces the last one in the same command, so two concurrent duplicates
+        // can't both pass.
+        let last_key = format!("{}_{sender_id}", CacheKey::ChatLastMessage.as_ref());
+        let last_message = conn
+            .set_options::<&str, &str, Option<String>>(
+                last_key.as_str(),
+                content,
+                SetOptions::default()
+                    .with_expiration(SetExpiry::EX(CHAT_DUPLICATE_WINDOW_SECS))
+                    .get(true),
+            )
+            .await?;
+        if last_message.as_deref() == Some(content) {
+            return Err(AppError::ChatMessageRejected(
+                "the same message was just sent".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn zone_chat_key(zone_id: i64) -> String {
+        format!(
+            "{}_{}",
+            CacheKey::Chat.as_ref(),
+            CacheMiddleware::get_zone(zone_id)
+        )
+    }
+
+    fn whisper_key(player_id: i64) -> String {
+        format!("{}_{player_id}", CacheKey::ChatWhisper.as_ref())
+    }
+}
+
+pub fn validate_content(content: &str) -> Result<&str> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(AppError::ChatMessageRejected("message is empty".to_owned()));
+    }
+    if content.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+        return Err(AppError::ChatMessageRejected(format!(
+            "message is longer than {MAX_CHAT_MESSAGE_LENGTH} characters"
+        )));
+    }
+    Ok(content)
+}
+
+/// Masks profane words, keeping their first letter: "damn" -> "d***".
+pub fn censor(content: &str) -> String {
+    let mut censored = String::with_capacity(content.len());
+    let mut word = String::new();
+    for c in content.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if PROFANITY.contains(&word.to_lowercase().as_str()) {
+            let mut chars = word.chars();
+            censored.extend(chars.next());
+            censored.extend(chars.map(|_| '*'));
+        } else {
+            censored.push_str(&word);
+        }
+        word.clear();
+        censored.push(c);
+    }
+    censored.pop();
+    censored
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::middleware::chat_middleware::{censor, validate_content};
+
+    #[test]
+    fn when_message_contains_profanity_then_masked() {
+        assert_eq!(censor("Damn, that hurts!"), "D***, that hurts!");
+        assert_eq!(censor("damnation is fine"), "damnation is fine");
+        assert_eq!(censor("hello"), "hello");
+    }
+
+    #[test]
+    fn when_message_empty_or_too_long_then_rejected() {
+        assert_eq!(validate_content("  hi  ").unwrap(), "hi");
+        assert!(validate_content("   ").is_err());
+        assert!(validate_content(&"a".repeat(301)).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::app::battle::{BattleEvent, Faction};
+use crate::app::protos::messages::battle_event::{
+    Attacked, Died, Event, Finished, Moved, TurnStarted,
+};
+use crate::app::protos::messages::server_frame::Payload;
+use crate::app::protos::messages::zone_event::Kind;
+use crate::app::protos::messages::{
+    BattleDelta, BattleEvent as BattleEventFrame, ChatMessage, HexPosition, RankPromotion,
+    ServerFrame, SessionEnded, Travel, ZoneEvent,
+};
+use bon::Builder;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+pub const GATEWAY_CHANNEL_CAPACITY: usize = 1024;
+
+/// Routing key of a pushed frame. A WebSocket connection only receives frames of the topics it
+/// is subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Zone(i64),
+    // Battle events are sent per faction, each one gets only what it sees.
+    Battle(String, Faction),
+    // Personal frames by player id, every connection is subscribed to the topic of its own
+    // player.
+    Player(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct GatewayEvent {
+    pub topic: Topic,
+    pub frame: ServerFrame,
+}
+
+/// In-process fan-out of real-time events to the WebSocket connections.
+#[derive(Builder)]
+pub struct GatewayMiddleware {
+    #[builder(default = broadcast::channel(GATEWAY_CHANNEL_CAPACITY).0)]
+    sender: broadcast::Sender<GatewayEvent>,
+}
+
+impl GatewayMiddleware {
+    pub fn subscribe(&self) -> broadcast::Receiver<GatewayEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, topic: Topic, payload: Payload) {
+        // Sending fails only when nobody is connected, which is fine for a push channel.
+        if let Err(e) = self.sender.send(GatewayEvent {
+            topic,
+            frame: ServerFrame {
+                payload: Some(payload),
+            },
+        }) {
+            debug!("No gateway subscribers for the event: {:?}", e.0.topic);
+        }
+    }
+
+    pub fn publish_zone_move(
+        &self,
+        old_zone_id: i64,
+        new_zone_id: i64,
+        player_id: i64,
+        nickname: &str,
+    ) {
+        self.publish_zone_event(Kind::Leave, old_zone_id, player_id, nickname);
+        self.publish_zone_event(Kind::Join, new_zone_id, player_id, nickname);
+    }
+
+    pub fn publish_zone_event(&self, kind: Kind, zone_id: i64, player_id: i64, nickname: &str) {
+        self.publish(
+            Topic::Zone(zone_id),
+            Payload::ZoneEvent(ZoneEvent {
+                kind: kind.into(),
+                zone_id,
+                player_id,
+                nickname: nickname.to_owned(),
+            }),
+        );
+    }
+
+    /// Zone messages go to the zone, whispers to the sender and the recipient only.
+    pub fn publish_chat_message(
+        &self,
+        message: ChatMessage,
+        sender_id: i64,
+        recipient_id: Option<i64>,
+    ) {
+        match recipient_id {
+            Some(recipient_id) if recipient_id == sender_id => {
+                self.publish(Topic::Player(sender_id), Payload::ChatMessage(message));
+            }
+            Some(recipient_id) => {
+                self.publish(
+                    Topic::Player(sender_id),
+                    Payload::ChatMessage(message.clone()),
+                );
+                self.publish(Topic::Player(recipient_id), Payload::ChatMessage(message));
+            }
+            None => self.publish(Topic::Zone(message.zone_id), Payload::ChatMessage(message)),
+        }
+    }
+
+    pub fn publish_rank_promotion(&self, promotion: RankPromotion) {
+        self.publish(
+            Topic::Player(promotion.player_id),
+            Payload::RankPromotion(promotion),
+        );
+    }
+
+    pub fn publish_travel_finished(&self, travel: Travel) {
+        self.publish(
+            Topic::Player(travel.player_id),
+            Payload::TravelFinished(travel),
+        );
+    }
+
+    /// Tells the connections of the player that its session is over, so they get closed.
+    pub fn publish_session_ended(&self, player_id: i64) {
+        self.publish(
+            Topic::Player(player_id),
+            Payload::SessionEnded(SessionEnded { player_id }),
+        );
+    }
+
+    /// Tells the player about a battle they were pulled into, so they can subscribe to it.
+    pub fn publish_battle_started(&self, player_id: i64, battle_id: &str, events: &[BattleEvent]) {
+        self.publish(
+            Topic::Player(player_id),
+            Payload::BattleDelta(battle_delta(battle_id, events)),
+        );
+    }
+
+    pub fn publish_battle_events(&self, battle_id: &str, faction: Faction, events: &[BattleEvent]) {
+        self.publish(
+            Topic::Battle(battle_id.to_owned(), faction),
+            Payload::BattleDelta(battle_delta(battle_id, events)),
+        );
+    }
+}
+
+pub fn battle_delta(battle_id: &str, events: &[BattleEvent]) -> BattleDelta {
+    BattleDelta {
+        battle_id: battle_id.to_owned(),
+        events: events
+            .iter()
+            .map(|event| BattleEventFrame {
+                event: Some(battle_event_frame(event)),
+            })
+            .collect(),
+    }
+}
+
+fn battle_event_frame(event: &BattleEvent) -> Event {
+    match event {
+        BattleEvent::TurnStarted { participant, round } => Event::TurnStarted(TurnStarted {
+            participant: *participant as u32,
+            round: *round,
+        }),
+        BattleEvent::Moved {
+            participant,
+            path,
+            action_points_left,
+        } => Event::Moved(Moved {
+            participant: *participant as u32,
+            path: path
+                .iter()
+                .map(|(col, row)| HexPosition {
+                    col: *col,
+                    row: *row,
+                })
+                .collect(),
+            action_points_left: *action_points_left,
+        }),
+        BattleEvent::Attacked {
+            attacker,
+            target,
+            damage,
+            target_health,
+        } => Event::Attacked(Attacked {
+            attacker: *attacker as u32,
+            target: *target as u32,
+            damage: *damage,
+            target_health: *target_health,
+        }),
+        BattleEvent::Died { participant } => Event::Died(Died {
+            participant: *participant as u32,
+        }),
+        BattleEvent::Finished { winner } => Event::Finished(Finished {
+            winner_faction: winner.id(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::battle::{BattleEvent, Faction};
+    use crate::app::middleware::gateway_middleware::{GatewayMiddleware, Topic};
+    use crate::app::protos::messages::battle_event::Event;
+    use crate::app::protos::messages::server_frame::Payload;
+    use crate::app::protos::messages::ChatMessage;
+
+    #[test]
+    fn when_battle_events_published_then_delta_received_by_subscriber() {
+        let gateway = GatewayMiddleware::builder().build();
+        let mut receiver = gateway.subscribe();
+
+        gateway.publish_battle_events(
+            "b-1",
+            Faction::En,
+            &[
+                BattleEvent::Died { participant: 1 },
+                BattleEvent::Finished {
+                    winner: Faction::Fr,
+                },
+            ],
+        );
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.topic, Topic::Battle("b-1".to_owned(), Faction::En));
+        let Some(Payload::BattleDelta(delta)) = event.frame.payload else {
+            panic!("Battle delta expected");
+        };
+        assert_eq!(delta.battle_id, "b-1");
+        assert_eq!(delta.events.len(), 2);
+        assert!(matches!(
+            delta.events[1].event,
+            Some(Event::Finished(ref finished)) if finished.winner_faction == 1
+        ));
+    }
+
+    #[test]
+    fn when_whisper_published_then_sent_to_both_players_by_id() {
+        let gateway = GatewayMiddleware::builder().build();
+        let mut receiver = gateway.subscribe();
+
+        gateway.publish_chat_message(
+            ChatMessage {
+                id: 1,
+                zone_id: 3,
+                sender: "alice".to_owned(),
+                content: "hi".to_owned(),
+                sent_at: 0,
+                whisper_to: Some("bob".to_owned()),
+            },
+            1,
+            Some(2),
+        );
+
+        assert_eq!(receiver.try_recv().unwrap().topic, Topic::Player(1));
+        assert_eq!(receiver.try_recv().unwrap().topic, Topic::Player(2));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_whisper_to_oneself_published_then_sent_once() {
+        let gateway = GatewayMiddleware::builder().build();
+        let mut receiver = gateway.subscribe();
+
+        gateway.publish_chat_message(
+            ChatMessage {
+                id: 1,
+                zone_id: 3,
+                sender: "alice".to_owned(),
+                content: "note to self".to_owned(),
+                sent_at: 0,
+                whisper_to: Some("alice".to_owned()),
+            },
+            1,
+            Some(1),
+        );
+
+        assert_eq!(receiver.try_recv().unwrap().topic, Topic::Player(1));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_session_ended_published_then_sent_to_the_player() {
+        let gateway = GatewayMiddleware::builder().build();
+        let mut receiver = gateway.subscribe();
+
+        gateway.publish_session_ended(7);
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.topic, Topic::Player(7));
+        assert!(matches!(
+            event.frame.payload,
+            Some(Payload::SessionEnded(ref ended)) if ended.player_id == 7
+        ));
+    }
+}
@@ -0,0 +1,592 @@
+use crate::app::middleware::player_middleware::lower;
+use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::error::{AppError, Result};
+use crate::model::guild::{Guild, GuildInvite, GuildMember, GuildRole, NewGuild, NewGuildInvite};
+use bon::Builder;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::DatabaseError;
+use diesel::SqliteConnection;
+use redis::AsyncCommands;
+use serde::Serialize;
+use std::sync::Arc;
+
+pub const GUILD_NAME_MIN_LENGTH: usize = 3;
+pub const GUILD_NAME_MAX_LENGTH: usize = 32;
+pub const GUILD_MAX_MEMBERS: i64 = 50;
+/// A guild gets one more rank for every 5 members, up to `MAX_GUILD_RANK`.
+pub const MEMBERS_PER_GUILD_RANK: i64 = 5;
+pub const MAX_GUILD_RANK: i32 = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuildMemberInfo {
+    pub player_id: i32,
+    pub nickname: String,
+    pub level: i32,
+    pub role: GuildRole,
+}
+
+/// Invite of a guild waiting for the invited player to accept it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingInvite {
+    pub guild_id: i32,
+    pub guild_name: String,
+    pub invited_by: String,
+    /// Unix timestamp, seconds.
+    pub invited_at: i64,
+}
+
+#[derive(Builder)]
+pub struct GuildMiddleware {
+    pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
+    pub cache_pool: Arc<bb8::Pool<RedisConnectionManager>>,
+}
+
+impl GuildMiddleware {
+    pub async fn get_guild(&self, g_id: i32) -> Result<Guild> {
+        use crate::schema::guild::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            guild
+                .filter(id.eq(g_id))
+                .select(Guild::as_select())
+                .first(conn)
+                .optional()
+        })
+        .await??
+        .ok_or(AppError::GuildNotFound(g_id))
+    }
+
+    /// The guild of the player with the role they have in it.
+    pub async fn get_player_guild(&self, p_id: i32) -> Result<Option<(Guild, GuildRole)>> {
+        use crate::schema::guild::dsl::guild;
+        use crate::schema::guild_member::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let membership = conn
+            .interact(move |conn| {
+                guild_member
+                    .inner_join(guild)
+                    .filter(player_id.eq(p_id))
+                    .select((Guild::as_select(), role))
+                    .first::<(Guild, i32)>(conn)
+                    .optional()
+            })
+            .await??;
+
+        Ok(membership.map(|(g, r)| (g, GuildRole::from_id(r))))
+    }
+
+    pub async fn get_members(&self, g_id: i32) -> Result<Vec<GuildMemberInfo>> {
+        use crate::schema::guild_member::dsl::*;
+        use crate::schema::player::dsl::{nickname, player};
+        use crate::schema::player_attributes::dsl::{level, player_attributes};
+
+        let conn = self.db_pool.clone().get().await?;
+        let members = conn
+            .interact(move |conn| {
+                guild_member
+                    .inner_join(player.inner_join(player_attributes))
+                    .filter(guild_id.eq(g_id))
+                    .select((player_id, nickname, level, role))
+                    .order_by((role.desc(), joined_at))
+                    .load::<(i32, String, i32, i32)>(conn)
+            })
+            .await??;
+
+        Ok(members
+            .into_iter()
+            .map(|(p_id, nick, lvl, r)| GuildMemberInfo {
+                player_id: p_id,
+                nickname: nick,
+                level: lvl,
+                role: GuildRole::from_id(r),
+            })
+            .collect())
+    }
+
+    /// Creates a guild led by the player.
+    pub async fn create_guild(&self, p_id: i32, guild_name: String) -> Result<Guild> {
+        use crate::schema::guild::dsl::*;
+
+        check_guild_name(&guild_name)?;
+
+        let conn = self.db_pool.clone().get().await?;
+        let created = conn
+            .interact(move |conn| {
+                conn.transaction::<_, AppError, _>(|conn| {
+                    if membership(conn, p_id)?.is_some() {
+                        return Err(AppError::GuildRejected(
+                            "the player is already in a guild".to_owned(),
+                        ));
+                    }
+                    // Names differing in case only are the same name, the unique index on
+                    // the lowercased name settles the concurrent creations.
+                    let name_taken =
+                        || AppError::GuildRejected(format!("the name {guild_name} is taken"));
+                    let taken = guild
+                        .filter(lower(name).eq(guild_name.to_lowercase()))
+                        .count()
+                        .get_result::<i64>(conn)?;
+                    if taken > 0 {
+                        return Err(name_taken());
+                    }
+
+                    let created = diesel::insert_into(guild)
+                        .values(NewGuild {
+                            name: guild_name.clone(),
+                            rank: guild_rank(1),
+                        })
+                        .returning(Guild::as_returning())
+                        .get_result(conn)
+                        .map_err(|e| match e {
+                            DatabaseError(UniqueViolation, _) => name_taken(),
+                            e => e.into(),
+                        })?;
+                    join_guild(conn, p_id, created.id, GuildRole::Leader)?;
+
+                    Ok(created)
+                })
+            })
+            .await??;
+
+        self.set_clan_links(&[p_id], Some(created.id)).await?;
+
+        Ok(created)
+    }
+
+    /// Disbands the guild, only its leader can do it.
+    pub async fn disband_guild(&self, p_id: i32, g_id: i32) -> Result<()> {
+        use crate::schema::guild::dsl::{guild, id};
+        use crate::schema::guild_invite::dsl::{guild_id as invite_guild_id, guild_invite};
+        use crate::schema::guild_member::dsl::{guild_id, guild_member, player_id};
+
+        let conn = self.db_pool.clone().get().await?;
+        let former_members = conn
+            .interact(move |conn| {
+                conn.transaction::<_, AppError, _>(|conn| {
+                    let actor = member_of(conn, p_id, g_id)?;
+                    if GuildRole::from_id(actor.role) != GuildRole::Leader {
+                        return Err(AppError::Forbidden);
+                    }
+
+                    let member_ids = guild_member
+                        .filter(guild_id.eq(g_id))
+                        .select(player_id)
+                        .load::<i32>(conn)?;
+                    for member_id in &member_ids {
+                        leave_guild(conn, *member_id)?;
+                    }
+                    diesel::delete(guild_invite)
+                        .filter(invite_guild_id.eq(g_id))
+                        .execute(conn)?;
+                    diesel::delete(guild).filter(id.eq(g_id)).execute(conn)?;
+
+                    Ok(member_ids)
+                })
+            })
+            .await??;
+
+        self.set_clan_links(&former_members, None).await
+    }
+
+    /// Invites a guildless player, officers and the leader can invite.
+    pub async fn invite(&self, p_id: i32, g_id: i32, invitee_id: i32) -> Result<GuildInvite> {
+        use crate::schema::guild_invite::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let actor = member_of(conn, p_id, g_id)?;
+                if GuildRole::from_id(actor.role) < GuildRole::Officer {
+                    return Err(AppError::Forbidden);
+                }
+                if membership(conn, invitee_id)?.is_some() {
+                    return Err(AppError::GuildRejected(
+                        "the player is already in a guild".to_owned(),
+                    ));
+                }
+
+                diesel::delete(guild_invite)
+                    .filter(guild_id.eq(g_id))
+                    .filter(player_id.eq(invitee_id))
+                    .execute(conn)?;
+                let invite = diesel::insert_into(guild_invite)
+                    .values(NewGuildInvite {
+                        guild_id: g_id,
+                        player_id: invitee_id,
+                        invited_by: p_id,
+                    })
+                    .returning(GuildInvite::as_returning())
+                    .get_result(conn)?;
+
+                Ok(invite)
+            })
+        })
+        .await?
+    }
+
+    /// Accepts an invite of the guild, all other invites of the player are dropped.
+    pub async fn accept_invite(&self, p_id: i32, g_id: i32) -> Result<Guild> {
+        use crate::schema::guild::dsl::{guild, id, rank};
+        use crate::schema::guild_invite::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let joined = conn
+            .interact(move |conn| {
+                conn.transaction::<_, AppError, _>(|conn| {
+                    let invite = guild_invite
+                        .filter(guild_id.eq(g_id))
+                        .filter(player_id.eq(p_id))
+                        .select(GuildInvite::as_select())
+                        .first(conn)
+                        .optional()?;
+                    if invite.is_none() {
+                        return Err(AppError::GuildRejected(
+                            "there is no invite from the guild".to_owned(),
+                        ));
+                    }
+                    if membership(conn, p_id)?.is_some() {
+                        return Err(AppError::GuildRejected(
+                            "the player is already in a guild".to_owned(),
+                        ));
+                    }
+                    let members = members_count(conn, g_id)?;
+                    if members >= GUILD_MAX_MEMBERS {
+                        return Err(AppError::GuildRejected("the guild is full".to_owned()));
+                    }
+
+                    diesel::delete(guild_invite)
+                        .filter(player_id.eq(p_id))
+                        .execute(conn)?;
+                    join_guild(conn, p_id, g_id, GuildRole::Member)?;
+                    diesel::update(guild)
+                        .filter(id.eq(g_id))
+                        .set(rank.eq(guild_rank(members + 1)))
+                        .execute(conn)?;
+
+                    Ok(guild
+                        .filter(id.eq(g_id))
+                        .select(Guild::as_select())
+                        .first(conn)?)
+                })
+            })
+            .await??;
+
+        self.set_clan_links(&[p_id], Some(joined.id)).await?;
+
+        Ok(joined)
+    }
+
+    /// Removes a member ranked lower than the acting officer or leader.
+    pub async fn kick(&self, p_id: i32, g_id: i32, kicked_id: i32) -> Result<()> {
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let actor = member_of(conn, p_id, g_id)?;
+                let kicked = member_of(conn, kicked_id, g_id)?;
+                check_can_kick(
+                    GuildRole::from_id(actor.role),
+                    GuildRole::from_id(kicked.role),
+                )?;
+
+                leave_guild(conn, kicked_id)?;
+                update_rank(conn, g_id)
+            })
+        })
+        .await??;
+
+        self.set_clan_links(&[kicked_id], None).await
+    }
+
+    /// Leaves the guild. The leader can't leave, the guild has to be disbanded instead.
+    pub async fn leave(&self, p_id: i32, g_id: i32) -> Result<()> {
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let member = member_of(conn, p_id, g_id)?;
+                if GuildRole::from_id(member.role) == GuildRole::Leader {
+                    return Err(AppError::GuildRejected(
+                        "the leader has to disband the guild".to_owned(),
+                    ));
+                }
+
+                leave_guild(conn, p_id)?;
+                update_rank(conn, g_id)
+            })
+        })
+        .await??;
+
+        self.set_clan_links(&[p_id], None).await
+    }
+
+    /// Makes a member an officer, only the leader can do it.
+    pub async fn promote_officer(&self, p_id: i32, g_id: i32, promoted_id: i32) -> Result<()> {
+        use crate::schema::guild_member::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let actor = member_of(conn, p_id, g_id)?;
+                let promoted = member_of(conn, promoted_id, g_id)?;
+                check_can_promote(
+                    GuildRole::from_id(actor.role),
+                    GuildRole::from_id(promoted.role),
+                )?;
+
+                diesel::update(guild_member)
+                    .filter(player_id.eq(promoted_id))
+                    .set(role.eq(GuildRole::Officer.id()))
+                    .execute(conn)?;
+
+                Ok(())
+            })
+        })
+        .await?
+    }
+
+    /// Invites the player hasn't accepted yet, the latest first.
+    pub async fn get_pending_invites(&self, p_id: i32) -> Result<Vec<PendingInvite>> {
+        use crate::schema::guild::dsl::{guild, name};
+        use crate::schema::guild_invite::dsl::*;
+        use crate::schema::player::dsl::{id as inviter_id, nickname, player};
+
+        let conn = self.db_pool.clone().get().await?;
+        let invites = conn
+            .interact(move |conn| {
+                guild_invite
+                    .inner_join(guild)
+                    .inner_join(player.on(inviter_id.eq(invited_by.nullable())))
+                    .filter(player_id.eq(p_id))
+                    .select((guild_id, name, nickname, created_at))
+                    .order_by((created_at.desc(), id.desc()))
+                    .load::<(i32, String, String, NaiveDateTime)>(conn)
+            })
+            .await??;
+
+        Ok(invites
+            .into_iter()
+            .map(|(g_id, guild_name, inviter, invited_at)| PendingInvite {
+                guild_id: g_id,
+                guild_name,
+                invited_by: inviter,
+                invited_at: invited_at.and_utc().timestamp(),
+            })
+            .collect())
+    }
+
+    /// Caches the clan links of all guild members, the cache doesn't outlive a Redis restart.
+    pub async fn prefetch_clan_links(&self) -> Result<()> {
+        use crate::schema::guild_member::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let members = conn
+            .interact(|conn| {
+                guild_member
+                    .select((player_id, guild_id))
+                    .load::<(i32, i32)>(conn)
+            })
+            .await??;
+        let links = members
+            .into_iter()
+            .map(|(p_id, g_id)| (p_id, clan_link(g_id)))
+            .collect::<Vec<_>>();
+
+        let mut cache_conn = self.cache_pool.get().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(CacheKey::PlayerGuild.as_ref()).ignore();
+        if !links.is_empty() {
+            pipe.hset_multiple(CacheKey::PlayerGuild.as_ref(), &links)
+                .ignore();
+        }
+        pipe.query_async::<()>(&mut *cache_conn).await?;
+
+        Ok(())
+    }
+
+    /// Link to the guild of the player as shown next to them in the zone.
+    pub async fn get_clan_link(&self, p_id: i64) -> Result<Option<String>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        Ok(conn
+            .hget::<&str, i64, Option<String>>(CacheKey::PlayerGuild.as_ref(), p_id)
+            .await?)
+    }
+
+    async fn set_clan_links(&self, player_ids: &[i32], g_id: Option<i32>) -> Result<()> {
+        if player_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.cache_pool.get().await?;
+
+        match g_id {
+            Some(g_id) => {
+                let links = player_ids
+                    .iter()
+                    .map(|p_id| (*p_id, clan_link(g_id)))
+                    .collect::<Vec<_>>();
+                conn.hset_multiple::<&str, i32, String, ()>(CacheKey::PlayerGuild.as_ref(), &links)
+                    .await?
+            }
+            None => {
+                conn.hdel::<&str, &[i32], ()>(CacheKey::PlayerGuild.as_ref(), player_ids)
+                    .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn clan_link(g_id: i32) -> String {
+    format!("/guild/{g_id}")
+}
+
+pub fn guild_rank(members: i64) -> i32 {
+    (1 + members / MEMBERS_PER_GUILD_RANK).min(MAX_GUILD_RANK as i64) as i32
+}
+
+pub fn check_guild_name(name: &str) -> Result<()> {
+    let length = name.trim().chars().count();
+    if name.trim() != name || !(GUILD_NAME_MIN_LENGTH..=GUILD_NAME_MAX_LENGTH).contains(&length) {
+        return Err(AppError::GuildRejected(format!(
+            "the name has to be {GUILD_NAME_MIN_LENGTH}-{GUILD_NAME_MAX_LENGTH} characters \
+             without surrounding spaces"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Officers can kick members, the leader can kick anyone but themselves.
+pub fn check_can_kick(actor: GuildRole, kicked: GuildRole) -> Result<()> {
+    if actor >= GuildRole::Officer && actor > kicked {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+pub fn check_can_promote(actor: GuildRole, promoted: GuildRole) -> Result<()> {
+    if actor != GuildRole::Leader {
+        return Err(AppError::Forbidden);
+    }
+    if promoted != GuildRole::Member {
+        return Err(AppError::GuildRejected(
+            "only members can be promoted to officers".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn membership(conn: &mut SqliteConnection, p_id: i32) -> QueryResult<Option<GuildMember>> {
+    use crate::schema::guild_member::dsl::*;
+
+    guild_member
+        .filter(player_id.eq(p_id))
+        .select(GuildMember::as_select())
+        .first(conn)
+        .optional()
+}
+
+fn member_of(conn: &mut SqliteConnection, p_id: i32, g_id: i32) -> Result<GuildMember> {
+    match membership(conn, p_id)? {
+        Some(member) if member.guild_id == g_id => Ok(member),
+        _ => Err(AppError::GuildRejected(
+            "the player is not a member of the guild".to_owned(),
+        )),
+    }
+}
+
+fn members_count(conn: &mut SqliteConnection, g_id: i32) -> QueryResult<i64> {
+    use crate::schema::guild_member::dsl::*;
+
+    guild_member
+        .filter(guild_id.eq(g_id))
+        .count()
+        .get_result(conn)
+}
+
+fn join_guild(
+    conn: &mut SqliteConnection,
+    p_id: i32,
+    g_id: i32,
+    member_role: GuildRole,
+) -> QueryResult<()> {
+    use crate::schema::guild_member::dsl::guild_member;
+    use crate::schema::player::dsl::{guild_id, id, player};
+
+    diesel::insert_into(guild_member)
+        .values(GuildMember {
+            player_id: p_id,
+            guild_id: g_id,
+            role: member_role.id(),
+            joined_at: Utc::now().naive_utc(),
+        })
+        .execute(conn)?;
+    diesel::update(player)
+        .filter(id.eq(p_id))
+        .set(guild_id.eq(Some(g_id)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn leave_guild(conn: &mut SqliteConnection, p_id: i32) -> QueryResult<()> {
+    use crate::schema::guild_member::dsl::{guild_member, player_id};
+    use crate::schema::player::dsl::{guild_id, id, player};
+
+    diesel::delete(guild_member)
+        .filter(player_id.eq(p_id))
+        .execute(conn)?;
+    diesel::update(player)
+        .filter(id.eq(p_id))
+        .set(guild_id.eq(None::<i32>))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn update_rank(conn: &mut SqliteConnection, g_id: i32) -> Result<()> {
+    use crate::schema::guild::dsl::*;
+
+    let members = members_count(conn, g_id)?;
+    diesel::update(guild)
+        .filter(id.eq(g_id))
+        .set(rank.eq(guild_rank(members)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::middleware::guild_middleware::{
+        check_can_kick, check_can_promote, check_guild_name, guild_rank,
+    };
+    use crate::model::guild::GuildRole::{Leader, Member, Officer};
+
+    #[test]
+    fn when_roles_compared_then_only_higher_roles_manage_lower_ones() {
+        assert!(check_can_kick(Officer, Member).is_ok());
+        assert!(check_can_kick(Leader, Officer).is_ok());
+        assert!(check_can_kick(Officer, Officer).is_err());
+        assert!(check_can_kick(Member, Member).is_err());
+        assert!(check_can_promote(Leader, Member).is_ok());
+        assert!(check_can_promote(Officer, Member).is_err());
+        assert!(check_can_promote(Leader, Officer).is_err());
+    }
+
+    #[test]
+    fn when_guild_grows_then_rank_grows_up_to_max() {
+        assert_eq!(guild_rank(1), 1);
+        assert_eq!(guild_rank(5), 2);
+        assert_eq!(guild_rank(50), 5);
+        assert!(check_guild_name("Knights").is_ok());
+        assert!(check_guild_name(" Knights").is_err());
+        assert!(check_guild_name("Kn").is_err());
+    }
+}
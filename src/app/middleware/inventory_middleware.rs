@@ -0,0 +1,194 @@
+use crate::error::{AppError, Result};
+use crate::model::item::{GearItem, InventoryEntry, Item, PlayerInventory, WeaponItem};
+use crate::model::player::PlayerAttributes;
+use bon::Builder;
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use std::sync::Arc;
+
+#[derive(Builder)]
+pub struct InventoryMiddleware {
+    pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
+}
+
+impl InventoryMiddleware {
+    pub async fn get_inventory(&self, p_id: i32) -> Result<Vec<InventoryEntry>> {
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| load_inventory(conn, p_id, None))
+            .await?
+    }
+
+    /// Equips the inventory item if the player meets all its requirements.
+    /// Only one weapon can be equipped at a time, so equipping a weapon takes off the previous one.
+    pub async fn equip_item(
+        &self,
+        attributes: PlayerAttributes,
+        inventory_id: i32,
+    ) -> Result<InventoryEntry> {
+        use crate::schema::player_inventory::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let p_id = attributes.player_id;
+                let mut entry = load_inventory(conn, p_id, Some(inventory_id))?
+                    .pop()
+                    .ok_or(AppError::ItemNotFound(inventory_id))?;
+
+                check_item_requirements(&entry.item, &attributes)?;
+
+                if entry.weapon.is_some() {
+                    let weapon_slots = load_inventory(conn, p_id, None)?
+                        .into_iter()
+                        .filter(|e| e.slot.equipped && e.weapon.is_some())
+                        .map(|e| e.slot.id)
+                        .collect::<Vec<_>>();
+                    diesel::update(player_inventory)
+                        .filter(id.eq_any(weapon_slots))
+                        .set(equipped.eq(false))
+                        .execute(conn)?;
+                }
+
+                diesel::update(player_inventory)
+                    .filter(id.eq(inventory_id))
+                    .set(equipped.eq(true))
+                    .execute(conn)?;
+                entry.slot.equipped = true;
+
+                Ok(entry)
+            })
+        })
+        .await?
+    }
+
+    pub async fn unequip_item(&self, p_id: i32, inventory_id: i32) -> Result<()> {
+        use crate::schema::player_inventory::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let updated = conn
+            .interact(move |conn| {
+                diesel::update(player_inventory)
+                    .filter(id.eq(inventory_id))
+                    .filter(player_id.eq(p_id))
+                    .set(equipped.eq(false))
+                    .execute(conn)
+            })
+            .await??;
+
+        match updated {
+            0 => Err(AppError::ItemNotFound(inventory_id)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn load_inventory(
+    conn: &mut SqliteConnection,
+    p_id: i32,
+    inventory_id: Option<i32>,
+) -> Result<Vec<InventoryEntry>> {
+    use crate::schema::gear_item::dsl::gear_item;
+    use crate::schema::item::dsl::item;
+    use crate::schema::player_inventory::dsl::*;
+    use crate::schema::weapon_item::dsl::weapon_item;
+
+    let mut query = player_inventory
+        .inner_join(item.left_join(weapon_item).left_join(gear_item))
+        .filter(player_id.eq(p_id))
+        .select((
+            PlayerInventory::as_select(),
+            Item::as_select(),
+            Option::<WeaponItem>::as_select(),
+            Option::<GearItem>::as_select(),
+        ))
+        .order_by(id)
+        .into_boxed();
+    if let Some(inventory_id) = inventory_id {
+        query = query.filter(id.eq(inventory_id));
+    }
+
+    Ok(query
+        .load::<(PlayerInventory, Item, Option<WeaponItem>, Option<GearItem>)>(conn)?
+        .into_iter()
+        .map(|(slot, item_def, weapon, gear)| InventoryEntry {
+            slot,
+            item: item_def,
+            weapon,
+            gear,
+        })
+        .collect())
+}
+
+pub fn check_item_requirements(item: &Item, attributes: &PlayerAttributes) -> Result<()> {
+    let mut unmet: Vec<String> = [
+        ("level", item.level_req, attributes.level),
+        ("strength", item.strength_req, attributes.strength),
+        ("dexterity", item.dexterity_req, attributes.dexterity),
+        ("physique", item.physique_req, attributes.physique),
+        ("intellect", item.intellect_req, attributes.intellect),
+        ("valor", item.valor_req, attributes.valor),
+    ]
+    .iter()
+    .filter(|(_, required, actual)| actual < required)
+    .map(|(name, required, _)| format!("{name} {required}"))
+    .collect();
+
+    if let Some(class_req) = item.class_req {
+        if class_req != attributes.class_id {
+            unmet.push(format!("class {class_req}"));
+        }
+    }
+
+    if unmet.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::ItemRequirementsNotMet(unmet.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::middleware::inventory_middleware::check_item_requirements;
+    use crate::error::AppError;
+    use crate::model::item::Item;
+    use crate::model::player::PlayerAttributes;
+    use crate::model::DefaultModel;
+
+    fn bow() -> Item {
+        Item {
+            id: 1,
+            name: "Long bow".to_owned(),
+            level_req: 2,
+            strength_req: 3,
+            dexterity_req: 5,
+            physique_req: 0,
+            intellect_req: 0,
+            valor_req: 0,
+            class_req: Some(2),
+        }
+    }
+
+    #[test]
+    fn when_requirements_met_then_item_can_be_equipped() {
+        let attributes = PlayerAttributes {
+            class_id: 2,
+            level: 2,
+            dexterity: 5,
+            ..PlayerAttributes::default_model(1)
+        };
+
+        assert!(check_item_requirements(&bow(), &attributes).is_ok());
+    }
+
+    #[test]
+    fn when_requirements_not_met_then_all_of_them_reported() {
+        let attributes = PlayerAttributes::default_model(1);
+
+        match check_item_requirements(&bow(), &attributes) {
+            Err(AppError::ItemRequirementsNotMet(unmet)) => {
+                assert_eq!(unmet, "level 2, dexterity 5, class 2")
+            }
+            other => panic!("Unexpected result: {other:?}"),
+        }
+    }
+}
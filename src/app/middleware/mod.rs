@@ -1,3 +1,11 @@
+pub mod account_middleware;
+pub mod battle_middleware;
 pub mod cache_middleware;
+pub mod chat_middleware;
+pub mod gateway_middleware;
+pub mod guild_middleware;
+pub mod inventory_middleware;
+pub mod moderation_middleware;
 pub mod player_middleware;
+pub mod rate_limit_middleware;
 pub mod static_tables_cache_middleware;
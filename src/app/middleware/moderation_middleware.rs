@@ -0,0 +1,317 @@
+use crate::app::middleware::player_middleware::{already_taken, lower};
+use crate::app::validation::{validate_nickname, FieldError};
+use crate::error::{AppError, Result};
+use crate::model::moderation::{ModerationAction, ModerationAuditEntry, NewModerationAuditEntry};
+use crate::model::player::{Player, PlayerRole};
+use bon::Builder;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use std::sync::Arc;
+
+pub const MODERATION_REASON_MAX_LENGTH: usize = 500;
+pub const AUDIT_DEFAULT_LIMIT: i64 = 50;
+pub const AUDIT_MAX_LIMIT: i64 = 200;
+
+/// Bans, mutes and renames players. Every action is recorded in the moderation audit within
+/// the same transaction, admins can't be the target of one.
+#[derive(Builder)]
+pub struct ModerationMiddleware {
+    pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
+}
+
+impl ModerationMiddleware {
+    /// Bans the player until the given time, forever without one.
+    pub async fn ban(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        ban_reason_text: String,
+        until: Option<NaiveDateTime>,
+    ) -> Result<ModerationAuditEntry> {
+        use crate::schema::player::dsl::*;
+
+        let details =
+            until.map_or_else(|| "permanent".to_owned(), |until| format!("until {until}"));
+        let entry = audit_entry(
+            admin_id,
+            target_id,
+            ModerationAction::Ban,
+            Some(ban_reason_text.clone()),
+            Some(details),
+        );
+        self.moderate(entry, move |conn, _| {
+            diesel::update(player)
+                .filter(id.eq(target_id))
+                .set((
+                    banned.eq(1),
+                    ban_reason.eq(Some(ban_reason_text)),
+                    banned_until.eq(until),
+                ))
+                .execute(conn)?;
+
+            Ok(None)
+        })
+        .await
+    }
+
+    pub async fn unban(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        reason: Option<String>,
+    ) -> Result<ModerationAuditEntry> {
+        use crate::schema::player::dsl::*;
+
+        let entry = audit_entry(admin_id, target_id, ModerationAction::Unban, reason, None);
+        self.moderate(entry, move |conn, _| {
+            diesel::update(player)
+                .filter(id.eq(target_id))
+                .set((
+                    banned.eq(0),
+                    ban_reason.eq(None::<String>),
+                    banned_until.eq(None::<NaiveDateTime>),
+                ))
+                .execute(conn)?;
+
+            Ok(None)
+        })
+        .await
+    }
+
+    /// Keeps the player out of the chat until the given time.
+    pub async fn mute(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        reason: String,
+        until: NaiveDateTime,
+    ) -> Result<ModerationAuditEntry> {
+        use crate::schema::player::dsl::*;
+
+        let entry = audit_entry(
+            admin_id,
+            target_id,
+            ModerationAction::Mute,
+            Some(reason),
+            Some(format!("until {until}")),
+        );
+        self.moderate(entry, move |conn, _| {
+            diesel::update(player)
+                .filter(id.eq(target_id))
+                .set(muted_until.eq(Some(until)))
+                .execute(conn)?;
+
+            Ok(None)
+        })
+        .await
+    }
+
+    pub async fn unmute(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        reason: Option<String>,
+    ) -> Result<ModerationAuditEntry> {
+        use crate::schema::player::dsl::*;
+
+        let entry = audit_entry(admin_id, target_id, ModerationAction::Unmute, reason, None);
+        self.moderate(entry, move |conn, _| {
+            diesel::update(player)
+                .filter(id.eq(target_id))
+                .set(muted_until.eq(None::<NaiveDateTime>))
+                .execute(conn)?;
+
+            Ok(None)
+        })
+        .await
+    }
+
+    /// Replaces an offensive nickname, the new one has to pass the registration rules.
+    pub async fn rename(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        new_nickname: String,
+        reason: String,
+    ) -> Result<ModerationAuditEntry> {
+        use crate::schema::player::dsl::*;
+
+        validate_nickname(&new_nickname).map_err(|error| AppError::Validation(vec![error]))?;
+
+        let entry = audit_entry(
+            admin_id,
+            target_id,
+            ModerationAction::Rename,
+            Some(reason),
+            None,
+        );
+        self.moderate(entry, move |conn, target| {
+            let taken = player
+                .filter(lower(nickname).eq(new_nickname.to_lowercase()))
+                .filter(id.ne(target_id))
+                .count()
+                .get_result::<i64>(conn)?;
+            if taken > 0 {
+                return Err(already_taken("nickname"));
+            }
+
+            diesel::update(player)
+                .filter(id.eq(target_id))
+                .set(nickname.eq(&new_nickname))
+                .execute(conn)?;
+
+            Ok(Some(format!("{} -> {new_nickname}", target.nickname)))
+        })
+        .await
+    }
+
+    /// Records an action which doesn't change the player row, like ending the session.
+    pub async fn record(
+        &self,
+        admin_id: i32,
+        target_id: i32,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) -> Result<ModerationAuditEntry> {
+        let entry = audit_entry(admin_id, target_id, action, reason, None);
+        self.moderate(entry, |_, _| Ok(None)).await
+    }
+
+    /// The latest entries first, of one player or of everyone.
+    pub async fn get_audit(
+        &self,
+        target: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<ModerationAuditEntry>> {
+        use crate::schema::moderation_audit::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let entries = conn
+            .interact(move |conn| {
+                let mut query = moderation_audit
+                    .select(ModerationAuditEntry::as_select())
+                    .order_by(id.desc())
+                    .limit(limit.clamp(1, AUDIT_MAX_LIMIT))
+                    .into_boxed();
+                if let Some(target) = target {
+                    query = query.filter(target_id.eq(target));
+                }
+
+                query.load(conn)
+            })
+            .await??;
+
+        Ok(entries)
+    }
+
+    /// Applies the change to the target and records it in one transaction. The change may
+    /// return the details of the audit entry if they're known only after loading the target.
+    async fn moderate<F>(
+        &self,
+        mut entry: NewModerationAuditEntry,
+        change: F,
+    ) -> Result<ModerationAuditEntry>
+    where
+        F: FnOnce(&mut SqliteConnection, &Player) -> Result<Option<String>> + Send + 'static,
+    {
+        use crate::schema::moderation_audit::dsl::moderation_audit;
+        use crate::schema::player::dsl::{id, player};
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let target = player
+                    .filter(id.eq(entry.target_id))
+                    .first::<Player>(conn)
+                    .optional()?
+                    .ok_or_else(|| AppError::PlayerNotFound(entry.target_id.to_string()))?;
+                if PlayerRole::from_id(target.role) == PlayerRole::Admin {
+                    return Err(AppError::Forbidden);
+                }
+
+                if let Some(details) = change(conn, &target)? {
+                    entry.details = Some(details);
+                }
+
+                Ok(diesel::insert_into(moderation_audit)
+                    .values(entry)
+                    .returning(ModerationAuditEntry::as_returning())
+                    .get_result(conn)?)
+            })
+        })
+        .await?
+    }
+}
+
+fn audit_entry(
+    admin_id: i32,
+    target_id: i32,
+    action: ModerationAction,
+    reason: Option<String>,
+    details: Option<String>,
+) -> NewModerationAuditEntry {
+    NewModerationAuditEntry {
+        admin_id,
+        target_id,
+        action: action.as_ref().to_owned(),
+        reason,
+        details,
+    }
+}
+
+/// Bans, mutes and renames have to be explained.
+pub fn check_reason(reason: &str) -> Result<String> {
+    let reason = reason.trim();
+    let error = |message: String| {
+        Err(AppError::Validation(vec![FieldError::new(
+            "reason", message,
+        )]))
+    };
+
+    if reason.is_empty() {
+        return error("must not be empty".to_owned());
+    }
+    if reason.chars().count() > MODERATION_REASON_MAX_LENGTH {
+        return error(format!(
+            "must be at most {MODERATION_REASON_MAX_LENGTH} characters long"
+        ));
+    }
+
+    Ok(reason.to_owned())
+}
+
+/// End of a ban or a mute lasting the given number of seconds from now.
+pub fn expires_in(duration_secs: i64) -> Result<NaiveDateTime> {
+    let error = || AppError::Validation(vec![FieldError::new("duration_secs", "must be positive")]);
+    if duration_secs <= 0 {
+        return Err(error());
+    }
+
+    TimeDelta::try_seconds(duration_secs)
+        .and_then(|duration| Utc::now().naive_utc().checked_add_signed(duration))
+        .ok_or_else(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::middleware::moderation_middleware::{
+        check_reason, expires_in, MODERATION_REASON_MAX_LENGTH,
+    };
+    use chrono::Utc;
+
+    #[test]
+    fn when_reason_blank_or_too_long_then_rejected_else_trimmed() {
+        assert!(check_reason("   ").is_err());
+        assert!(check_reason(&"a".repeat(MODERATION_REASON_MAX_LENGTH + 1)).is_err());
+        assert_eq!(check_reason("  spam  ").unwrap(), "spam");
+    }
+
+    #[test]
+    fn when_duration_not_positive_or_overflowing_then_rejected() {
+        assert!(expires_in(0).is_err());
+        assert!(expires_in(-60).is_err());
+        assert!(expires_in(i64::MAX).is_err());
+        assert!(expires_in(60).unwrap() > Utc::now().naive_utc());
+    }
+}
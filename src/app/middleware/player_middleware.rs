@@ -1,21 +1,38 @@
+use crate::app::progression::{
+    add_spec_progress, allocate_attributes, check_class_selection, progress, rank_promotion,
+    respec_attributes, AttributeAllocation, Progression,
+};
+use crate::app::protos::messages::RankPromotion;
 use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::app::validation::FieldError;
 use crate::error::AppError::PlayerNotFound;
 use crate::error::{AppError, Result};
-use crate::model::player::{Player, PlayerAttributes};
+use crate::model::player::{NewPlayerClassProgress, Player, PlayerAttributes, PlayerClassProgress};
+use crate::model::r#static::{PlayerExperienceTable, RankRequirement};
 use crate::model::DefaultModel;
 use crate::schema::player::dsl::player;
 use crate::schema::player::nickname;
 use crate::schema::player_attributes::dsl::player_attributes;
 use bon::Builder;
-use diesel::result::Error::RollbackTransaction;
+use diesel::define_sql_function;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, RollbackTransaction};
+use diesel::sql_types::Text;
 use diesel::QueryDsl;
-use diesel::{Connection, ExpressionMethods, RunQueryDsl, SelectableHelper};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, RunQueryDsl, SelectableHelper};
+use prost::Message;
 use redis::AsyncCommands;
 use std::sync::Arc;
 use tracing::error;
 
 pub type PlayerWithAttributes = (Player, PlayerAttributes);
 
+define_sql_function!(fn lower(x: Text) -> Text);
+
+pub(crate) fn already_taken(field: &'static str) -> AppError {
+    AppError::AlreadyTaken(FieldError::new(field, "is already taken"))
+}
+
 #[derive(Builder)]
 pub struct PlayerMiddleware {
     pub db_pool: Arc<deadpool_diesel::sqlite::Pool>,
@@ -53,15 +70,34 @@ impl PlayerMiddleware {
         let nick = new_player.nickname.clone();
         let tx_res = conn
             .interact(move |conn| {
-                conn.transaction(|conn| {
+                conn.transaction::<_, AppError, _>(|conn| {
+                    // Nicknames and emails differing in case only are the same ones.
+                    let taken = player
+                        .filter(lower(nickname).eq(new_player.nickname.to_lowercase()))
+                        .or_filter(lower(email).eq(new_player.email.to_lowercase()))
+                        .select((nickname, email))
+                        .load::<(String, String)>(conn)?;
+                    if taken.iter().any(|(taken_nickname, _)| {
+                        taken_nickname.eq_ignore_ascii_case(&new_player.nickname)
+                    }) {
+                        return Err(already_taken("nickname"));
+                    }
+                    if !taken.is_empty() {
+                        return Err(already_taken("email"));
+                    }
+
                     // Insert into `player` table.
                     let p: Player = diesel::insert_into(player)
                         .values(new_player)
                         .returning(Player::as_returning())
-                        .get_result(conn)?;
+                        .get_result(conn)
+                        .map_err(|e| match e {
+                            DatabaseError(UniqueViolation, _) => already_taken("nickname"),
+                            e => e.into(),
+                        })?;
 
                     let Some(player_id) = p.id else {
-                        return Err(RollbackTransaction);
+                        return Err(RollbackTransaction.into());
                     };
 
                     // Insert defaults into `player_attributes`
@@ -76,6 +112,7 @@ impl PlayerMiddleware {
 
         match tx_res {
             Ok(p) => Ok(p),
+            Err(e @ AppError::AlreadyTaken(_)) => Err(e),
             Err(e) => {
                 error!("Error during player registration: {:?}", e);
                 Err(AppError::PlayerCannotRegister(nick))
@@ -83,12 +120,17 @@ impl PlayerMiddleware {
         }
     }
 
+    /// Nicknames and emails are unique regardless of case, so they're looked up that way too.
     pub async fn get_player_by_nick(&self, nick: String) -> Result<Player> {
         let conn = self.db_pool.clone().get().await?;
         let _nick: String = nick.clone();
 
         let query_result = conn
-            .interact(move |conn| player.filter(nickname.eq(nick)).first::<Player>(conn))
+            .interact(move |conn| {
+                player
+                    .filter(lower(nickname).eq(nick.to_lowercase()))
+                    .first::<Player>(conn)
+            })
             .await?;
 
         match query_result {
@@ -97,6 +139,85 @@ impl PlayerMiddleware {
         }
     }
 
+    /// Id of the player with the nickname, whatever its case.
+    pub async fn get_player_id_by_nick(&self, nick: String) -> Result<i32> {
+        self.get_player_by_nick(nick.clone())
+            .await?
+            .id
+            .ok_or(PlayerNotFound(nick))
+    }
+
+    pub async fn get_player_by_id(&self, p_id: i32) -> Result<Option<Player>> {
+        use crate::schema::player::dsl::id;
+
+        let conn = self.db_pool.clone().get().await?;
+
+        Ok(conn
+            .interact(move |conn| player.filter(id.eq(p_id)).first::<Player>(conn).optional())
+            .await??)
+    }
+
+    pub async fn get_player_by_email(&self, player_email: String) -> Result<Option<Player>> {
+        use crate::schema::player::dsl::email;
+
+        let conn = self.db_pool.clone().get().await?;
+
+        Ok(conn
+            .interact(move |conn| {
+                player
+                    .filter(lower(email).eq(player_email.to_lowercase()))
+                    .first::<Player>(conn)
+                    .optional()
+            })
+            .await??)
+    }
+
+    pub async fn set_email_verified(&self, p_id: i32) -> Result<()> {
+        use crate::schema::player::dsl::{email_verified, id};
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            diesel::update(player)
+                .filter(id.eq(p_id))
+                .set(email_verified.eq(1))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Stores the password, already hashed.
+    pub async fn set_password(&self, p_id: i32, password_hash: String) -> Result<()> {
+        use crate::schema::player::dsl::{id, password};
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            diesel::update(player)
+                .filter(id.eq(p_id))
+                .set(password.eq(password_hash))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    pub async fn set_last_map_location(&self, p_id: i32, location_id: i32) -> Result<()> {
+        use crate::schema::player::dsl::{id, last_map_location};
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            diesel::update(player)
+                .filter(id.eq(p_id))
+                .set(last_map_location.eq(Some(location_id)))
+                .execute(conn)
+        })
+        .await??;
+
+        Ok(())
+    }
+
     pub async fn get_full_player_info_by_nick(&self, nick: String) -> Result<PlayerWithAttributes> {
         let conn = self.db_pool.clone().get().await?;
         let _nick: String = nick.clone();
@@ -104,7 +225,7 @@ impl PlayerMiddleware {
         let query_result = conn
             .interact(move |conn| {
                 player
-                    .filter(nickname.eq(nick))
+                    .filter(lower(nickname).eq(nick.to_lowercase()))
                     .inner_join(player_attributes)
                     .first::<(Player, PlayerAttributes)>(conn)
             })
@@ -116,66 +237,277 @@ impl PlayerMiddleware {
         }
     }
 
-    pub async fn store_player_session_token(&self, nick: &str, token: String) -> Result<()> {
-        let mut conn = self.cache_pool.get().await?;
-        let set_member = format!("{}:{}", nick, token);
-        conn.sadd::<&str, &str, ()>(CacheKey::PlayerSession.as_ref(), set_member.as_str())
-            .await
-            .map_err(|e| {
-                error!("Error during storing player session token: {:?}", e);
-                AppError::CacheError(e)
+    pub async fn get_attributes(&self, p_id: i32) -> Result<PlayerAttributes> {
+        use crate::schema::player_attributes::dsl::player_id;
+
+        let conn = self.db_pool.clone().get().await?;
+        let attributes = conn
+            .interact(move |conn| {
+                player_attributes
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)
             })
+            .await??;
+
+        Ok(attributes)
     }
 
-    pub async fn check_player_session_token(
+    /// Adds experience to the player and grants rewards of every crossed threshold of the
+    /// experience table in one transaction.
+    pub async fn add_experience(
         &self,
-        nick: &str,
-        token_to_compare: String,
-    ) -> Result<bool> {
-        let mut conn = self.cache_pool.get().await?;
-        let set_member = format!("{}:{}", nick, token_to_compare);
-        Ok(1 == conn
-            .sismember::<&str, &str, i64>(CacheKey::PlayerSession.as_ref(), set_member.as_str())
-            .await
-            .map_err(|e| {
-                error!("Error during storing player session token: {:?}", e);
-                AppError::CacheError(e)
-            })?)
+        p_id: i32,
+        gained: i32,
+        experience_table: Vec<PlayerExperienceTable>,
+    ) -> Result<Progression> {
+        use crate::schema::player_attributes::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let mut attributes = player_attributes
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)?;
+
+                let progression = progress(&experience_table, &attributes, gained);
+                progression.apply(&mut attributes);
+
+                diesel::update(player_attributes)
+                    .filter(player_id.eq(p_id))
+                    .set((
+                        experience.eq(attributes.experience),
+                        level.eq(attributes.level),
+                        up.eq(attributes.up),
+                        attribute_points.eq(attributes.attribute_points),
+                        money.eq(attributes.money),
+                    ))
+                    .execute(conn)?;
+
+                Ok(progression)
+            })
+        })
+        .await?
     }
 
-    pub async fn remove_player_session_token(&self, nick: &str, access_token: &str) -> Result<()> {
-        let mut conn = self.cache_pool.get().await?;
-        let set_member = format!("{}:{}", nick, access_token);
-        conn.srem::<&str, &str, ()>(CacheKey::PlayerSession.as_ref(), set_member.as_str())
+    /// Spends unallocated attribute points of the player.
+    pub async fn allocate_attributes(
+        &self,
+        p_id: i32,
+        allocation: AttributeAllocation,
+    ) -> Result<PlayerAttributes> {
+        self.update_attributes(p_id, move |attributes| {
+            allocate_attributes(attributes, &allocation)
+        })
+        .await
+    }
+
+    /// Resets the player attributes to the defaults and refunds the spent points for money.
+    pub async fn respec_attributes(&self, p_id: i32) -> Result<PlayerAttributes> {
+        self.update_attributes(p_id, |attributes| respec_attributes(attributes).map(|_| ()))
             .await
-            .map_err(|e| {
-                error!("Error during removing player session token: {:?}", e);
-                AppError::CacheError(e)
-            })
     }
 
-    // Note: just an example of inner JOIN and transaction
-    async fn inc_valor(&self, p_id: i32, rank_up: bool) -> Result<()> {
+    /// Loads, changes and stores the attributes in one transaction.
+    async fn update_attributes<F>(&self, p_id: i32, change: F) -> Result<PlayerAttributes>
+    where
+        F: FnOnce(&mut PlayerAttributes) -> std::result::Result<(), String> + Send + 'static,
+    {
         use crate::schema::player_attributes::dsl::*;
-        let conn = &self.db_pool.get().await?;
-        let _ = conn
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let mut attributes = player_attributes
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)?;
+
+                change(&mut attributes).map_err(AppError::AttributesRejected)?;
+
+                diesel::update(player_attributes)
+                    .filter(player_id.eq(p_id))
+                    .set((
+                        strength.eq(attributes.strength),
+                        dexterity.eq(attributes.dexterity),
+                        physique.eq(attributes.physique),
+                        luck.eq(attributes.luck),
+                        intellect.eq(attributes.intellect),
+                        attribute_points.eq(attributes.attribute_points),
+                        money.eq(attributes.money),
+                    ))
+                    .execute(conn)?;
+
+                Ok(attributes)
+            })
+        })
+        .await?
+    }
+
+    pub async fn get_class_progress(&self, p_id: i32) -> Result<Option<PlayerClassProgress>> {
+        use crate::schema::player_class_progress::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        let class_progress = conn
             .interact(move |conn| {
-                conn.transaction(|conn| {
-                    diesel::update(player_attributes)
-                        .filter(player_id.eq(p_id))
-                        .set(valor.eq(valor + 1))
-                        .execute(conn)?;
-                    if rank_up {
-                        diesel::update(player_attributes)
-                            .filter(player_id.eq(p_id))
-                            .set(rank_id.eq(rank_id + 1))
-                            .execute(conn)?;
-                    }
-                    Ok::<(), deadpool_diesel::Error>(())
-                })
+                player_class_progress
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerClassProgress>(conn)
+                    .optional()
             })
+            .await??;
+
+        Ok(class_progress)
+    }
+
+    /// Sets the class of a classless player and starts its spec progress from scratch.
+    pub async fn choose_class(
+        &self,
+        p_id: i32,
+        new_class_id: i32,
+    ) -> Result<(PlayerAttributes, PlayerClassProgress)> {
+        use crate::schema::player_attributes::dsl as attrs;
+        use crate::schema::player_class_progress::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let mut attributes = player_attributes
+                    .filter(attrs::player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)?;
+                check_class_selection(&attributes, new_class_id)
+                    .map_err(AppError::ClassRejected)?;
+
+                diesel::update(player_attributes)
+                    .filter(attrs::player_id.eq(p_id))
+                    .set(attrs::class_id.eq(new_class_id))
+                    .execute(conn)?;
+                attributes.class_id = new_class_id;
+
+                diesel::delete(player_class_progress)
+                    .filter(player_id.eq(p_id))
+                    .execute(conn)?;
+                diesel::insert_into(player_class_progress)
+                    .values(NewPlayerClassProgress {
+                        player_id: p_id,
+                        class_id: new_class_id,
+                    })
+                    .execute(conn)?;
+                let class_progress = player_class_progress
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerClassProgress>(conn)?;
+
+                Ok((attributes, class_progress))
+            })
+        })
+        .await?
+    }
+
+    /// Spends attribute points on the progress of a spec of the current class.
+    pub async fn add_spec_progress(
+        &self,
+        p_id: i32,
+        spec: u8,
+        points: i32,
+    ) -> Result<(PlayerAttributes, PlayerClassProgress)> {
+        use crate::schema::player_attributes::dsl as attrs;
+        use crate::schema::player_class_progress::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let mut attributes = player_attributes
+                    .filter(attrs::player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)?;
+                let mut class_progress = player_class_progress
+                    .filter(player_id.eq(p_id))
+                    .filter(class_id.eq(attributes.class_id))
+                    .first::<PlayerClassProgress>(conn)
+                    .optional()?
+                    .ok_or_else(|| AppError::ClassRejected("no class chosen".to_owned()))?;
+
+                add_spec_progress(&mut class_progress, &mut attributes, spec, points)
+                    .map_err(AppError::ClassRejected)?;
+
+                diesel::update(player_attributes)
+                    .filter(attrs::player_id.eq(p_id))
+                    .set(attrs::attribute_points.eq(attributes.attribute_points))
+                    .execute(conn)?;
+                diesel::update(player_class_progress)
+                    .filter(player_id.eq(p_id))
+                    .set((
+                        first_spec_progress.eq(class_progress.first_spec_progress),
+                        second_spec_progress.eq(class_progress.second_spec_progress),
+                        third_spec_progress.eq(class_progress.third_spec_progress),
+                    ))
+                    .execute(conn)?;
+                // `total_progress` is a generated column, so the row is read back.
+                let class_progress = player_class_progress
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerClassProgress>(conn)?;
+
+                Ok((attributes, class_progress))
+            })
+        })
+        .await?
+    }
+
+    /// Adds valor to the player and promotes them to the highest rank they qualify for in one
+    /// transaction. Returns the previous and the new rank ids if the player got promoted.
+    pub async fn add_valor(
+        &self,
+        p_id: i32,
+        gained: i32,
+        rank_requirements: Vec<RankRequirement>,
+    ) -> Result<Option<(i32, i32)>> {
+        use crate::schema::player_attributes::dsl::*;
+
+        let conn = self.db_pool.clone().get().await?;
+        conn.interact(move |conn| {
+            conn.transaction::<_, AppError, _>(|conn| {
+                let mut attributes = player_attributes
+                    .filter(player_id.eq(p_id))
+                    .first::<PlayerAttributes>(conn)?;
+                attributes.valor = attributes.valor.saturating_add(gained.max(0));
+
+                let promotion = rank_promotion(&rank_requirements, &attributes)
+                    .map(|new_rank_id| (attributes.rank_id, new_rank_id));
+                if let Some((_, new_rank_id)) = promotion {
+                    attributes.rank_id = new_rank_id;
+                }
+
+                diesel::update(player_attributes)
+                    .filter(player_id.eq(p_id))
+                    .set((valor.eq(attributes.valor), rank_id.eq(attributes.rank_id)))
+                    .execute(conn)?;
+
+                Ok(promotion)
+            })
+        })
+        .await?
+    }
+
+    /// Keeps the latest rank promotion of the player, so it can be shown in the profile.
+    pub async fn save_rank_promotion(&self, promotion: &RankPromotion) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        conn.hset::<&str, i64, Vec<u8>, ()>(
+            CacheKey::RankPromotion.as_ref(),
+            promotion.player_id,
+            promotion.encode_to_vec(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Error during saving rank promotion: {:?}", e);
+            AppError::CacheError(e)
+        })
+    }
+
+    pub async fn get_rank_promotion(&self, p_id: i32) -> Result<Option<RankPromotion>> {
+        let mut conn = self.cache_pool.get().await?;
+        let encoded = conn
+            .hget::<&str, i32, Option<Vec<u8>>>(CacheKey::RankPromotion.as_ref(), p_id)
             .await?;
 
-        Ok(())
+        Ok(encoded
+            .map(|buf| RankPromotion::decode(&buf[..]))
+            .transpose()?)
     }
 }
@@ -0,0 +1,417 @@
+use crate::app::redis::{CacheKey, RedisConnectionManager};
+use crate::app_state::AppState;
+use crate::error::{AppError, Result};
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use bon::Builder;
+use chrono::Utc;
+use ipnet::IpNet;
+use rand::Rng;
+use redis::{AsyncCommands, Script};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock};
+use strum::{AsRefStr, EnumIter, IntoEnumIterator};
+use thiserror::Error;
+
+// After this many wrong passwords in a row the nickname is locked out, every further
+// failure doubles the lockout up to LOGIN_MAX_LOCKOUT_SECS.
+pub const LOGIN_FAILURES_BEFORE_LOCKOUT: u32 = 5;
+pub const LOGIN_BASE_LOCKOUT_SECS: u64 = 30;
+pub const LOGIN_MAX_LOCKOUT_SECS: u64 = 60 * 60;
+// Failures are forgotten after this long without a new one.
+pub const LOGIN_FAILURES_TTL_SECS: i64 = 60 * 60 * 24;
+
+/// Adds the request to the sliding window unless the window is full.
+/// Returns 0 if the request is allowed, otherwise milliseconds until a slot frees up.
+static SLIDING_WINDOW: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local now, window, limit = tonumber(ARGV[1]), tonumber(ARGV[2]), tonumber(ARGV[3])
+        redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+        if redis.call('ZCARD', KEYS[1]) < limit then
+            redis.call('ZADD', KEYS[1], now, ARGV[4])
+            redis.call('PEXPIRE', KEYS[1], window)
+            return 0
+        end
+        local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+        return math.max(tonumber(oldest[2]) + window - now, 1)
+        ",
+    )
+});
+
+/// Comma separated addresses or networks of the reverse proxies, e.g. `10.0.0.0/8`.
+/// `X-Forwarded-For` is taken into account only for the requests coming from them.
+pub const TRUSTED_PROXIES_VAR: &str = "TRUSTED_PROXIES";
+/// Prefix of the variables overriding the default limits, `RATE_LIMIT_LOGIN=10/60` allows
+/// 10 logins per 60 seconds.
+pub const RATE_LIMIT_VAR_PREFIX: &str = "RATE_LIMIT_";
+
+#[derive(Error, Debug)]
+pub enum RateLimitConfigError {
+    #[error("Malformed {0}={1}, expected max_requests/window_secs")]
+    MalformedLimit(String, String),
+    #[error("Malformed trusted proxy {0}, expected an address or a network")]
+    MalformedProxy(String),
+}
+
+/// Routes with their own limits, a route can be limited per IP and per player separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum RateLimitedRoute {
+    Login,
+    Register,
+    TokenRefresh,
+    AccountMail,
+}
+
+/// At most `max_requests` within any `window_secs` long period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window_secs: u64,
+}
+
+pub fn default_rate_limits() -> HashMap<RateLimitedRoute, RateLimit> {
+    HashMap::from([
+        (
+            RateLimitedRoute::Login,
+            RateLimit {
+                max_requests: 10,
+                window_secs: 60,
+            },
+        ),
+        (
+            RateLimitedRoute::Register,
+            RateLimit {
+                max_requests: 3,
+                window_secs: 60 * 10,
+            },
+        ),
+        (
+            RateLimitedRoute::AccountMail,
+            RateLimit {
+                max_requests: 5,
+                window_secs: 60 * 60,
+            },
+        ),
+        (
+            RateLimitedRoute::TokenRefresh,
+            RateLimit {
+                max_requests: 30,
+                window_secs: 60,
+            },
+        ),
+    ])
+}
+
+/// Default limits overridden by the `RATE_LIMIT_{ROUTE}` variables which are set.
+pub fn rate_limits_from_env(
+) -> std::result::Result<HashMap<RateLimitedRoute, RateLimit>, RateLimitConfigError> {
+    let mut limits = default_rate_limits();
+    for route in RateLimitedRoute::iter() {
+        let var = format!(
+            "{RATE_LIMIT_VAR_PREFIX}{}",
+            route.as_ref().to_ascii_uppercase()
+        );
+        if let Ok(value) = env::var(&var) {
+            let limit =
+                parse_rate_limit(&value).ok_or(RateLimitConfigError::MalformedLimit(var, value))?;
+            limits.insert(route, limit);
+        }
+    }
+
+    Ok(limits)
+}
+
+/// Parses `max_requests/window_secs`.
+pub fn parse_rate_limit(value: &str) -> Option<RateLimit> {
+    let (max_requests, window_secs) = value.split_once('/')?;
+
+    Some(RateLimit {
+        max_requests: max_requests.trim().parse().ok()?,
+        window_secs: window_secs.trim().parse().ok()?,
+    })
+}
+
+pub fn trusted_proxies_from_env() -> std::result::Result<Vec<IpNet>, RateLimitConfigError> {
+    env::var(TRUSTED_PROXIES_VAR).map_or(Ok(vec![]), |value| parse_trusted_proxies(&value))
+}
+
+/// Parses comma separated addresses and networks.
+pub fn parse_trusted_proxies(value: &str) -> std::result::Result<Vec<IpNet>, RateLimitConfigError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| RateLimitConfigError::MalformedProxy(entry.to_owned()))
+        })
+        .collect()
+}
+
+#[derive(Builder)]
+pub struct RateLimitMiddleware {
+    pub cache_pool: Arc<bb8::Pool<RedisConnectionManager>>,
+    #[builder(default = default_rate_limits())]
+    pub limits: HashMap<RateLimitedRoute, RateLimit>,
+    /// Reverse proxies whose `X-Forwarded-For` is trusted, none by default.
+    #[builder(default)]
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl RateLimitMiddleware {
+    /// Counts the request of the client (an IP or a nickname) to the route.
+    /// Routes without a configured limit are not limited.
+    pub async fn check(&self, route: RateLimitedRoute, client: &str) -> Result<()> {
+        let Some(limit) = self.limits.get(&route) else {
+            return Ok(());
+        };
+        let now = Utc::now().timestamp_millis();
+        let member = format!("{now}-{}", rand::rng().random::<u32>());
+
+        let mut conn = self.cache_pool.get().await?;
+        let retry_after_millis = SLIDING_WINDOW
+            .key(Self::get_window_key(route, client))
+            .arg(now)
+            .arg(limit.window_secs * 1000)
+            .arg(limit.max_requests)
+            .arg(member)
+            .invoke_async::<u64>(&mut *conn)
+            .await?;
+
+        if retry_after_millis > 0 {
+            return Err(AppError::RateLimited(retry_after_millis.div_ceil(1000)));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects the login while the nickname is locked out after too many wrong passwords.
+    pub async fn check_login_lockout(&self, nickname: &str) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        let ttl = conn
+            .ttl::<&str, i64>(Self::get_lockout_key(nickname).as_str())
+            .await?;
+
+        if ttl > 0 {
+            return Err(AppError::RateLimited(ttl as u64));
+        }
+
+        Ok(())
+    }
+
+    /// Counts a wrong password and locks the nickname out once there were too many of them.
+    pub async fn register_login_failure(&self, nickname: &str) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        let failures_key = Self::get_login_failures_key(nickname);
+        let failures = conn
+            .incr::<&str, u32, u32>(failures_key.as_str(), 1)
+            .await?;
+        conn.expire::<&str, ()>(failures_key.as_str(), LOGIN_FAILURES_TTL_SECS)
+            .await?;
+
+        if let Some(lockout) = lockout_secs(failures) {
+            conn.set_ex::<&str, u32, ()>(
+                Self::get_lockout_key(nickname).as_str(),
+                failures,
+                lockout,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn reset_login_failures(&self, nickname: &str) -> Result<()> {
+        let mut conn = self.cache_pool.get().await?;
+        conn.del::<&[String], ()>(&[
+            Self::get_login_failures_key(nickname),
+            Self::get_lockout_key(nickname),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn get_window_key(route: RateLimitedRoute, client: &str) -> String {
+        format!(
+            "{}_{}_{client}",
+            CacheKey::RateLimit.as_ref(),
+            route.as_ref()
+        )
+    }
+
+    /// Nicknames are case-insensitive, so are the failures and lockouts keyed on them.
+    pub(crate) fn get_login_failures_key(nickname: &str) -> String {
+        format!(
+            "{}_{}",
+            CacheKey::LoginFailures.as_ref(),
+            nickname.to_lowercase()
+        )
+    }
+
+    pub(crate) fn get_lockout_key(nickname: &str) -> String {
+        format!(
+            "{}_{}",
+            CacheKey::LoginLockout.as_ref(),
+            nickname.to_lowercase()
+        )
+    }
+}
+
+/// Lockout after the given number of consecutive wrong passwords, if any.
+pub fn lockout_secs(failures: u32) -> Option<u64> {
+    let over = failures.checked_sub(LOGIN_FAILURES_BEFORE_LOCKOUT)?;
+
+    Some(
+        LOGIN_BASE_LOCKOUT_SECS
+            .saturating_mul(1 << over.min(16))
+            .min(LOGIN_MAX_LOCKOUT_SECS),
+    )
+}
+
+/// IP address of the client. `X-Forwarded-For` counts only when the request comes from a
+/// trusted proxy, otherwise anyone could dodge the limits by sending a new address every time.
+#[derive(Debug, Clone)]
+pub struct ClientIp(pub String);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        let forwarded_for = parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok());
+        let ip = client_ip(
+            peer,
+            forwarded_for,
+            &state.rate_limit_middleware.trusted_proxies,
+        );
+
+        Ok(ClientIp(
+            ip.map_or_else(|| "unknown".to_owned(), |ip| ip.to_string()),
+        ))
+    }
+}
+
+/// Walks `X-Forwarded-For` back from the peer while the hops are trusted proxies. The first
+/// hop which isn't a trusted proxy is the client, whatever it put into the header itself.
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let mut ip = peer?;
+    for hop in forwarded_for
+        .into_iter()
+        .flat_map(|value| value.rsplit(','))
+    {
+        if !trusted_proxies.iter().any(|proxy| proxy.contains(&ip)) {
+            break;
+        }
+        match hop.trim().parse::<IpAddr>() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+
+    Some(ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::middleware::rate_limit_middleware::{
+        client_ip, lockout_secs, parse_rate_limit, parse_trusted_proxies, RateLimit,
+        LOGIN_BASE_LOCKOUT_SECS, LOGIN_FAILURES_BEFORE_LOCKOUT, LOGIN_MAX_LOCKOUT_SECS,
+    };
+    use std::net::IpAddr;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn when_failures_exceed_allowed_then_lockout_starts_and_doubles() {
+        assert_eq!(lockout_secs(LOGIN_FAILURES_BEFORE_LOCKOUT - 1), None);
+        assert_eq!(
+            lockout_secs(LOGIN_FAILURES_BEFORE_LOCKOUT),
+            Some(LOGIN_BASE_LOCKOUT_SECS)
+        );
+        assert_eq!(
+            lockout_secs(LOGIN_FAILURES_BEFORE_LOCKOUT + 2),
+            Some(LOGIN_BASE_LOCKOUT_SECS * 4)
+        );
+        assert_eq!(lockout_secs(u32::MAX), Some(LOGIN_MAX_LOCKOUT_SECS));
+    }
+
+    #[test]
+    fn when_request_from_trusted_proxy_then_client_ip_is_the_one_seen_by_the_proxy() {
+        let proxies = parse_trusted_proxies("10.0.0.0/8").unwrap();
+
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.2")), Some("6.6.6.6, 1.1.1.1"), &proxies),
+            Some(ip("1.1.1.1"))
+        );
+    }
+
+    #[test]
+    fn when_request_not_from_trusted_proxy_then_forwarded_for_ignored() {
+        let proxies = parse_trusted_proxies("10.0.0.2").unwrap();
+
+        assert_eq!(
+            client_ip(Some(ip("1.1.1.1")), Some("6.6.6.6"), &proxies),
+            Some(ip("1.1.1.1"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("1.1.1.1")), Some("6.6.6.6"), &[]),
+            Some(ip("1.1.1.1"))
+        );
+    }
+
+    #[test]
+    fn when_chain_of_trusted_proxies_then_first_untrusted_hop_is_the_client() {
+        let proxies = parse_trusted_proxies("10.0.0.0/8, 192.168.1.1").unwrap();
+
+        assert_eq!(
+            client_ip(
+                Some(ip("10.0.0.2")),
+                Some("6.6.6.6, 1.1.1.1, 192.168.1.1"),
+                &proxies
+            ),
+            Some(ip("1.1.1.1"))
+        );
+    }
+
+    #[test]
+    fn when_rate_limit_configured_then_parsed() {
+        assert_eq!(
+            parse_rate_limit("10/60"),
+            Some(RateLimit {
+                max_requests: 10,
+                window_secs: 60,
+            })
+        );
+        assert_eq!(parse_rate_limit("10"), None);
+        assert_eq!(parse_rate_limit("ten/60"), None);
+    }
+
+    #[test]
+    fn when_trusted_proxy_malformed_then_rejected() {
+        assert!(parse_trusted_proxies("10.0.0.0/8, proxy").is_err());
+        assert_eq!(parse_trusted_proxies("").unwrap(), vec![]);
+    }
+}
@@ -1,7 +1,11 @@
 use crate::app::redis::{CacheKey, RedisConnectionManager};
 use crate::error::AppError;
-use crate::model::r#static::{PlayerClass, PlayerRankTable};
+use crate::model::r#static::{
+    MapLocation, PlayerClass, PlayerExperienceTable, PlayerRankTable, RankRequirement,
+};
+use crate::schema::map_location::dsl::map_location;
 use crate::schema::player_class::dsl::player_class;
+use crate::schema::player_experience_table::dsl::player_experience_table;
 use crate::schema::player_rank_table::dsl::player_rank_table;
 use bon::Builder;
 use diesel::RunQueryDsl;
@@ -16,6 +20,14 @@ pub struct StaticTablesCacheMiddleware {
 }
 
 impl StaticTablesCacheMiddleware {
+    pub async fn prefetch_all(&self) -> crate::error::Result<()> {
+        self.prefetch_rank_table().await?;
+        self.prefetch_class_table().await?;
+        self.prefetch_experience_table().await?;
+        self.prefetch_map_locations().await?;
+        Ok(())
+    }
+
     pub async fn prefetch_rank_table(&self) -> crate::error::Result<()> {
         let conn = self.db_pool.clone().get().await?;
 
@@ -27,6 +39,19 @@ impl StaticTablesCacheMiddleware {
         let mut cache_conn = self.cache_pool.get().await?;
 
         for rank_table in ranks {
+            let requirement = serde_json::to_string(&RankRequirement::from(&rank_table))
+                .map_err(|e| AppError::QueryError(e.to_string()))?;
+            if let Err(e) = cache_conn
+                .hset::<&str, i32, String, ()>(
+                    CacheKey::RankRequirements.as_ref(),
+                    rank_table.id,
+                    requirement,
+                )
+                .await
+            {
+                error!("Failed to cache player rank requirements: {}", e);
+                return Err(AppError::CacheError(e));
+            }
             if let Err(e) = cache_conn
                 .hset::<&str, i32, String, ()>(
                     CacheKey::RankTable.as_ref(),
@@ -55,6 +80,19 @@ impl StaticTablesCacheMiddleware {
         let mut cache_conn = self.cache_pool.get().await?;
 
         for class in classes {
+            let details =
+                serde_json::to_string(&class).map_err(|e| AppError::QueryError(e.to_string()))?;
+            if let Err(e) = cache_conn
+                .hset::<&str, i32, String, ()>(
+                    CacheKey::ClassDetails.as_ref(),
+                    class.class_id,
+                    details,
+                )
+                .await
+            {
+                error!("Failed to cache player class details: {}", e);
+                return Err(AppError::CacheError(e));
+            }
             if let Err(e) = cache_conn
                 .hset::<&str, i32, String, ()>(
                     CacheKey::ClassTable.as_ref(),
@@ -72,6 +110,100 @@ impl StaticTablesCacheMiddleware {
         Ok(())
     }
 
+    pub async fn prefetch_experience_table(&self) -> crate::error::Result<()> {
+        let conn = self.db_pool.clone().get().await?;
+
+        let thresholds = conn
+            .interact(|conn| player_experience_table.load::<PlayerExperienceTable>(conn))
+            .await?
+            .map_err(|e| AppError::QueryError(e.to_string()))?;
+
+        let mut cache_conn = self.cache_pool.get().await?;
+
+        for threshold in thresholds {
+            let encoded = serde_json::to_string(&threshold)
+                .map_err(|e| AppError::QueryError(e.to_string()))?;
+            if let Err(e) = cache_conn
+                .hset::<&str, i32, String, ()>(
+                    CacheKey::ExperienceTable.as_ref(),
+                    threshold.exp,
+                    encoded,
+                )
+                .await
+            {
+                error!("Failed to cache player experience table data: {}", e);
+                return Err(AppError::CacheError(e));
+            }
+        }
+        tracing::info!("Player experience table prefetched into the cache successfully.");
+
+        Ok(())
+    }
+
+    pub async fn prefetch_map_locations(&self) -> crate::error::Result<()> {
+        let conn = self.db_pool.clone().get().await?;
+
+        let locations = conn
+            .interact(|conn| map_location.load::<MapLocation>(conn))
+            .await?
+            .map_err(|e| AppError::QueryError(e.to_string()))?;
+
+        let mut cache_conn = self.cache_pool.get().await?;
+
+        for location in locations {
+            let encoded = serde_json::to_string(&location)
+                .map_err(|e| AppError::QueryError(e.to_string()))?;
+            if let Err(e) = cache_conn
+                .hset::<&str, i32, String, ()>(
+                    CacheKey::MapLocations.as_ref(),
+                    location.id,
+                    encoded,
+                )
+                .await
+            {
+                error!("Failed to cache map locations: {}", e);
+                return Err(AppError::CacheError(e));
+            }
+        }
+        tracing::info!("Map locations prefetched into the cache successfully.");
+
+        Ok(())
+    }
+
+    /// Returns the cached experience table sorted by the experience thresholds.
+    pub async fn get_experience_table(&self) -> crate::error::Result<Vec<PlayerExperienceTable>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        let encoded = conn
+            .hvals::<&str, Vec<String>>(CacheKey::ExperienceTable.as_ref())
+            .await?;
+        let mut thresholds = encoded
+            .iter()
+            .map(|threshold| serde_json::from_str::<PlayerExperienceTable>(threshold))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::QueryError(e.to_string()))?;
+        thresholds.sort_by_key(|threshold| threshold.exp);
+
+        Ok(thresholds)
+    }
+
+    /// Returns the cached rank requirements sorted by the rank id.
+    pub async fn get_rank_requirements(&self) -> crate::error::Result<Vec<RankRequirement>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        let encoded = conn
+            .hvals::<&str, Vec<String>>(CacheKey::RankRequirements.as_ref())
+            .await?;
+        let mut requirements = encoded
+            .iter()
+            .map(|requirement| serde_json::from_str::<RankRequirement>(requirement))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::QueryError(e.to_string()))?;
+        requirements.sort_by_key(|requirement| requirement.id);
+
+        Ok(requirements)
+    }
+
     pub async fn get_rank_name_by_id(&self, rank_id: i32) -> crate::error::Result<String> {
         let mut conn = self.cache_pool.get().await.unwrap();
 
@@ -87,4 +219,55 @@ impl StaticTablesCacheMiddleware {
             .hget::<&str, i32, String>(CacheKey::ClassTable.as_ref(), class_id)
             .await?)
     }
+
+    /// Returns the class with its spec names, `None` for an unknown class id.
+    pub async fn get_class_by_id(
+        &self,
+        class_id: i32,
+    ) -> crate::error::Result<Option<PlayerClass>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        let encoded = conn
+            .hget::<&str, i32, Option<String>>(CacheKey::ClassDetails.as_ref(), class_id)
+            .await?;
+
+        encoded
+            .map(|class| serde_json::from_str::<PlayerClass>(&class))
+            .transpose()
+            .map_err(|e| AppError::QueryError(e.to_string()))
+    }
+
+    /// Returns the cached map locations sorted by id.
+    pub async fn get_map_locations(&self) -> crate::error::Result<Vec<MapLocation>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        let encoded = conn
+            .hvals::<&str, Vec<String>>(CacheKey::MapLocations.as_ref())
+            .await?;
+        let mut locations = encoded
+            .iter()
+            .map(|location| serde_json::from_str::<MapLocation>(location))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::QueryError(e.to_string()))?;
+        locations.sort_by_key(|location| location.id);
+
+        Ok(locations)
+    }
+
+    /// Returns the location, `None` for an unknown location id.
+    pub async fn get_map_location(
+        &self,
+        location_id: i32,
+    ) -> crate::error::Result<Option<MapLocation>> {
+        let mut conn = self.cache_pool.get().await?;
+
+        let encoded = conn
+            .hget::<&str, i32, Option<String>>(CacheKey::MapLocations.as_ref(), location_id)
+            .await?;
+
+        encoded
+            .map(|location| serde_json::from_str::<MapLocation>(&location))
+            .transpose()
+            .map_err(|e| AppError::QueryError(e.to_string()))
+    }
 }
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS battle_participant_battle;
+DROP TABLE IF EXISTS battle_participant;
+
+CREATE TABLE IF NOT EXISTS battle_participant
+(
+    battle_id      INTEGER PRIMARY KEY            NOT NULL REFERENCES battle (id),
+    player_id      INTEGER REFERENCES player (id) NOT NULL,
+    bot_id         INTEGER REFERENCES bot (id)    NOT NULL,
+    outcome_damage INTEGER                        NOT NULL DEFAULT 0,
+    income_damage  INTEGER                        NOT NULL DEFAULT 0,
+    gained_exp     INTEGER                        NOT NULL DEFAULT 0,
+    gained_valor   BOOLEAN                        NOT NULL
+);
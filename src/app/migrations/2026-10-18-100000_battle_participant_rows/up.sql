@@ -0,0 +1,20 @@
+-- A battle has up to 32 participants, so `battle_id` can't be the primary key of this table.
+-- Every participant is either a player or a bot, hence both references are nullable.
+-- Battle records are written only after the battle is finished, so the table is safe to recreate.
+
+DROP TABLE IF EXISTS battle_participant;
+
+CREATE TABLE IF NOT EXISTS battle_participant
+(
+    id             INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    battle_id      INTEGER                           NOT NULL REFERENCES battle (id),
+    player_id      INTEGER REFERENCES player (id),
+    bot_id         INTEGER REFERENCES bot (id),
+    faction        INTEGER                           NOT NULL REFERENCES factions (id),
+    outcome_damage INTEGER                           NOT NULL DEFAULT 0,
+    income_damage  INTEGER                           NOT NULL DEFAULT 0,
+    gained_exp     INTEGER                           NOT NULL DEFAULT 0,
+    gained_valor   BOOLEAN                           NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS battle_participant_battle ON battle_participant (battle_id);
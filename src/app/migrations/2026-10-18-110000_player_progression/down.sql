@@ -0,0 +1,3 @@
+ALTER TABLE player_attributes DROP COLUMN money;
+ALTER TABLE player_attributes DROP COLUMN attribute_points;
+ALTER TABLE player_attributes DROP COLUMN up;
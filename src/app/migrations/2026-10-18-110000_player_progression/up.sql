@@ -0,0 +1,4 @@
+-- 'up' within the current level, unspent attribute points and money granted by progression.
+ALTER TABLE player_attributes ADD COLUMN up INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE player_attributes ADD COLUMN attribute_points INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE player_attributes ADD COLUMN money INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS guild_invite;
+DROP INDEX IF EXISTS guild_member_guild;
+DROP TABLE IF EXISTS guild_member;
@@ -0,0 +1,21 @@
+-- Guild membership: the role of every member and pending invites.
+-- `player.guild_id` keeps pointing to the guild of a member.
+CREATE TABLE IF NOT EXISTS guild_member
+(
+    player_id INTEGER PRIMARY KEY NOT NULL REFERENCES player (id),
+    guild_id  INTEGER             NOT NULL REFERENCES guild (id),
+    role      INTEGER             NOT NULL DEFAULT 0, -- 0 member, 1 officer, 2 leader
+    joined_at TIMESTAMP           NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS guild_member_guild ON guild_member (guild_id);
+
+CREATE TABLE IF NOT EXISTS guild_invite
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id   INTEGER                           NOT NULL REFERENCES guild (id),
+    player_id  INTEGER                           NOT NULL REFERENCES player (id),
+    invited_by INTEGER                           NOT NULL REFERENCES player (id),
+    created_at TIMESTAMP                         NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (guild_id, player_id)
+);
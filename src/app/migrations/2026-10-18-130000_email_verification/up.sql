@@ -0,0 +1,2 @@
+-- Whether the player confirmed the registration email, faking boolean as `banned` does.
+ALTER TABLE player ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS moderation_audit_target;
+DROP TABLE IF EXISTS moderation_audit;
+
+ALTER TABLE player DROP COLUMN muted_until;
+ALTER TABLE player DROP COLUMN banned_until;
+ALTER TABLE player DROP COLUMN ban_reason;
+ALTER TABLE player DROP COLUMN role;
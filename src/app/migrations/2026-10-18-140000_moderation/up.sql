@@ -0,0 +1,19 @@
+-- Moderation: staff roles, bans with a reason and an expiry, chat mutes
+-- and the audit of every moderation action.
+ALTER TABLE player ADD COLUMN role INTEGER NOT NULL DEFAULT 0; -- 0 player, 1 admin
+ALTER TABLE player ADD COLUMN ban_reason TEXT;
+ALTER TABLE player ADD COLUMN banned_until TIMESTAMP; -- NULL while banned means forever
+ALTER TABLE player ADD COLUMN muted_until TIMESTAMP;
+
+CREATE TABLE IF NOT EXISTS moderation_audit
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    admin_id   INTEGER                           NOT NULL REFERENCES player (id),
+    target_id  INTEGER                           NOT NULL REFERENCES player (id),
+    action     TEXT                              NOT NULL,
+    reason     TEXT,
+    details    TEXT,
+    created_at TIMESTAMP                         NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS moderation_audit_target ON moderation_audit (target_id);
@@ -0,0 +1 @@
+ALTER TABLE player DROP COLUMN last_map_location;
@@ -0,0 +1,2 @@
+-- Location the player is at, or travels from. NULL is the starting location.
+ALTER TABLE player ADD COLUMN last_map_location INTEGER REFERENCES map_location (id);
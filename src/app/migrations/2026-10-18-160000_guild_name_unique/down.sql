@@ -0,0 +1 @@
+DROP INDEX IF EXISTS guild_name_lower;
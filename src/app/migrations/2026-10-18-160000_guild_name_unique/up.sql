@@ -0,0 +1,2 @@
+-- Guild names differing in case only are the same name.
+CREATE UNIQUE INDEX IF NOT EXISTS guild_name_lower ON guild (lower(name));
@@ -1,9 +1,20 @@
 pub mod app_state;
+pub mod battle;
+pub mod combat;
+pub mod encounter;
 pub mod error;
-mod grid;
+pub mod grid;
+pub mod key_ring;
+pub mod mailer;
 pub mod middleware;
 pub mod model;
+pub mod progression;
 pub mod protos;
 pub mod redis;
+pub mod rewards;
 pub mod routes;
 pub mod schema;
+pub mod session;
+pub mod stats;
+pub mod travel;
+pub mod validation;
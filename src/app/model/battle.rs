@@ -0,0 +1,40 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::bot)]
+pub struct Bot {
+    pub id: i32,
+    pub weapon_id: i32,
+    pub name: String,
+    pub level: i32,
+    pub action_points: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::battle)]
+pub struct NewBattle {
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub winner: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::battle_participant)]
+pub struct NewBattleParticipant {
+    pub battle_id: i32,
+    pub player_id: Option<i32>,
+    pub bot_id: Option<i32>,
+    pub faction: i32,
+    pub outcome_damage: i32,
+    pub income_damage: i32,
+    pub gained_exp: i32,
+    pub gained_valor: bool,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::battle_log)]
+pub struct BattleLog {
+    pub id: i32,
+    pub log: String,
+}
@@ -0,0 +1,68 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::guild)]
+pub struct Guild {
+    pub id: i32,
+    pub name: String,
+    pub rank: i32,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::guild)]
+pub struct NewGuild {
+    pub name: String,
+    pub rank: i32,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::guild_member)]
+pub struct GuildMember {
+    pub player_id: i32,
+    pub guild_id: i32,
+    pub role: i32,
+    pub joined_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::guild_invite)]
+pub struct GuildInvite {
+    pub id: i32,
+    pub guild_id: i32,
+    pub player_id: i32,
+    pub invited_by: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::guild_invite)]
+pub struct NewGuildInvite {
+    pub guild_id: i32,
+    pub player_id: i32,
+    pub invited_by: i32,
+}
+
+/// Role of a member within the guild, stored in `guild_member.role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuildRole {
+    Member = 0,
+    Officer = 1,
+    Leader = 2,
+}
+
+impl GuildRole {
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            2 => GuildRole::Leader,
+            1 => GuildRole::Officer,
+            _ => GuildRole::Member,
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::item)]
+pub struct Item {
+    pub id: i32,
+    pub name: String,
+    pub level_req: i32,
+    pub strength_req: i32,
+    pub dexterity_req: i32,
+    pub physique_req: i32,
+    pub intellect_req: i32,
+    pub valor_req: i32,
+    pub class_req: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::weapon_item)]
+pub struct WeaponItem {
+    pub id: i32,
+    pub item_id: i32,
+    pub action_points_to_use: i32,
+    pub basic_damage: i32,
+    pub range: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::gear_item)]
+pub struct GearItem {
+    pub id: i32,
+    pub item_id: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::player_inventory)]
+pub struct PlayerInventory {
+    pub id: i32,
+    pub player_id: i32,
+    pub item_id: i32,
+    pub amount: i32,
+    pub weight: f32,
+    pub equipped: bool,
+}
+
+/// A single inventory slot with the item definition and its weapon/gear specifics, if any.
+#[derive(Debug, Clone)]
+pub struct InventoryEntry {
+    pub slot: PlayerInventory,
+    pub item: Item,
+    pub weapon: Option<WeaponItem>,
+    pub gear: Option<GearItem>,
+}
@@ -1,4 +1,8 @@
+pub mod battle;
 pub mod cache;
+pub mod guild;
+pub mod item;
+pub mod moderation;
 pub mod player;
 pub mod r#static;
 
@@ -0,0 +1,37 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+use strum::AsRefStr;
+
+/// Kind of a moderation action, stored in `moderation_audit.action`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ModerationAction {
+    Ban,
+    Unban,
+    Mute,
+    Unmute,
+    ForceLogout,
+    Rename,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::moderation_audit)]
+pub struct ModerationAuditEntry {
+    pub id: i32,
+    pub admin_id: i32,
+    pub target_id: i32,
+    pub action: String,
+    pub reason: Option<String>,
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::moderation_audit)]
+pub struct NewModerationAuditEntry {
+    pub admin_id: i32,
+    pub target_id: i32,
+    pub action: String,
+    pub reason: Option<String>,
+    pub details: Option<String>,
+}
@@ -1,6 +1,7 @@
 use crate::model::DefaultModel;
 use chrono::naive::NaiveDateTime;
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
 #[derive(Selectable, Queryable, Insertable, Clone, Default)]
@@ -17,6 +18,47 @@ pub struct Player {
     pub last_login_time: Option<NaiveDateTime>,
     pub guild_id: Option<i32>,
     pub banned: i32,
+    pub email_verified: i32,
+    pub role: i32,
+    pub ban_reason: Option<String>,
+    /// `None` while banned means the ban is permanent.
+    pub banned_until: Option<NaiveDateTime>,
+    pub muted_until: Option<NaiveDateTime>,
+    /// `None` is the starting location.
+    pub last_map_location: Option<i32>,
+}
+
+impl Player {
+    /// Bans expire on their own, an expired one is not lifted in the table.
+    pub fn is_banned(&self, now: NaiveDateTime) -> bool {
+        self.banned != 0 && self.banned_until.is_none_or(|until| until > now)
+    }
+
+    pub fn is_muted(&self, now: NaiveDateTime) -> bool {
+        self.muted_until.is_some_and(|until| until > now)
+    }
+}
+
+/// Role of the player in the game administration, stored in `player.role`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerRole {
+    #[default]
+    Player = 0,
+    Admin = 1,
+}
+
+impl PlayerRole {
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            1 => PlayerRole::Admin,
+            _ => PlayerRole::Player,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +81,9 @@ pub struct PlayerAttributes {
     pub experience: i32,
     pub level: i32,
     pub valor: i32,
+    pub up: i32,
+    pub attribute_points: i32,
+    pub money: i32,
 }
 
 #[derive(Queryable, Insertable, Default, Debug, Clone)]
@@ -53,6 +98,13 @@ pub struct PlayerClassProgress {
     pub total_progress: Option<f32>,
 }
 
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::player_class_progress)]
+pub struct NewPlayerClassProgress {
+    pub player_id: i32,
+    pub class_id: i32,
+}
+
 impl Debug for Player {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Player")
@@ -63,6 +115,13 @@ impl Debug for Player {
             .field("registration_time", &self.registration_time)
             .field("last_login_time", &self.last_login_time)
             .field("guild_id", &self.guild_id)
+            .field("banned", &self.banned)
+            .field("email_verified", &self.email_verified)
+            .field("role", &self.role)
+            .field("ban_reason", &self.ban_reason)
+            .field("banned_until", &self.banned_until)
+            .field("muted_until", &self.muted_until)
+            .field("last_map_location", &self.last_map_location)
             .finish()
     }
 }
@@ -81,6 +140,9 @@ impl DefaultModel for PlayerAttributes {
             experience: 0,
             level: 1,
             valor: 0,
+            up: 0,
+            attribute_points: 0,
+            money: 0,
         }
     }
 }
@@ -1,8 +1,9 @@
 // The definition of tables with a static content
 
 use diesel::Queryable;
+use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Debug, Clone)]
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::player_class)]
 pub struct PlayerClass {
     pub class_id: i32,
@@ -12,6 +13,18 @@ pub struct PlayerClass {
     pub class_spec_tree_name: Option<String>,
 }
 
+impl PlayerClass {
+    /// Name of the 1-based spec of the class.
+    pub fn spec_name(&self, spec: u8) -> Option<&str> {
+        match spec {
+            1 => self.class_spec_one_name.as_deref(),
+            2 => self.class_spec_two_name.as_deref(),
+            3 => self.class_spec_tree_name.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Queryable, Debug, Clone)]
 #[diesel(table_name = crate::schema::player_rank_table)]
@@ -26,7 +39,7 @@ pub struct PlayerRankTable {
     pub rank_pic_url_FR: Option<String>,
 }
 
-#[derive(Queryable, Debug, Clone)]
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[diesel(table_name = crate::schema::player_experience_table)]
 pub struct PlayerExperienceTable {
     pub exp: i32,
@@ -35,3 +48,34 @@ pub struct PlayerExperienceTable {
     pub attrs: i32,
     pub money: i32,
 }
+
+/// A location of the world map, every location is a chat and presence zone as well.
+/// `location` is the position of the location along the map.
+#[derive(Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::map_location)]
+pub struct MapLocation {
+    pub id: i32,
+    pub name: String,
+    pub location: i32,
+    pub location_difficulty: i32,
+    pub movement_accel: f32,
+    pub aggression_prob: f32,
+}
+
+/// Promotion requirements of a rank, cached next to the rank names.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RankRequirement {
+    pub id: i32,
+    pub valor: i32,
+    pub min_level: i32,
+}
+
+impl From<&PlayerRankTable> for RankRequirement {
+    fn from(rank: &PlayerRankTable) -> Self {
+        RankRequirement {
+            id: rank.id,
+            valor: rank.valor,
+            min_level: rank.min_level,
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use crate::model::player::{PlayerAttributes, PlayerClassProgress};
+use crate::model::r#static::{PlayerExperienceTable, RankRequirement};
+use crate::model::DefaultModel;
+use serde::{Deserialize, Serialize};
+
+/// Every attribute is capped by `BASE_ATTRIBUTE_CAP + ATTRIBUTE_CAP_PER_LEVEL * level`.
+pub const BASE_ATTRIBUTE_CAP: i32 = 10;
+pub const ATTRIBUTE_CAP_PER_LEVEL: i32 = 5;
+/// Respec costs `RESPEC_COST_PER_LEVEL * level` money.
+pub const RESPEC_COST_PER_LEVEL: i32 = 100;
+/// 'no-class' of the `player_class` table, every player starts with it.
+pub const NO_CLASS_ID: i32 = 0;
+pub const CLASS_SELECTION_MIN_LEVEL: i32 = 2;
+pub const MAX_SPEC_PROGRESS: f32 = 100.0;
+/// Spec progress bought for one attribute point.
+pub const SPEC_PROGRESS_PER_POINT: f32 = 5.0;
+
+/// Result of awarding experience, reported back to the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Progression {
+    pub experience: i32,
+    pub level: i32,
+    pub up: i32,
+    pub thresholds_crossed: i32,
+    pub attribute_points_granted: i32,
+    pub money_granted: i32,
+    pub level_changed: bool,
+}
+
+/// Walks the experience table from the current experience up to `experience + gained`.
+/// Every crossed threshold grants its `attrs` and `money`, and the last crossed one defines
+/// the resulting level and "up". `experience_table` has to be sorted by `exp`.
+pub fn progress(
+    experience_table: &[PlayerExperienceTable],
+    attributes: &PlayerAttributes,
+    gained: i32,
+) -> Progression {
+    let from = attributes.experience;
+    let to = from.saturating_add(gained.max(0));
+
+    let mut progression = Progression {
+        experience: to,
+        level: attributes.level,
+        up: attributes.up,
+        thresholds_crossed: 0,
+        attribute_points_granted: 0,
+        money_granted: 0,
+        level_changed: false,
+    };
+
+    experience_table
+        .iter()
+        .filter(|threshold| from < threshold.exp && threshold.exp <= to)
+        .for_each(|threshold| {
+            progression.thresholds_crossed += 1;
+            progression.attribute_points_granted += threshold.attrs;
+            progression.money_granted += threshold.money;
+            progression.level = threshold.level;
+            progression.up = threshold.up;
+        });
+    progression.level_changed = progression.level != attributes.level;
+
+    progression
+}
+
+impl Progression {
+    pub fn apply(&self, attributes: &mut PlayerAttributes) {
+        attributes.experience = self.experience;
+        attributes.level = self.level;
+        attributes.up = self.up;
+        attributes.attribute_points += self.attribute_points_granted;
+        attributes.money += self.money_granted;
+    }
+}
+
+/// Returns the highest rank the player qualifies for by valor and level, if it is higher than
+/// the current one. Ranks are never taken away.
+pub fn rank_promotion(
+    rank_requirements: &[RankRequirement],
+    attributes: &PlayerAttributes,
+) -> Option<i32> {
+    rank_requirements
+        .iter()
+        .filter(|rank| attributes.valor >= rank.valor && attributes.level >= rank.min_level)
+        .map(|rank| rank.id)
+        .max()
+        .filter(|rank_id| *rank_id > attributes.rank_id)
+}
+
+/// Attribute points to spend, per attribute.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AttributeAllocation {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub physique: i32,
+    pub luck: i32,
+    pub intellect: i32,
+}
+
+pub fn attribute_cap(level: i32) -> i32 {
+    BASE_ATTRIBUTE_CAP + ATTRIBUTE_CAP_PER_LEVEL * level
+}
+
+pub fn respec_cost(level: i32) -> i32 {
+    RESPEC_COST_PER_LEVEL * level
+}
+
+/// Spends unallocated attribute points. Either the whole allocation is applied or nothing is,
+/// the error describes the first violated rule.
+pub fn allocate_attributes(
+    attributes: &mut PlayerAttributes,
+    allocation: &AttributeAllocation,
+) -> Result<(), String> {
+    let cap = attribute_cap(attributes.level);
+    let spends = [
+        ("strength", allocation.strength, &mut attributes.strength),
+        ("dexterity", allocation.dexterity, &mut attributes.dexterity),
+        ("physique", allocation.physique, &mut attributes.physique),
+        ("luck", allocation.luck, &mut attributes.luck),
+        ("intellect", allocation.intellect, &mut attributes.intellect),
+    ];
+
+    let mut total = 0;
+    for (name, points, current) in &spends {
+        if *points < 0 {
+            return Err(format!("{name} points can't be negative"));
+        }
+        if **current + *points > cap {
+            return Err(format!("{name} can't exceed {cap} on this level"));
+        }
+        total += *points;
+    }
+    if total == 0 {
+        return Err("no points to allocate".to_owned());
+    }
+    if total > attributes.attribute_points {
+        return Err(format!(
+            "{total} points requested, {} available",
+            attributes.attribute_points
+        ));
+    }
+
+    for (_, points, current) in spends {
+        *current += points;
+    }
+    attributes.attribute_points -= total;
+
+    Ok(())
+}
+
+/// Resets attributes to their defaults, refunding all the spent points for money.
+/// Returns the number of refunded points.
+pub fn respec_attributes(attributes: &mut PlayerAttributes) -> Result<i32, String> {
+    let cost = respec_cost(attributes.level);
+    if attributes.money < cost {
+        return Err(format!(
+            "respec costs {cost}, {} available",
+            attributes.money
+        ));
+    }
+
+    let defaults = PlayerAttributes::default_model(attributes.player_id);
+    let refunded = (attributes.strength - defaults.strength)
+        + (attributes.dexterity - defaults.dexterity)
+        + (attributes.physique - defaults.physique)
+        + (attributes.luck - defaults.luck)
+        + (attributes.intellect - defaults.intellect);
+
+    attributes.strength = defaults.strength;
+    attributes.dexterity = defaults.dexterity;
+    attributes.physique = defaults.physique;
+    attributes.luck = defaults.luck;
+    attributes.intellect = defaults.intellect;
+    attributes.attribute_points += refunded;
+    attributes.money -= cost;
+
+    Ok(refunded)
+}
+
+/// Checks whether the player can pick the class, which happens only once.
+pub fn check_class_selection(attributes: &PlayerAttributes, class_id: i32) -> Result<(), String> {
+    if attributes.class_id != NO_CLASS_ID {
+        return Err("the class is already chosen".to_owned());
+    }
+    if class_id == NO_CLASS_ID {
+        return Err("a real class has to be chosen".to_owned());
+    }
+    if attributes.level < CLASS_SELECTION_MIN_LEVEL {
+        return Err(format!(
+            "a class can be chosen from level {CLASS_SELECTION_MIN_LEVEL}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Spends attribute points on the progress of a spec, `spec` is 1-based.
+/// The progress never exceeds `MAX_SPEC_PROGRESS`, points which would overflow it are kept.
+pub fn add_spec_progress(
+    class_progress: &mut PlayerClassProgress,
+    attributes: &mut PlayerAttributes,
+    spec: u8,
+    points: i32,
+) -> Result<(), String> {
+    if points <= 0 {
+        return Err("no points to spend".to_owned());
+    }
+    if points > attributes.attribute_points {
+        return Err(format!(
+            "{points} points requested, {} available",
+            attributes.attribute_points
+        ));
+    }
+    let current = match spec {
+        1 => class_progress.first_spec_progress,
+        2 => class_progress.second_spec_progress,
+        3 => class_progress.third_spec_progress.unwrap_or(0.0),
+        _ => return Err(format!("unknown spec {spec}")),
+    };
+
+    let left_to_max = ((MAX_SPEC_PROGRESS - current) / SPEC_PROGRESS_PER_POINT).ceil() as i32;
+    if left_to_max <= 0 {
+        return Err(format!("spec {spec} is already mastered"));
+    }
+    let spent = points.min(left_to_max);
+    let progress = (current + spent as f32 * SPEC_PROGRESS_PER_POINT).min(MAX_SPEC_PROGRESS);
+
+    match spec {
+        1 => class_progress.first_spec_progress = progress,
+        2 => class_progress.second_spec_progress = progress,
+        _ => class_progress.third_spec_progress = Some(progress),
+    }
+    attributes.attribute_points -= spent;
+
+    Ok(())
+}
+
+/// 1-based index of the spec with the highest progress, if any progress is made.
+pub fn leading_spec(class_progress: &PlayerClassProgress) -> Option<u8> {
+    [
+        (1, class_progress.first_spec_progress),
+        (2, class_progress.second_spec_progress),
+        (3, class_progress.third_spec_progress.unwrap_or(0.0)),
+    ]
+    .into_iter()
+    .filter(|(_, progress)| *progress > 0.0)
+    .fold(
+        None,
+        |leader: Option<(u8, f32)>, (spec, progress)| match leader {
+            Some((_, best)) if best >= progress => leader,
+            _ => Some((spec, progress)),
+        },
+    )
+    .map(|(spec, _)| spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::progression::{
+        add_spec_progress, allocate_attributes, check_class_selection, leading_spec, progress,
+        rank_promotion, respec_attributes, AttributeAllocation,
+    };
+    use crate::model::player::{PlayerAttributes, PlayerClassProgress};
+    use crate::model::r#static::{PlayerExperienceTable, RankRequirement};
+    use crate::model::DefaultModel;
+
+    fn experience_table() -> Vec<PlayerExperienceTable> {
+        [
+            (0, 0, 0, 3, 50),
+            (25, 0, 1, 3, 100),
+            (100, 1, 1, 1, 100),
+            (250, 0, 2, 4, 200),
+            (400, 1, 2, 1, 200),
+        ]
+        .into_iter()
+        .map(|(exp, up, level, attrs, money)| PlayerExperienceTable {
+            exp,
+            up,
+            level,
+            attrs,
+            money,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn when_no_threshold_crossed_then_only_experience_changes() {
+        let attributes = PlayerAttributes::default_model(1);
+        let progression = progress(&experience_table(), &attributes, 10);
+
+        assert_eq!(progression.experience, 10);
+        assert_eq!(progression.level, 1);
+        assert_eq!(progression.thresholds_crossed, 0);
+        assert!(!progression.level_changed);
+    }
+
+    #[test]
+    fn when_several_thresholds_crossed_then_all_rewards_granted() {
+        let mut attributes = PlayerAttributes {
+            experience: 90,
+            ..PlayerAttributes::default_model(1)
+        };
+        let progression = progress(&experience_table(), &attributes, 160);
+
+        assert_eq!(progression.experience, 250);
+        assert_eq!((progression.level, progression.up), (2, 0));
+        assert_eq!(progression.thresholds_crossed, 2);
+        assert_eq!(progression.attribute_points_granted, 5);
+        assert_eq!(progression.money_granted, 300);
+        assert!(progression.level_changed);
+
+        progression.apply(&mut attributes);
+        assert_eq!(attributes.attribute_points, 5);
+        assert_eq!(attributes.money, 300);
+    }
+
+    #[test]
+    fn when_threshold_already_reached_then_not_granted_twice() {
+        let attributes = PlayerAttributes {
+            experience: 100,
+            up: 1,
+            ..PlayerAttributes::default_model(1)
+        };
+        let progression = progress(&experience_table(), &attributes, 0);
+
+        assert_eq!(progression.thresholds_crossed, 0);
+        assert_eq!(progression.up, 1);
+    }
+
+    fn rank_requirements() -> Vec<RankRequirement> {
+        [(1, 0, 0), (2, 5, 2), (3, 10, 3), (4, 25, 4)]
+            .into_iter()
+            .map(|(id, valor, min_level)| RankRequirement {
+                id,
+                valor,
+                min_level,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn when_valor_and_level_suffice_then_promoted_to_highest_rank() {
+        let attributes = PlayerAttributes {
+            valor: 12,
+            level: 3,
+            ..PlayerAttributes::default_model(1)
+        };
+
+        assert_eq!(rank_promotion(&rank_requirements(), &attributes), Some(3));
+    }
+
+    #[test]
+    fn when_level_is_too_low_then_rank_limited_by_level() {
+        let attributes = PlayerAttributes {
+            valor: 30,
+            level: 2,
+            ..PlayerAttributes::default_model(1)
+        };
+
+        assert_eq!(rank_promotion(&rank_requirements(), &attributes), Some(2));
+    }
+
+    #[test]
+    fn when_rank_already_reached_then_no_promotion() {
+        let attributes = PlayerAttributes {
+            valor: 30,
+            level: 2,
+            rank_id: 4,
+            ..PlayerAttributes::default_model(1)
+        };
+
+        assert_eq!(rank_promotion(&rank_requirements(), &attributes), None);
+    }
+
+    #[test]
+    fn when_points_available_then_allocation_applied() {
+        let mut attributes = PlayerAttributes {
+            attribute_points: 5,
+            ..PlayerAttributes::default_model(1)
+        };
+        let allocation = AttributeAllocation {
+            strength: 2,
+            luck: 3,
+            ..AttributeAllocation::default()
+        };
+
+        assert_eq!(allocate_attributes(&mut attributes, &allocation), Ok(()));
+        assert_eq!((attributes.strength, attributes.luck), (5, 3));
+        assert_eq!(attributes.attribute_points, 0);
+    }
+
+    #[test]
+    fn when_allocation_breaks_a_rule_then_nothing_applied() {
+        let mut attributes = PlayerAttributes {
+            attribute_points: 20,
+            ..PlayerAttributes::default_model(1)
+        };
+        let over_cap = AttributeAllocation {
+            dexterity: 1,
+            strength: 13,
+            ..AttributeAllocation::default()
+        };
+        let not_enough = AttributeAllocation {
+            intellect: 21,
+            ..AttributeAllocation::default()
+        };
+
+        assert!(allocate_attributes(&mut attributes, &over_cap).is_err());
+        assert!(allocate_attributes(&mut attributes, &not_enough).is_err());
+        assert_eq!((attributes.strength, attributes.dexterity), (3, 3));
+        assert_eq!(attributes.attribute_points, 20);
+    }
+
+    #[test]
+    fn when_respec_then_spent_points_refunded_for_money() {
+        let mut attributes = PlayerAttributes {
+            strength: 8,
+            luck: 2,
+            attribute_points: 1,
+            money: 150,
+            ..PlayerAttributes::default_model(1)
+        };
+
+        assert_eq!(respec_attributes(&mut attributes), Ok(7));
+        assert_eq!((attributes.strength, attributes.luck), (3, 0));
+        assert_eq!(attributes.attribute_points, 8);
+        assert_eq!(attributes.money, 50);
+        assert!(respec_attributes(&mut attributes).is_err());
+    }
+
+    #[test]
+    fn when_level_too_low_or_class_chosen_then_class_selection_rejected() {
+        let novice = PlayerAttributes::default_model(1);
+        let veteran = PlayerAttributes {
+            level: 2,
+            ..PlayerAttributes::default_model(1)
+        };
+        let warrior = PlayerAttributes {
+            class_id: 1,
+            ..veteran.clone()
+        };
+
+        assert!(check_class_selection(&novice, 1).is_err());
+        assert!(check_class_selection(&veteran, 0).is_err());
+        assert!(check_class_selection(&warrior, 2).is_err());
+        assert!(check_class_selection(&veteran, 2).is_ok());
+    }
+
+    #[test]
+    fn when_spec_progress_bought_then_capped_and_leading_spec_changes() {
+        let mut attributes = PlayerAttributes {
+            attribute_points: 30,
+            ..PlayerAttributes::default_model(1)
+        };
+        let mut class_progress = PlayerClassProgress {
+            class_id: 1,
+            second_spec_progress: 20.0,
+            ..PlayerClassProgress::default()
+        };
+        assert_eq!(leading_spec(&class_progress), Some(2));
+
+        add_spec_progress(&mut class_progress, &mut attributes, 3, 25).unwrap();
+
+        assert_eq!(class_progress.third_spec_progress, Some(100.0));
+        assert_eq!(attributes.attribute_points, 10);
+        assert_eq!(leading_spec(&class_progress), Some(3));
+        assert!(add_spec_progress(&mut class_progress, &mut attributes, 3, 1).is_err());
+        assert!(add_spec_progress(&mut class_progress, &mut attributes, 4, 1).is_err());
+    }
+}
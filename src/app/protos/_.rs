@@ -15,4 +15,240 @@ pub struct PlayerSession {
     pub is_in_battle: bool,
     #[prost(string, optional, tag = "7")]
     pub link_to_battle: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int32, tag = "8")]
+    pub health: i32,
+    #[prost(int32, tag = "9")]
+    pub stamina: i32,
+    #[prost(int64, tag = "10")]
+    pub vitals_updated_at: i64,
+    #[prost(string, tag = "11")]
+    pub refresh_family: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RefreshToken {
+    #[prost(int64, tag = "1")]
+    pub player_id: i64,
+    #[prost(string, tag = "2")]
+    pub family: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub used: bool,
+}
+/// Travel of a player between map locations, the locations are zones as well.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Travel {
+    #[prost(int64, tag = "1")]
+    pub player_id: i64,
+    #[prost(string, tag = "2")]
+    pub nickname: ::prost::alloc::string::String,
+    #[prost(int32, tag = "3")]
+    pub from_location: i32,
+    #[prost(int32, tag = "4")]
+    pub to_location: i32,
+    #[prost(int64, tag = "5")]
+    pub started_at: i64,
+    #[prost(int64, tag = "6")]
+    pub arrives_at: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ZoneEvent {
+    #[prost(enumeration = "zone_event::Kind", tag = "1")]
+    pub kind: i32,
+    #[prost(int64, tag = "2")]
+    pub zone_id: i64,
+    #[prost(int64, tag = "3")]
+    pub player_id: i64,
+    #[prost(string, tag = "4")]
+    pub nickname: ::prost::alloc::string::String,
+}
+/// Nested message and enum types in `ZoneEvent`.
+pub mod zone_event {
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum Kind {
+        Join = 0,
+        Leave = 1,
+    }
+    impl Kind {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Join => "JOIN",
+                Self::Leave => "LEAVE",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "JOIN" => Some(Self::Join),
+                "LEAVE" => Some(Self::Leave),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChatMessage {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(int64, tag = "2")]
+    pub zone_id: i64,
+    #[prost(string, tag = "3")]
+    pub sender: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub content: ::prost::alloc::string::String,
+    #[prost(int64, tag = "5")]
+    pub sent_at: i64,
+    #[prost(string, optional, tag = "6")]
+    pub whisper_to: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct HexPosition {
+    #[prost(int32, tag = "1")]
+    pub col: i32,
+    #[prost(int32, tag = "2")]
+    pub row: i32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BattleEvent {
+    #[prost(oneof = "battle_event::Event", tags = "1, 2, 3, 4, 5")]
+    pub event: ::core::option::Option<battle_event::Event>,
+}
+/// Nested message and enum types in `BattleEvent`.
+pub mod battle_event {
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct TurnStarted {
+        #[prost(uint32, tag = "1")]
+        pub participant: u32,
+        #[prost(uint32, tag = "2")]
+        pub round: u32,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Moved {
+        #[prost(uint32, tag = "1")]
+        pub participant: u32,
+        #[prost(message, repeated, tag = "2")]
+        pub path: ::prost::alloc::vec::Vec<super::HexPosition>,
+        #[prost(int32, tag = "3")]
+        pub action_points_left: i32,
+    }
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Attacked {
+        #[prost(uint32, tag = "1")]
+        pub attacker: u32,
+        #[prost(uint32, tag = "2")]
+        pub target: u32,
+        #[prost(int32, tag = "3")]
+        pub damage: i32,
+        #[prost(int32, tag = "4")]
+        pub target_health: i32,
+    }
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Died {
+        #[prost(uint32, tag = "1")]
+        pub participant: u32,
+    }
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Finished {
+        #[prost(int32, tag = "1")]
+        pub winner_faction: i32,
+    }
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "1")]
+        TurnStarted(TurnStarted),
+        #[prost(message, tag = "2")]
+        Moved(Moved),
+        #[prost(message, tag = "3")]
+        Attacked(Attacked),
+        #[prost(message, tag = "4")]
+        Died(Died),
+        #[prost(message, tag = "5")]
+        Finished(Finished),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RankPromotion {
+    #[prost(int64, tag = "1")]
+    pub player_id: i64,
+    #[prost(string, tag = "2")]
+    pub nickname: ::prost::alloc::string::String,
+    #[prost(int32, tag = "3")]
+    pub old_rank_id: i32,
+    #[prost(int32, tag = "4")]
+    pub new_rank_id: i32,
+    #[prost(string, tag = "5")]
+    pub rank_name: ::prost::alloc::string::String,
+    #[prost(int64, tag = "6")]
+    pub promoted_at: i64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct SessionEnded {
+    #[prost(int64, tag = "1")]
+    pub player_id: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BattleDelta {
+    #[prost(string, tag = "1")]
+    pub battle_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub events: ::prost::alloc::vec::Vec<BattleEvent>,
+}
+/// Frames pushed from the server to a WebSocket client.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerFrame {
+    #[prost(oneof = "server_frame::Payload", tags = "1, 2, 3, 4, 5, 6")]
+    pub payload: ::core::option::Option<server_frame::Payload>,
+}
+/// Nested message and enum types in `ServerFrame`.
+pub mod server_frame {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "1")]
+        ZoneEvent(super::ZoneEvent),
+        #[prost(message, tag = "2")]
+        ChatMessage(super::ChatMessage),
+        #[prost(message, tag = "3")]
+        BattleDelta(super::BattleDelta),
+        #[prost(message, tag = "4")]
+        RankPromotion(super::RankPromotion),
+        /// The travel of the player has finished.
+        #[prost(message, tag = "5")]
+        TravelFinished(super::Travel),
+        /// The session of the player has ended, the connection is closed right after.
+        #[prost(message, tag = "6")]
+        SessionEnded(super::SessionEnded),
+    }
+}
+/// Frames sent by a WebSocket client to manage its subscriptions.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientFrame {
+    #[prost(oneof = "client_frame::Command", tags = "1, 2, 3, 4")]
+    pub command: ::core::option::Option<client_frame::Command>,
+}
+/// Nested message and enum types in `ClientFrame`.
+pub mod client_frame {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Command {
+        #[prost(int64, tag = "1")]
+        SubscribeZone(i64),
+        #[prost(int64, tag = "2")]
+        UnsubscribeZone(i64),
+        #[prost(string, tag = "3")]
+        SubscribeBattle(::prost::alloc::string::String),
+        #[prost(string, tag = "4")]
+        UnsubscribeBattle(::prost::alloc::string::String),
+    }
 }
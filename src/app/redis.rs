@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use redis::{aio::MultiplexedConnection, ErrorKind};
 use redis::{Client, IntoConnectionInfo, RedisError};
-use serde::{Deserialize, Serialize};
+use strum::AsRefStr;
 
 /// A `bb8::ManageConnection` for `redis::Client::get_async_connection`.
 #[derive(Clone, Debug)]
@@ -41,20 +41,94 @@ impl bb8::ManageConnection for RedisConnectionManager {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+/// Every key (or key prefix) the application keeps in Redis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr)]
 pub enum CacheKey {
-    PlayerSession = 1,
-    RankTable = 3,
-    ClassTable = 4,
-}
+    // multiple containers with TTL, session_{player_id} -> encoded PlayerSession
+    #[strum(serialize = "session")]
+    Session,
 
-impl AsRef<str> for CacheKey {
-    fn as_ref(&self) -> &str {
-        match self {
-            CacheKey::PlayerSession => "player_session",
-            CacheKey::RankTable => "rank_table",
-            CacheKey::ClassTable => "class_table",
-        }
-    }
+    // multiple containers with TTL, refresh_token_{token} -> encoded RefreshToken
+    #[strum(serialize = "refresh_token")]
+    RefreshToken,
+
+    // multiple containers, zone_{zone_id} -> player_id
+    #[strum(serialize = "zone")]
+    ZonePlayers,
+
+    // Hash: rank_id -> rank name
+    #[strum(serialize = "rank_table")]
+    RankTable,
+
+    // Hash: rank_id -> JSON RankRequirement
+    #[strum(serialize = "rank_requirements")]
+    RankRequirements,
+
+    // Hash: player_id -> encoded RankPromotion, the latest one
+    #[strum(serialize = "rank_promotion")]
+    RankPromotion,
+
+    // Hash: class_id -> class name
+    #[strum(serialize = "class_table")]
+    ClassTable,
+
+    // Hash: class_id -> JSON PlayerClass
+    #[strum(serialize = "class_details")]
+    ClassDetails,
+
+    // Hash: exp -> JSON PlayerExperienceTable
+    #[strum(serialize = "experience_table")]
+    ExperienceTable,
+
+    // List: chat_zone_{zone_id} -> encoded ChatMessage, capped
+    #[strum(serialize = "chat")]
+    Chat,
+
+    // List: chat_whisper_{player_id} -> encoded ChatMessage, capped
+    #[strum(serialize = "chat_whisper")]
+    ChatWhisper,
+
+    // Counter of chat message ids
+    #[strum(serialize = "chat_seq")]
+    ChatSequence,
+
+    // Counter with TTL: chat_throttle_{player_id}
+    #[strum(serialize = "chat_throttle")]
+    ChatThrottle,
+
+    // String with TTL: chat_last_{player_id}
+    #[strum(serialize = "chat_last")]
+    ChatLastMessage,
+
+    // Sorted set with TTL: rate_limit_{route}_{client} -> request timestamps in millis
+    #[strum(serialize = "rate_limit")]
+    RateLimit,
+
+    // Counter with TTL: login_failures_{nickname}
+    #[strum(serialize = "login_failures")]
+    LoginFailures,
+
+    // String with TTL: login_lockout_{nickname}, the TTL is the lockout left
+    #[strum(serialize = "login_lockout")]
+    LoginLockout,
+
+    // String with TTL: email_verification_{token} -> player_id, single use
+    #[strum(serialize = "email_verification")]
+    EmailVerification,
+
+    // String with TTL: password_reset_{token} -> player_id, single use
+    #[strum(serialize = "password_reset")]
+    PasswordReset,
+
+    // Hash: map_location id -> JSON MapLocation
+    #[strum(serialize = "map_locations")]
+    MapLocations,
+
+    // multiple containers with TTL, travel_{player_id} -> encoded Travel, while travelling
+    #[strum(serialize = "travel")]
+    Travel,
+
+    // Hash: player_id -> link to the guild of the player
+    #[strum(serialize = "player_guild")]
+    PlayerGuild,
 }
@@ -0,0 +1,153 @@
+use crate::app::battle::{Battle, Combatant, Participant};
+use crate::app::protos::messages::RankPromotion;
+use crate::app::stats::CombatStats;
+use crate::app_state::AppState;
+use crate::error::Result;
+use crate::model::battle::NewBattleParticipant;
+use crate::model::r#static::PlayerExperienceTable;
+use chrono::Utc;
+use tracing::error;
+
+/// Valor granted to a player for a won battle where they dealt any damage.
+pub const VALOR_PER_WON_BATTLE: i32 = 1;
+
+/// Adds valor to the player and, if they meet the requirements of a new rank now, promotes
+/// them. No valor at all just re-checks the rank.
+/// A promotion is kept for the profile and pushed to the player's WebSocket connections.
+pub async fn award_valor(
+    state: &AppState,
+    p_id: i32,
+    nickname: &str,
+    gained: i32,
+) -> Result<Option<RankPromotion>> {
+    let rank_requirements = state
+        .static_table_middleware
+        .get_rank_requirements()
+        .await?;
+    let Some((old_rank_id, new_rank_id)) = state
+        .player_middleware
+        .add_valor(p_id, gained, rank_requirements)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let promotion = RankPromotion {
+        player_id: p_id as i64,
+        nickname: nickname.to_owned(),
+        old_rank_id,
+        new_rank_id,
+        rank_name: state
+            .static_table_middleware
+            .get_rank_name_by_id(new_rank_id)
+            .await?,
+        promoted_at: Utc::now().timestamp_millis(),
+    };
+    state
+        .player_middleware
+        .save_rank_promotion(&promotion)
+        .await?;
+    state
+        .gateway_middleware
+        .publish_rank_promotion(promotion.clone());
+
+    Ok(Some(promotion))
+}
+
+/// Grants experience and valor of a finished battle to its player participants, keeps the
+/// health they left the battle with and lets them out of the battle. A player who can't be
+/// rewarded is logged and skipped, the others still get their rewards.
+pub async fn award_battle_rewards(
+    state: &AppState,
+    battle_id: &str,
+    battle: &Battle,
+) -> Result<()> {
+    let experience_table = state.static_table_middleware.get_experience_table().await?;
+
+    for (participant, record) in battle
+        .participants()
+        .iter()
+        .zip(battle.participant_records())
+    {
+        let Combatant::Player { player_id, .. } = &participant.fighter.combatant else {
+            continue;
+        };
+
+        if let Err(e) =
+            award_participant(state, battle_id, participant, &record, &experience_table).await
+        {
+            error!(
+                "Can't reward player {} for battle {}: {:?}",
+                player_id, battle_id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn award_participant(
+    state: &AppState,
+    battle_id: &str,
+    participant: &Participant,
+    record: &NewBattleParticipant,
+    experience_table: &[PlayerExperienceTable],
+) -> Result<()> {
+    let Combatant::Player {
+        player_id,
+        nickname,
+    } = &participant.fighter.combatant
+    else {
+        return Ok(());
+    };
+
+    let progression = state
+        .player_middleware
+        .add_experience(*player_id, record.gained_exp, experience_table.to_vec())
+        .await?;
+    let now = Utc::now().timestamp();
+    let stats = player_stats(state, *player_id).await?;
+    let vitals = state
+        .cache_middleware
+        .get_vitals(*player_id as i64, &stats, now)
+        .await?;
+    state
+        .cache_middleware
+        .update_session(*player_id as i64, |session| {
+            session.level = progression.level as u32;
+            session.health = participant.health.min(stats.max_health);
+            session.stamina = vitals.stamina;
+            session.vitals_updated_at = vitals.updated_at;
+            if session.link_to_battle.as_deref() == Some(battle_id) {
+                session.is_in_battle = false;
+                session.link_to_battle = None;
+            }
+            true
+        })
+        .await?;
+
+    // Ranks require levels too, so a level-up alone may be enough for a promotion.
+    let gained_valor = if record.gained_valor {
+        VALOR_PER_WON_BATTLE
+    } else {
+        0
+    };
+    if gained_valor > 0 || progression.level_changed {
+        award_valor(state, *player_id, nickname, gained_valor).await?;
+    }
+
+    Ok(())
+}
+
+/// Derives combat stats of the player from the stored attributes and inventory.
+pub async fn player_stats(state: &AppState, p_id: i32) -> Result<CombatStats> {
+    let attributes = state.player_middleware.get_attributes(p_id).await?;
+    let class_progress = state.player_middleware.get_class_progress(p_id).await?;
+    let inventory = state.inventory_middleware.get_inventory(p_id).await?;
+
+    Ok(CombatStats::derive(
+        &attributes,
+        class_progress.as_ref(),
+        &inventory,
+    ))
+}
@@ -0,0 +1,169 @@
+use crate::app::middleware::rate_limit_middleware::{ClientIp, RateLimitedRoute};
+use crate::app::session::end_session;
+use crate::app::validation::validate_password;
+use crate::app_state::AppState;
+use crate::error::{AppError, Result};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountActionResponse {
+    pub ok: bool,
+}
+
+pub fn account_router() -> Router<AppState> {
+    Router::new()
+        .route("/email/verify", post(verify_email))
+        .route("/email/verify/resend", post(resend_email_verification))
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
+}
+
+pub(crate) async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<Json<AccountActionResponse>> {
+    let AppState {
+        player_middleware,
+        account_middleware,
+        ..
+    } = state;
+
+    let player_id = account_middleware
+        .consume_email_verification(&payload.token)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+    player_middleware.set_email_verified(player_id).await?;
+
+    Ok(Json(AccountActionResponse { ok: true }))
+}
+
+/// Answers the same whether the email is known or not, so it can't be used to look up players.
+pub(crate) async fn resend_email_verification(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<EmailRequest>,
+) -> Result<Json<AccountActionResponse>> {
+    let AppState {
+        player_middleware,
+        account_middleware,
+        rate_limit_middleware,
+        ..
+    } = state;
+    rate_limit_middleware
+        .check(RateLimitedRoute::AccountMail, &ip)
+        .await?;
+
+    if let Some(player) = player_middleware
+        .get_player_by_email(payload.email)
+        .await?
+        .filter(|player| player.email_verified == 0)
+    {
+        let player_id = player.id.ok_or(AppError::PlayerNotFound(player.nickname))?;
+        account_middleware
+            .send_email_verification(player_id, &player.email)
+            .await?;
+    }
+
+    Ok(Json(AccountActionResponse { ok: true }))
+}
+
+/// Answers the same whether the email is known or not, so it can't be used to look up players.
+pub(crate) async fn forgot_password(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<EmailRequest>,
+) -> Result<Json<AccountActionResponse>> {
+    let AppState {
+        player_middleware,
+        account_middleware,
+        rate_limit_middleware,
+        ..
+    } = state;
+    rate_limit_middleware
+        .check(RateLimitedRoute::AccountMail, &ip)
+        .await?;
+
+    if let Some(player) = player_middleware.get_player_by_email(payload.email).await? {
+        let player_id = player.id.ok_or(AppError::PlayerNotFound(player.nickname))?;
+        account_middleware
+            .send_password_reset(player_id, &player.email)
+            .await?;
+    }
+
+    Ok(Json(AccountActionResponse { ok: true }))
+}
+
+/// Sets a new password, ends the session started with the old one and lifts a login lockout.
+pub(crate) async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<Json<AccountActionResponse>> {
+    let app_state = state.clone();
+    let AppState {
+        player_middleware,
+        account_middleware,
+        rate_limit_middleware,
+        ..
+    } = state;
+
+    // A rejected password leaves the token valid, the token is used up by a reset only.
+    let player_id = account_middleware
+        .peek_password_reset(&payload.token)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+    let player = player_middleware
+        .get_player_by_id(player_id)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+    validate_password(&payload.password, &player.nickname)
+        .map_err(|error| AppError::Validation(vec![error]))?;
+    // Only one of the concurrent resets with the token gets through.
+    if account_middleware
+        .consume_password_reset(&payload.token)
+        .await?
+        != Some(player_id)
+    {
+        return Err(AppError::InvalidToken);
+    }
+
+    player_middleware
+        .set_password(
+            player_id,
+            password_auth::generate_hash(payload.password.as_bytes()),
+        )
+        .await?;
+    // Receiving the token proves the email, so there is no need to verify it separately.
+    player_middleware.set_email_verified(player_id).await?;
+    end_session(&app_state, player_id).await?;
+    if let Err(e) = rate_limit_middleware
+        .reset_login_failures(&player.nickname)
+        .await
+    {
+        warn!(
+            "Can't lift the login lockout of {}: {:?}",
+            player.nickname, e
+        );
+    }
+
+    Ok(Json(AccountActionResponse { ok: true }))
+}
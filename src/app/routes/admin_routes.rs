@@ -0,0 +1,242 @@
+use crate::app::middleware::moderation_middleware::{
+    check_reason, expires_in, AUDIT_DEFAULT_LIMIT,
+};
+use crate::app::session::end_session;
+use crate::app_state::{AdminPlayer, AppState};
+use crate::error::Result;
+use crate::model::moderation::{ModerationAction, ModerationAuditEntry};
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct BanRequest {
+    pub reason: String,
+    /// The ban is permanent without a duration.
+    pub duration_secs: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MuteRequest {
+    pub reason: String,
+    pub duration_secs: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameRequest {
+    pub new_nickname: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationNoteRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub nickname: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModerationResponse {
+    pub id: i32,
+    pub admin_id: i32,
+    pub target_id: i32,
+    pub action: String,
+    pub reason: Option<String>,
+    pub details: Option<String>,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+}
+
+impl From<ModerationAuditEntry> for ModerationResponse {
+    fn from(entry: ModerationAuditEntry) -> Self {
+        ModerationResponse {
+            id: entry.id,
+            admin_id: entry.admin_id,
+            target_id: entry.target_id,
+            action: entry.action,
+            reason: entry.reason,
+            details: entry.details,
+            created_at: entry.created_at.and_utc().timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditResponse {
+    pub entries: Vec<ModerationResponse>,
+}
+
+pub fn admin_router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/player/{nickname}/ban", post(ban))
+        .route("/admin/player/{nickname}/unban", post(unban))
+        .route("/admin/player/{nickname}/mute", post(mute))
+        .route("/admin/player/{nickname}/unmute", post(unmute))
+        .route("/admin/player/{nickname}/logout", post(force_logout))
+        .route("/admin/player/{nickname}/rename", post(rename))
+        .route("/admin/audit", get(audit))
+}
+
+/// Bans the player and ends the session, closing their sockets, so the ban applies immediately.
+pub(crate) async fn ban(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<BanRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let reason = check_reason(&payload.reason)?;
+    let until = payload.duration_secs.map(expires_in).transpose()?;
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    let entry = state
+        .moderation_middleware
+        .ban(admin.player_id, target_id, reason, until)
+        .await?;
+    end_session(&state, target_id).await?;
+
+    Ok(Json(entry.into()))
+}
+
+pub(crate) async fn unban(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<ModerationNoteRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    Ok(Json(
+        state
+            .moderation_middleware
+            .unban(admin.player_id, target_id, payload.reason)
+            .await?
+            .into(),
+    ))
+}
+
+pub(crate) async fn mute(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<MuteRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let reason = check_reason(&payload.reason)?;
+    let until = expires_in(payload.duration_secs)?;
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    Ok(Json(
+        state
+            .moderation_middleware
+            .mute(admin.player_id, target_id, reason, until)
+            .await?
+            .into(),
+    ))
+}
+
+pub(crate) async fn unmute(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<ModerationNoteRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    Ok(Json(
+        state
+            .moderation_middleware
+            .unmute(admin.player_id, target_id, payload.reason)
+            .await?
+            .into(),
+    ))
+}
+
+/// Ends the session of the player, both the access and the refresh token stop working and
+/// their sockets are closed.
+pub(crate) async fn force_logout(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<ModerationNoteRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    let entry = state
+        .moderation_middleware
+        .record(
+            admin.player_id,
+            target_id,
+            ModerationAction::ForceLogout,
+            payload.reason,
+        )
+        .await?;
+    end_session(&state, target_id).await?;
+
+    Ok(Json(entry.into()))
+}
+
+/// Renames the player and ends the session, the tokens carry the old nickname.
+pub(crate) async fn rename(
+    AdminPlayer(admin): AdminPlayer,
+    State(state): State<AppState>,
+    Path(nickname): Path<String>,
+    Json(payload): Json<RenameRequest>,
+) -> Result<Json<ModerationResponse>> {
+    let reason = check_reason(&payload.reason)?;
+    let target_id = state
+        .player_middleware
+        .get_player_id_by_nick(nickname)
+        .await?;
+
+    let entry = state
+        .moderation_middleware
+        .rename(admin.player_id, target_id, payload.new_nickname, reason)
+        .await?;
+    end_session(&state, target_id).await?;
+
+    Ok(Json(entry.into()))
+}
+
+pub(crate) async fn audit(
+    AdminPlayer(_): AdminPlayer,
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditResponse>> {
+    let target = match query.nickname {
+        Some(nickname) => Some(
+            state
+                .player_middleware
+                .get_player_id_by_nick(nickname)
+                .await?,
+        ),
+        None => None,
+    };
+
+    let entries = state
+        .moderation_middleware
+        .get_audit(target, query.limit.unwrap_or(AUDIT_DEFAULT_LIMIT))
+        .await?;
+
+    Ok(Json(AuditResponse {
+        entries: entries.into_iter().map(Into::into).collect(),
+    }))
+}
@@ -0,0 +1,134 @@
+use crate::app::battle::{Battle, BattleAction, ParticipantId};
+use crate::app::combat::{current_battle, play_turn};
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::{AppError, Result};
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BattleActionRequest {
+    Move { col: i32, row: i32 },
+    Attack { target: ParticipantId },
+    EndTurn,
+}
+
+impl From<BattleActionRequest> for BattleAction {
+    fn from(request: BattleActionRequest) -> Self {
+        match request {
+            BattleActionRequest::Move { col, row } => BattleAction::Move { col, row },
+            BattleActionRequest::Attack { target } => BattleAction::Attack { target },
+            BattleActionRequest::EndTurn => BattleAction::EndTurn,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParticipantResponse {
+    pub id: ParticipantId,
+    pub name: String,
+    pub faction: i32,
+    pub col: i32,
+    pub row: i32,
+    pub health: i32,
+    pub max_health: i32,
+    pub action_points: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BattleResponse {
+    pub battle_id: String,
+    pub round: u32,
+    /// Participant of the requesting player.
+    pub participant: ParticipantId,
+    pub current_participant: Option<ParticipantId>,
+    /// Faction id of the winners once the battle is over.
+    pub winner: Option<i32>,
+    /// Participants the player's faction sees.
+    pub participants: Vec<ParticipantResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BattleActResponse {
+    pub events: Vec<String>,
+    pub battle: BattleResponse,
+}
+
+impl BattleResponse {
+    /// The battle as the player sees it, the participants out of sight are left out.
+    fn new(battle_id: String, battle: &Battle, participant: ParticipantId) -> Self {
+        let visible =
+            battle.visible_participants(battle.participants()[participant].fighter.faction);
+        BattleResponse {
+            battle_id,
+            round: battle.round(),
+            participant,
+            current_participant: battle
+                .current_participant()
+                .filter(|current| visible.contains(current)),
+            winner: battle.winner().map(|winner| winner.id()),
+            participants: battle
+                .participants()
+                .iter()
+                .enumerate()
+                .filter(|(id, _)| visible.contains(id))
+                .map(|(id, p)| ParticipantResponse {
+                    id,
+                    name: p.fighter.combatant.name().to_owned(),
+                    faction: p.fighter.faction.id(),
+                    col: p.position.0,
+                    row: p.position.1,
+                    health: p.health,
+                    max_health: p.fighter.max_health,
+                    action_points: p.action_points,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn battle_router() -> Router<AppState> {
+    Router::new()
+        .route("/battle", get(battle_status))
+        .route("/battle/act", post(battle_act))
+}
+
+pub(crate) async fn battle_status(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+) -> Result<Json<BattleResponse>> {
+    let (battle_id, battle) = current_battle(&state, player.player_id).await?;
+    let battle = battle.lock().await;
+    let participant = battle
+        .player_participant(player.player_id)
+        .ok_or(AppError::Forbidden)?;
+
+    Ok(Json(BattleResponse::new(battle_id, &battle, participant)))
+}
+
+pub(crate) async fn battle_act(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Json(payload): Json<BattleActionRequest>,
+) -> Result<Json<BattleActResponse>> {
+    let (battle_id, battle) = current_battle(&state, player.player_id).await?;
+    let mut battle = battle.lock().await;
+    let events = play_turn(
+        &state,
+        &battle_id,
+        &mut battle,
+        player.player_id,
+        payload.into(),
+    )
+    .await?;
+    let participant = battle
+        .player_participant(player.player_id)
+        .ok_or(AppError::Forbidden)?;
+
+    Ok(Json(BattleActResponse {
+        events: events.iter().map(ToString::to_string).collect(),
+        battle: BattleResponse::new(battle_id, &battle, participant),
+    }))
+}
@@ -0,0 +1,142 @@
+use crate::app::middleware::cache_middleware::CacheMiddleware;
+use crate::app::middleware::chat_middleware::WhisperRecipient;
+use crate::app::protos::messages::ChatMessage;
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::{AppError, Result};
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct PostChatMessageRequest {
+    pub content: String,
+    pub whisper_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatHistoryQuery {
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatMessageResponse {
+    pub id: i64,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: i64,
+    pub whisper_to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatHistoryResponse {
+    pub messages: Vec<ChatMessageResponse>,
+}
+
+impl From<ChatMessage> for ChatMessageResponse {
+    fn from(message: ChatMessage) -> Self {
+        ChatMessageResponse {
+            id: message.id,
+            sender: message.sender,
+            content: message.content,
+            timestamp: message.sent_at,
+            whisper_to: message.whisper_to,
+        }
+    }
+}
+
+pub fn chat_router() -> Router<AppState> {
+    Router::new().route(
+        "/zone/{zone_id}/chat",
+        get(chat_history).post(post_chat_message),
+    )
+}
+
+pub(crate) async fn post_chat_message(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(zone_id): Path<i64>,
+    Json(payload): Json<PostChatMessageRequest>,
+) -> Result<Json<ChatMessageResponse>> {
+    let AppState {
+        player_middleware,
+        cache_middleware,
+        chat_middleware,
+        gateway_middleware,
+        ..
+    } = state;
+
+    if let Some(until) = player
+        .muted_until
+        .filter(|until| *until > Utc::now().naive_utc())
+    {
+        return Err(AppError::PlayerMuted(until));
+    }
+    ensure_in_zone(&cache_middleware, zone_id, player.player_id).await?;
+    // Whispers can be addressed to existing players only, under their stored nickname.
+    let recipient = match payload.whisper_to {
+        Some(whisper_to) => {
+            let recipient = player_middleware
+                .get_player_by_nick(whisper_to.clone())
+                .await
+                .map_err(|e| match e {
+                    AppError::PlayerNotFound(_) => AppError::RecipientNotFound(whisper_to.clone()),
+                    e => e,
+                })?;
+            Some(WhisperRecipient {
+                player_id: recipient
+                    .id
+                    .ok_or(AppError::RecipientNotFound(whisper_to))?
+                    as i64,
+                nickname: recipient.nickname,
+            })
+        }
+        None => None,
+    };
+    let recipient_id = recipient.as_ref().map(|recipient| recipient.player_id);
+
+    let message = chat_middleware
+        .post_message(
+            zone_id,
+            player.player_id as i64,
+            &player.nickname,
+            &payload.content,
+            recipient,
+        )
+        .await?;
+    gateway_middleware.publish_chat_message(message.clone(), player.player_id as i64, recipient_id);
+
+    Ok(Json(message.into()))
+}
+
+pub(crate) async fn chat_history(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(zone_id): Path<i64>,
+    Query(query): Query<ChatHistoryQuery>,
+) -> Result<Json<ChatHistoryResponse>> {
+    let AppState {
+        cache_middleware,
+        chat_middleware,
+        ..
+    } = state;
+
+    ensure_in_zone(&cache_middleware, zone_id, player.player_id).await?;
+    let messages = chat_middleware
+        .get_messages(zone_id, player.player_id as i64, query.since.unwrap_or(0))
+        .await?;
+
+    Ok(Json(ChatHistoryResponse {
+        messages: messages.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Zone chat is open to the players in the zone only.
+async fn ensure_in_zone(cache: &CacheMiddleware, zone_id: i64, player_id: i32) -> Result<()> {
+    if cache.is_in_zone(zone_id, player_id as i64).await? {
+        Ok(())
+    } else {
+        Err(AppError::NotInZone(zone_id))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::app::battle::Faction;
+use crate::app::middleware::gateway_middleware::{GatewayEvent, Topic};
+use crate::app::protos::messages::client_frame::Command;
+use crate::app::protos::messages::server_frame::Payload;
+use crate::app::protos::messages::zone_event::Kind;
+use crate::app::protos::messages::{ClientFrame, ZoneEvent};
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use prost::Message as ProstMessage;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+/// How often an open connection checks that its session is still alive.
+pub const SESSION_CHECK_INTERVAL_SECS: u64 = 30;
+
+pub fn gateway_router() -> Router<AppState> {
+    Router::new().route("/ws", get(gateway))
+}
+
+pub(crate) async fn gateway(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| serve_socket(socket, player.player_id, state))
+}
+
+/// Pumps frames in both directions until either the client or the gateway goes away, or the
+/// session the connection was opened with ends. Client frames manage subscriptions, server
+/// frames are prost-encoded `ServerFrame`s.
+async fn serve_socket(mut socket: WebSocket, player_id: i32, state: AppState) {
+    // Refreshes replace the token of the session but keep its family.
+    let refresh_family = match state.cache_middleware.get_session(player_id as i64).await {
+        Ok(Some(session)) => session.refresh_family,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Can't get the session of player {player_id}: {:?}", e);
+            return;
+        }
+    };
+    let mut events = state.gateway_middleware.subscribe();
+    let mut topics: HashSet<Topic> = HashSet::from([Topic::Player(player_id as i64)]);
+    // Sessions may also end without a word to the gateway, e.g. by expiring.
+    let mut session_check = tokio::time::interval(Duration::from_secs(SESSION_CHECK_INTERVAL_SECS));
+
+    loop {
+        tokio::select! {
+            _ = session_check.tick() => match state
+                .cache_middleware
+                .get_session(player_id as i64)
+                .await
+            {
+                Ok(Some(session)) if session.refresh_family == refresh_family => {}
+                Ok(_) => break,
+                Err(e) => warn!("Can't check the session of player {player_id}: {:?}", e),
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Binary(bytes))) => match ClientFrame::decode(bytes) {
+                    Ok(frame) => apply_client_frame(&state, player_id, frame, &mut topics).await,
+                    Err(e) => debug!("Malformed client frame: {:?}", e),
+                },
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    debug!("WebSocket receive error: {:?}", e);
+                    break;
+                }
+            },
+            event = events.recv() => match event {
+                Ok(GatewayEvent { topic, frame }) if topics.contains(&topic) => {
+                    let bytes = frame.encode_to_vec();
+                    if socket.send(Message::Binary(bytes.into())).await.is_err() {
+                        break;
+                    }
+                    if matches!(frame.payload, Some(Payload::SessionEnded(_))) {
+                        break;
+                    }
+                    // A player who left the zone stops receiving its events.
+                    if let Some(Payload::ZoneEvent(ZoneEvent { kind, zone_id, player_id: id, .. })) =
+                        frame.payload
+                    {
+                        if kind == Kind::Leave as i32 && id == player_id as i64 {
+                            topics.remove(&Topic::Zone(zone_id));
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket client lagged behind, {skipped} frames skipped");
+                }
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+    // The client may be gone already, there is nobody to tell then.
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Players subscribe only to the zone they are in and to the battles they take part in.
+async fn apply_client_frame(
+    state: &AppState,
+    player_id: i32,
+    frame: ClientFrame,
+    topics: &mut HashSet<Topic>,
+) {
+    match frame.command {
+        Some(Command::SubscribeZone(zone_id)) => {
+            match state
+                .cache_middleware
+                .is_in_zone(zone_id, player_id as i64)
+                .await
+            {
+                Ok(true) => {
+                    topics.insert(Topic::Zone(zone_id));
+                }
+                Ok(false) => debug!("Player {player_id} is not in zone {zone_id}"),
+                Err(e) => warn!("Can't check the zone of player {player_id}: {:?}", e),
+            }
+        }
+        Some(Command::UnsubscribeZone(zone_id)) => {
+            topics.remove(&Topic::Zone(zone_id));
+        }
+        Some(Command::SubscribeBattle(battle_id)) => {
+            match battle_faction(state, player_id, &battle_id).await {
+                Some(faction) => {
+                    topics.insert(Topic::Battle(battle_id, faction));
+                }
+                None => debug!("Player {player_id} doesn't take part in battle {battle_id}"),
+            }
+        }
+        Some(Command::UnsubscribeBattle(battle_id)) => {
+            topics.retain(|topic| !matches!(topic, Topic::Battle(id, _) if *id == battle_id));
+        }
+        None => {}
+    }
+}
+
+/// Faction the player fights for in the battle, `None` if they don't take part in it.
+async fn battle_faction(state: &AppState, player_id: i32, battle_id: &str) -> Option<Faction> {
+    let battle = state.battle_middleware.get_battle(battle_id)?;
+    let battle = battle.lock().await;
+    let participant = battle.player_participant(player_id)?;
+
+    Some(battle.participants()[participant].fighter.faction)
+}
@@ -0,0 +1,187 @@
+use crate::app::middleware::guild_middleware::{GuildMemberInfo, PendingInvite};
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::Result;
+use crate::model::guild::Guild;
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGuildRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GuildMemberRequest {
+    pub nickname: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuildResponse {
+    pub id: i32,
+    pub name: String,
+    pub rank: i32,
+    pub members: Vec<GuildMemberInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingInvitesResponse {
+    pub invites: Vec<PendingInvite>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuildActionResponse {
+    pub ok: bool,
+}
+
+pub fn guild_router() -> Router<AppState> {
+    Router::new()
+        .route("/guild", post(create_guild))
+        .route("/guild/invites", get(pending_invites))
+        .route("/guild/{guild_id}", get(guild_info).delete(disband_guild))
+        .route("/guild/{guild_id}/invite", post(invite))
+        .route("/guild/{guild_id}/accept", post(accept_invite))
+        .route("/guild/{guild_id}/kick", post(kick))
+        .route("/guild/{guild_id}/leave", post(leave))
+        .route("/guild/{guild_id}/promote", post(promote_officer))
+}
+
+pub(crate) async fn create_guild(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Json(payload): Json<CreateGuildRequest>,
+) -> Result<Json<GuildResponse>> {
+    let guild = state
+        .guild_middleware
+        .create_guild(player.player_id, payload.name)
+        .await?;
+
+    guild_response(&state, guild).await
+}
+
+/// Invites of the player waiting to be accepted.
+pub(crate) async fn pending_invites(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+) -> Result<Json<PendingInvitesResponse>> {
+    let invites = state
+        .guild_middleware
+        .get_pending_invites(player.player_id)
+        .await?;
+
+    Ok(Json(PendingInvitesResponse { invites }))
+}
+
+pub(crate) async fn guild_info(
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+) -> Result<Json<GuildResponse>> {
+    let guild = state.guild_middleware.get_guild(guild_id).await?;
+
+    guild_response(&state, guild).await
+}
+
+pub(crate) async fn disband_guild(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+) -> Result<Json<GuildActionResponse>> {
+    state
+        .guild_middleware
+        .disband_guild(player.player_id, guild_id)
+        .await?;
+
+    Ok(Json(GuildActionResponse { ok: true }))
+}
+
+pub(crate) async fn invite(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+    Json(payload): Json<GuildMemberRequest>,
+) -> Result<Json<GuildActionResponse>> {
+    let invitee_id = state
+        .player_middleware
+        .get_player_id_by_nick(payload.nickname)
+        .await?;
+    state
+        .guild_middleware
+        .invite(player.player_id, guild_id, invitee_id)
+        .await?;
+
+    Ok(Json(GuildActionResponse { ok: true }))
+}
+
+pub(crate) async fn accept_invite(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+) -> Result<Json<GuildResponse>> {
+    let guild = state
+        .guild_middleware
+        .accept_invite(player.player_id, guild_id)
+        .await?;
+
+    guild_response(&state, guild).await
+}
+
+pub(crate) async fn kick(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+    Json(payload): Json<GuildMemberRequest>,
+) -> Result<Json<GuildActionResponse>> {
+    let kicked_id = state
+        .player_middleware
+        .get_player_id_by_nick(payload.nickname)
+        .await?;
+    state
+        .guild_middleware
+        .kick(player.player_id, guild_id, kicked_id)
+        .await?;
+
+    Ok(Json(GuildActionResponse { ok: true }))
+}
+
+pub(crate) async fn leave(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+) -> Result<Json<GuildActionResponse>> {
+    state
+        .guild_middleware
+        .leave(player.player_id, guild_id)
+        .await?;
+
+    Ok(Json(GuildActionResponse { ok: true }))
+}
+
+pub(crate) async fn promote_officer(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(guild_id): Path<i32>,
+    Json(payload): Json<GuildMemberRequest>,
+) -> Result<Json<GuildActionResponse>> {
+    let promoted_id = state
+        .player_middleware
+        .get_player_id_by_nick(payload.nickname)
+        .await?;
+    state
+        .guild_middleware
+        .promote_officer(player.player_id, guild_id, promoted_id)
+        .await?;
+
+    Ok(Json(GuildActionResponse { ok: true }))
+}
+
+async fn guild_response(state: &AppState, guild: Guild) -> Result<Json<GuildResponse>> {
+    let members = state.guild_middleware.get_members(guild.id).await?;
+
+    Ok(Json(GuildResponse {
+        id: guild.id,
+        name: guild.name,
+        rank: guild.rank,
+        members,
+    }))
+}
@@ -0,0 +1,172 @@
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::{AppError, Result};
+use crate::model::item::InventoryEntry;
+use crate::model::player::PlayerAttributes;
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeaponResponse {
+    pub action_points_to_use: i32,
+    pub basic_damage: i32,
+    pub range: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InventoryItemResponse {
+    pub id: i32,
+    pub item_id: i32,
+    pub name: String,
+    pub kind: String,
+    pub amount: i32,
+    pub weight: f32,
+    pub equipped: bool,
+    pub level_req: i32,
+    pub strength_req: i32,
+    pub dexterity_req: i32,
+    pub physique_req: i32,
+    pub intellect_req: i32,
+    pub valor_req: i32,
+    pub class_req: Option<i32>,
+    pub weapon: Option<WeaponResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InventoryResponse {
+    pub nickname: String,
+    pub items: Vec<InventoryItemResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EquipItemRequest {
+    pub inventory_id: i32,
+}
+
+impl From<InventoryEntry> for InventoryItemResponse {
+    fn from(entry: InventoryEntry) -> Self {
+        let InventoryEntry {
+            slot,
+            item,
+            weapon,
+            gear,
+        } = entry;
+        let kind = match (&weapon, &gear) {
+            (Some(_), _) => "weapon",
+            (None, Some(_)) => "gear",
+            (None, None) => "other",
+        };
+
+        InventoryItemResponse {
+            id: slot.id,
+            item_id: item.id,
+            name: item.name,
+            kind: kind.to_owned(),
+            amount: slot.amount,
+            weight: slot.weight,
+            equipped: slot.equipped,
+            level_req: item.level_req,
+            strength_req: item.strength_req,
+            dexterity_req: item.dexterity_req,
+            physique_req: item.physique_req,
+            intellect_req: item.intellect_req,
+            valor_req: item.valor_req,
+            class_req: item.class_req,
+            weapon: weapon.map(|w| WeaponResponse {
+                action_points_to_use: w.action_points_to_use,
+                basic_damage: w.basic_damage,
+                range: w.range,
+            }),
+        }
+    }
+}
+
+pub fn inventory_router() -> Router<AppState> {
+    Router::new()
+        .route("/player/inventory/{player_nickname}", get(player_inventory))
+        .route(
+            "/player/inventory/{player_nickname}/equip",
+            post(equip_item),
+        )
+        .route(
+            "/player/inventory/{player_nickname}/unequip",
+            post(unequip_item),
+        )
+}
+
+pub(crate) async fn player_inventory(
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+) -> Result<Json<InventoryResponse>> {
+    let AppState {
+        player_middleware,
+        inventory_middleware,
+        ..
+    } = state;
+
+    let player = player_middleware
+        .get_player_by_nick(player_nickname.clone())
+        .await?;
+    let player_id = player.id.ok_or(AppError::PlayerNotFound(player_nickname))?;
+    let items = inventory_middleware.get_inventory(player_id).await?;
+
+    Ok(Json(InventoryResponse {
+        nickname: player.nickname,
+        items: items.into_iter().map(Into::into).collect(),
+    }))
+}
+
+pub(crate) async fn equip_item(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+    Json(payload): Json<EquipItemRequest>,
+) -> Result<Json<InventoryItemResponse>> {
+    let attributes = own_attributes(&state, &player, player_nickname).await?;
+    let entry = state
+        .inventory_middleware
+        .equip_item(attributes, payload.inventory_id)
+        .await?;
+
+    Ok(Json(entry.into()))
+}
+
+pub(crate) async fn unequip_item(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+    Json(payload): Json<EquipItemRequest>,
+) -> Result<Json<InventoryResponse>> {
+    let attributes = own_attributes(&state, &player, player_nickname.clone()).await?;
+    state
+        .inventory_middleware
+        .unequip_item(attributes.player_id, payload.inventory_id)
+        .await?;
+    let items = state
+        .inventory_middleware
+        .get_inventory(attributes.player_id)
+        .await?;
+
+    Ok(Json(InventoryResponse {
+        nickname: player_nickname,
+        items: items.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Players can manage only their own inventory. Nicknames are case-insensitive, like the
+/// lookups by them.
+async fn own_attributes(
+    state: &AppState,
+    player: &AuthenticatedPlayer,
+    player_nickname: String,
+) -> Result<PlayerAttributes> {
+    if !player.nickname.eq_ignore_ascii_case(&player_nickname) {
+        return Err(AppError::Forbidden);
+    }
+
+    state
+        .player_middleware
+        .get_attributes(player.player_id)
+        .await
+}
@@ -1,8 +1,16 @@
 use crate::model::cache::PlayerInZone;
 use serde::{Deserialize, Serialize};
 
+pub mod account_routes;
+pub mod admin_routes;
+pub mod battle_routes;
+pub mod chat_routes;
+pub mod gateway_routes;
+pub mod guild_routes;
+pub mod inventory_routes;
 pub mod profile_routes;
 pub mod root_routes;
+pub mod travel_routes;
 
 #[derive(Debug, Deserialize)]
 pub struct RegisterPlayerRequest {
@@ -26,9 +34,24 @@ pub struct LoginPlayerRequest {
 #[derive(Debug, Serialize)]
 pub struct LoginPlayerResponse {
     access_token: String,
+    refresh_token: String,
+    /// Seconds the access token is valid for.
+    expires_in: i64,
     nickname: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshTokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LogoutPlayerRequest {
     nickname: String,
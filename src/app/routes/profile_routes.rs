@@ -1,7 +1,16 @@
-use crate::app_state::AppState;
-use crate::model::player::PlayerAttributes;
+use crate::app::middleware::static_tables_cache_middleware::StaticTablesCacheMiddleware;
+use crate::app::progression::{
+    attribute_cap, leading_spec, respec_cost, AttributeAllocation, CLASS_SELECTION_MIN_LEVEL,
+};
+use crate::app::protos::messages::RankPromotion;
+use crate::app::stats::CombatStats;
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::{AppError, Result};
+use crate::model::guild::GuildRole;
+use crate::model::player::{PlayerAttributes, PlayerClassProgress};
+use crate::model::r#static::PlayerClass;
 use axum::extract::{Path, State};
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::{Json, Router};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -11,32 +20,194 @@ pub struct PlayerProfileResponse {
     pub nickname: String,
     pub level: u8,
     pub rank: String,
+    pub last_promotion: Option<RankPromotionResponse>,
     pub spec: String,
+    pub guild: Option<ProfileGuildResponse>,
     pub health: i32,
     pub max_health: i32,
     pub stamina: i32,
     pub max_stamina: i32,
+    pub stats: CombatStatsResponse,
     pub strength: i32,
     pub dexterity: i32,
     pub physique: i32,
     pub luck: i32,
     pub intellect: i32,
+    pub attribute_points: i32,
     pub days_played: i32,
     pub inventory: Vec<String>,
     pub statistics: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileGuildResponse {
+    pub id: i32,
+    pub name: String,
+    pub rank: i32,
+    pub role: GuildRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CombatStatsResponse {
+    pub action_points: i32,
+    pub damage_bonus: i32,
+    pub dodge_chance: f32,
+    pub crit_chance: f32,
+    pub carry_weight: f32,
+    pub carried_weight: f32,
+}
+
+impl From<CombatStats> for CombatStatsResponse {
+    fn from(stats: CombatStats) -> Self {
+        CombatStatsResponse {
+            action_points: stats.action_points,
+            damage_bonus: stats.damage_bonus,
+            dodge_chance: stats.dodge_chance,
+            crit_chance: stats.crit_chance,
+            carry_weight: stats.carry_weight,
+            carried_weight: stats.carried_weight,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RankPromotionResponse {
+    pub old_rank_id: i32,
+    pub new_rank_id: i32,
+    pub rank_name: String,
+    pub promoted_at: i64,
+}
+
+impl From<RankPromotion> for RankPromotionResponse {
+    fn from(promotion: RankPromotion) -> Self {
+        RankPromotionResponse {
+            old_rank_id: promotion.old_rank_id,
+            new_rank_id: promotion.new_rank_id,
+            rank_name: promotion.rank_name,
+            promoted_at: promotion.promoted_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttributesResponse {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub physique: i32,
+    pub luck: i32,
+    pub intellect: i32,
+    pub attribute_points: i32,
+    pub attribute_cap: i32,
+    pub respec_cost: i32,
+    pub money: i32,
+}
+
+impl From<PlayerAttributes> for AttributesResponse {
+    fn from(attributes: PlayerAttributes) -> Self {
+        AttributesResponse {
+            strength: attributes.strength,
+            dexterity: attributes.dexterity,
+            physique: attributes.physique,
+            luck: attributes.luck,
+            intellect: attributes.intellect,
+            attribute_points: attributes.attribute_points,
+            attribute_cap: attribute_cap(attributes.level),
+            respec_cost: respec_cost(attributes.level),
+            money: attributes.money,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChooseClassRequest {
+    pub class_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpecProgressRequest {
+    /// 1-based spec index, in the order of the class spec names.
+    pub spec: u8,
+    pub points: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpecResponse {
+    pub name: Option<String>,
+    pub progress: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClassResponse {
+    pub class_id: i32,
+    pub class_name: String,
+    pub selection_min_level: i32,
+    pub specs: Vec<SpecResponse>,
+    pub total_progress: f32,
+    pub attribute_points: i32,
+}
+
+impl ClassResponse {
+    fn new(
+        class: PlayerClass,
+        class_progress: Option<PlayerClassProgress>,
+        attribute_points: i32,
+    ) -> Self {
+        let class_progress = class_progress.unwrap_or_default();
+        let progress = [
+            class_progress.first_spec_progress,
+            class_progress.second_spec_progress,
+            class_progress.third_spec_progress.unwrap_or(0.0),
+        ];
+
+        ClassResponse {
+            class_id: class.class_id,
+            specs: (1..=3)
+                .zip(progress)
+                .map(|(spec, progress)| SpecResponse {
+                    name: class.spec_name(spec).map(str::to_owned),
+                    progress,
+                })
+                .collect(),
+            class_name: class.class_name,
+            selection_min_level: CLASS_SELECTION_MIN_LEVEL,
+            total_progress: class_progress.total_progress.unwrap_or(0.0),
+            attribute_points,
+        }
+    }
+}
+
 pub fn profile_router() -> Router<AppState> {
-    Router::new().route("/profile/{player_nickname}", get(player_profile))
+    Router::new()
+        .route("/profile/{player_nickname}", get(player_profile))
+        .route(
+            "/profile/{player_nickname}/attributes",
+            post(allocate_attributes),
+        )
+        .route(
+            "/profile/{player_nickname}/attributes/respec",
+            post(respec_attributes),
+        )
+        .route(
+            "/profile/{player_nickname}/class",
+            get(player_class).post(choose_class),
+        )
+        .route(
+            "/profile/{player_nickname}/class/spec",
+            post(add_spec_progress),
+        )
 }
 
 pub(crate) async fn player_profile(
+    _player: AuthenticatedPlayer,
     State(state): State<AppState>,
     Path(player_nickname): Path<String>,
-) -> crate::error::Result<Json<PlayerProfileResponse>> {
+) -> Result<Json<PlayerProfileResponse>> {
     let AppState {
         player_middleware,
         static_table_middleware,
+        inventory_middleware,
+        cache_middleware,
+        guild_middleware,
         ..
     } = state;
 
@@ -44,6 +215,45 @@ pub(crate) async fn player_profile(
         .get_full_player_info_by_nick(player_nickname)
         .await?;
 
+    let inventory_entries = inventory_middleware
+        .get_inventory(player_attributes.player_id)
+        .await?;
+    let class_progress = player_middleware
+        .get_class_progress(player_attributes.player_id)
+        .await?
+        .filter(|class_progress| class_progress.class_id == player_attributes.class_id);
+    let stats = CombatStats::derive(
+        &player_attributes,
+        class_progress.as_ref(),
+        &inventory_entries,
+    );
+    let vitals = cache_middleware
+        .get_vitals(
+            player_attributes.player_id as i64,
+            &stats,
+            Utc::now().timestamp(),
+        )
+        .await?;
+    let inventory = inventory_entries
+        .into_iter()
+        .map(|entry| entry.item.name)
+        .collect();
+
+    let guild = guild_middleware
+        .get_player_guild(player_attributes.player_id)
+        .await?
+        .map(|(guild, role)| ProfileGuildResponse {
+            id: guild.id,
+            name: guild.name,
+            rank: guild.rank,
+            role,
+        });
+
+    let last_promotion = player_middleware
+        .get_rank_promotion(player_attributes.player_id)
+        .await?
+        .map(Into::into);
+
     let PlayerAttributes {
         class_id,
         rank_id,
@@ -53,6 +263,7 @@ pub(crate) async fn player_profile(
         luck,
         intellect,
         level,
+        attribute_points,
         ..
     } = player_attributes;
 
@@ -68,22 +279,160 @@ pub(crate) async fn player_profile(
         nickname: player.nickname,
         level: level as u8,
         rank: static_table_middleware.get_rank_name_by_id(rank_id).await?,
-        spec: static_table_middleware
-            .get_class_name_by_id(class_id)
-            .await?,
-        health: 0,
-        max_health: 0,
-        stamina: 0,
-        max_stamina: 0,
+        last_promotion,
+        spec: spec_title(&static_table_middleware, class_id, class_progress.as_ref()).await?,
+        guild,
+        health: vitals.health,
+        max_health: stats.max_health,
+        stamina: vitals.stamina,
+        max_stamina: stats.max_stamina,
+        stats: stats.into(),
         strength,
         dexterity,
         physique,
         luck,
         intellect,
+        attribute_points,
         days_played,
-        inventory: vec![],  // TODO, NYI
+        inventory,
         statistics: vec![], // TODO, NYI
     };
 
     Ok(Json(response))
 }
+
+pub(crate) async fn allocate_attributes(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+    Json(allocation): Json<AttributeAllocation>,
+) -> Result<Json<AttributesResponse>> {
+    let player_id = own_player_id(&player, &player_nickname)?;
+    let attributes = state
+        .player_middleware
+        .allocate_attributes(player_id, allocation)
+        .await?;
+
+    Ok(Json(attributes.into()))
+}
+
+pub(crate) async fn respec_attributes(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+) -> Result<Json<AttributesResponse>> {
+    let player_id = own_player_id(&player, &player_nickname)?;
+    let attributes = state.player_middleware.respec_attributes(player_id).await?;
+
+    Ok(Json(attributes.into()))
+}
+
+pub(crate) async fn player_class(
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+) -> Result<Json<ClassResponse>> {
+    let (_, attributes) = state
+        .player_middleware
+        .get_full_player_info_by_nick(player_nickname)
+        .await?;
+    let class_progress = state
+        .player_middleware
+        .get_class_progress(attributes.player_id)
+        .await?
+        .filter(|class_progress| class_progress.class_id == attributes.class_id);
+
+    class_response(&state, attributes, class_progress).await
+}
+
+pub(crate) async fn choose_class(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+    Json(payload): Json<ChooseClassRequest>,
+) -> Result<Json<ClassResponse>> {
+    let player_id = own_player_id(&player, &player_nickname)?;
+    if state
+        .static_table_middleware
+        .get_class_by_id(payload.class_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::ClassRejected(format!(
+            "unknown class {}",
+            payload.class_id
+        )));
+    }
+    let (attributes, class_progress) = state
+        .player_middleware
+        .choose_class(player_id, payload.class_id)
+        .await?;
+
+    class_response(&state, attributes, Some(class_progress)).await
+}
+
+pub(crate) async fn add_spec_progress(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Path(player_nickname): Path<String>,
+    Json(payload): Json<SpecProgressRequest>,
+) -> Result<Json<ClassResponse>> {
+    let player_id = own_player_id(&player, &player_nickname)?;
+    let (attributes, class_progress) = state
+        .player_middleware
+        .add_spec_progress(player_id, payload.spec, payload.points)
+        .await?;
+
+    class_response(&state, attributes, Some(class_progress)).await
+}
+
+async fn class_response(
+    state: &AppState,
+    attributes: PlayerAttributes,
+    class_progress: Option<PlayerClassProgress>,
+) -> Result<Json<ClassResponse>> {
+    let class = state
+        .static_table_middleware
+        .get_class_by_id(attributes.class_id)
+        .await?
+        .ok_or(AppError::QueryError(format!(
+            "Class {} is not cached",
+            attributes.class_id
+        )))?;
+
+    Ok(Json(ClassResponse::new(
+        class,
+        class_progress,
+        attributes.attribute_points,
+    )))
+}
+
+/// "Class: Spec" of the leading spec, or only the class name while no spec is progressed.
+async fn spec_title(
+    static_table_middleware: &StaticTablesCacheMiddleware,
+    class_id: i32,
+    class_progress: Option<&PlayerClassProgress>,
+) -> Result<String> {
+    let Some(class) = static_table_middleware.get_class_by_id(class_id).await? else {
+        return static_table_middleware.get_class_name_by_id(class_id).await;
+    };
+
+    Ok(
+        match class_progress
+            .and_then(leading_spec)
+            .and_then(|spec| class.spec_name(spec))
+        {
+            Some(spec_name) => format!("{}: {spec_name}", class.class_name),
+            None => class.class_name,
+        },
+    )
+}
+
+/// Players can change only their own attributes. Nicknames are case-insensitive, like the
+/// lookups by them.
+fn own_player_id(player: &AuthenticatedPlayer, player_nickname: &str) -> Result<i32> {
+    if !player.nickname.eq_ignore_ascii_case(player_nickname) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(player.player_id)
+}
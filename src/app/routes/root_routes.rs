@@ -1,50 +1,63 @@
-use crate::app_state::{AppState, Claims, Keys, JWT_AUTH_SECRET};
+use crate::app::key_ring::KeyRing;
+use crate::app::middleware::account_middleware::random_token;
+use crate::app::middleware::rate_limit_middleware::{ClientIp, RateLimitedRoute};
+use crate::app::protos::messages::{PlayerSession, RefreshToken};
+use crate::app::session::{join_zone, session_ended};
+use crate::app::validation::validate_registration;
+use crate::app_state::{AppState, Claims};
 use crate::error::{AppError, Result};
 use crate::model::player::Player;
 use crate::routes::{
     LoginPlayerRequest, LoginPlayerResponse, LogoutPlayerRequest, LogoutPlayerResponse,
-    PlayersInZoneResponse, RegisterPlayerRequest, RegisterPlayerResponse,
+    PlayersInZoneResponse, RefreshTokenRequest, RefreshTokenResponse, RegisterPlayerRequest,
+    RegisterPlayerResponse,
 };
 use axum::extract::{Path, State};
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use chrono::prelude::Utc;
-use jsonwebtoken::Algorithm::HS512;
-use jsonwebtoken::Header;
-use std::sync::LazyLock;
+use jsonwebtoken::jwk::JwkSet;
 use tower_http::services::ServeDir;
-use tracing::{debug, warn};
+use tracing::warn;
 
-pub const TOKEN_EXPIRATION_OFFSET: i64 = 60 * 60 * 24 * 30; // 30 days
+pub const ACCESS_TOKEN_TTL_SECONDS: i64 = 60 * 15; // 15 minutes
 pub const AUTH_TOKEN_TYPE: &str = "Bearer ";
 
-static KEYS: LazyLock<Keys> = LazyLock::new(|| Keys::new(JWT_AUTH_SECRET.as_bytes()));
-
 pub fn root_router() -> Router<AppState> {
     Router::new()
         .route_service("/", ServeDir::new("public"))
         .route("/register", post(register))
         .route("/login", post(login))
         .route("/logout", post(logout))
+        .route("/token/refresh", post(refresh_token))
+        .route("/.well-known/jwks.json", get(jwks))
         .route("/zone/players", get(players_in_zone))
 }
 
 pub(crate) async fn register(
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Json(new_player): Json<RegisterPlayerRequest>,
 ) -> Result<impl IntoResponse> {
     let AppState {
-        player_middleware, ..
+        player_middleware,
+        rate_limit_middleware,
+        account_middleware,
+        ..
     } = state;
 
-    debug!("Registering player: {:?}", new_player);
+    rate_limit_middleware
+        .check(RateLimitedRoute::Register, &ip)
+        .await?;
 
-    if new_player.username.is_empty() || new_player.password.is_empty() {
-        return Err(AppError::MissedCredentials);
-    }
+    validate_registration(
+        &new_player.username,
+        &new_player.email,
+        &new_player.password,
+    )
+    .map_err(AppError::Validation)?;
 
-    let nickname = new_player.username.clone();
     let new_player = Player {
         nickname: new_player.username,
         email: new_player.email,
@@ -55,8 +68,19 @@ pub(crate) async fn register(
 
     let user = player_middleware
         .register_player_transaction(new_player)
-        .await
-        .map_err(|_| AppError::PlayerCannotRegister(nickname))?;
+        .await?;
+    // The player is registered anyway, the verification mail can be requested again.
+    if let Some(player_id) = user.id {
+        if let Err(e) = account_middleware
+            .send_email_verification(player_id, &user.email)
+            .await
+        {
+            warn!(
+                "Can't send the email verification to {}: {:?}",
+                user.nickname, e
+            );
+        }
+    }
 
     Ok(Json(RegisterPlayerResponse {
         nickname: user.nickname,
@@ -65,11 +89,19 @@ pub(crate) async fn register(
 }
 
 pub(crate) async fn login(
+    ClientIp(ip): ClientIp,
     State(state): State<AppState>,
     Json(payload): Json<LoginPlayerRequest>,
 ) -> Result<Json<LoginPlayerResponse>> {
+    let app_state = state.clone();
     let AppState {
-        player_middleware, ..
+        player_middleware,
+        cache_middleware,
+        battle_middleware,
+        rate_limit_middleware,
+        account_middleware,
+        key_ring,
+        ..
     } = state;
     // Check if the user sent the credentials
     if payload.username.is_empty() || payload.password.is_empty() {
@@ -77,31 +109,94 @@ pub(crate) async fn login(
     }
 
     let nickname = payload.username.clone();
+    // Nicknames are case-insensitive, so are the limits on them.
+    let limited_nickname = nickname.to_lowercase();
+    // Both guessing many passwords from one IP and one password from many IPs are limited.
+    rate_limit_middleware
+        .check(RateLimitedRoute::Login, &ip)
+        .await?;
+    rate_limit_middleware
+        .check(RateLimitedRoute::Login, &limited_nickname)
+        .await?;
+    rate_limit_middleware
+        .check_login_lockout(&limited_nickname)
+        .await?;
 
-    if let Ok(user) = player_middleware.get_player_by_nick(payload.username).await {
-        password_auth::verify_password(payload.password, user.password.as_ref())
-            .map_err(|_| AppError::WrongCredentials(nickname.clone()))?;
+    if let Ok((user, attributes)) = player_middleware
+        .get_full_player_info_by_nick(payload.username)
+        .await
+    {
+        if password_auth::verify_password(payload.password, user.password.as_ref()).is_err() {
+            rate_limit_middleware
+                .register_login_failure(&limited_nickname)
+                .await?;
+            return Err(AppError::WrongCredentials(nickname));
+        }
+        rate_limit_middleware
+            .reset_login_failures(&limited_nickname)
+            .await?;
+        // The stored spelling of the nickname is the one the session and token carry.
+        let nickname = user.nickname.clone();
+        if user.is_banned(Utc::now().naive_utc()) {
+            return Err(AppError::PlayerBanned(nickname));
+        }
+        if account_middleware.require_verified_email && user.email_verified == 0 {
+            return Err(AppError::EmailNotVerified(nickname));
+        }
 
-        let claims = Claims {
-            sub: "alex.syrotenko.official@gmail.com".to_owned(),
-            exp: (Utc::now().timestamp() + TOKEN_EXPIRATION_OFFSET) as usize,
-        };
-        // Create the authorization token
-        let access_token = jsonwebtoken::encode(&Header::new(HS512), &claims, &KEYS.encoding)
-            .map_err(|_| AppError::TokenCreation)?;
+        let player_id = user.id.ok_or(AppError::PlayerNotFound(nickname.clone()))?;
+        let access_token = issue_access_token(&key_ring, player_id, &nickname)?;
+        let refresh_token = random_token();
+        let refresh_family = random_token();
+        cache_middleware
+            .save_refresh_token(
+                &refresh_token,
+                &RefreshToken {
+                    player_id: player_id as i64,
+                    family: refresh_family.clone(),
+                    used: false,
+                },
+            )
+            .await?;
 
-        player_middleware
-            .store_player_session_token(nickname.as_str(), access_token.clone())
+        // Vitals outlive a re-login, a new session starts with the ones of the previous session.
+        // So does a battle still being fought, logging in again is no way out of it.
+        let previous = cache_middleware.get_session(player_id as i64).await?;
+        let link_to_battle = previous
+            .as_ref()
+            .filter(|session| battle_middleware.is_in_live_battle(session))
+            .and_then(|session| session.link_to_battle.clone());
+        cache_middleware
+            .start_session(&PlayerSession {
+                id: player_id as i64,
+                nickname: nickname.clone(),
+                token: access_token.clone(),
+                level: attributes.level as u32,
+                session_started_at: Utc::now().timestamp() as u32,
+                is_in_battle: link_to_battle.is_some(),
+                link_to_battle,
+                health: previous.as_ref().map_or(0, |session| session.health),
+                stamina: previous.as_ref().map_or(0, |session| session.stamina),
+                vitals_updated_at: previous.map_or(0, |session| session.vitals_updated_at),
+                refresh_family,
+            })
             .await?;
+        join_zone(&app_state, player_id, &nickname, user.last_map_location).await?;
 
         // Send the authorized token
         Ok(Json(LoginPlayerResponse {
             access_token,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_TTL_SECONDS,
             nickname,
         }))
     } else {
-        // If the user is not found, return an error
-        Err(AppError::PlayerNotFound(nickname))
+        // An unknown nickname looks the same as a wrong password, so players can't be
+        // enumerated.
+        rate_limit_middleware
+            .register_login_failure(&limited_nickname)
+            .await?;
+        Err(AppError::WrongCredentials(nickname))
     }
 }
 
@@ -109,8 +204,11 @@ pub(crate) async fn logout(
     State(state): State<AppState>,
     Json(payload): Json<LogoutPlayerRequest>,
 ) -> Result<Json<LogoutPlayerResponse>> {
+    let app_state = state.clone();
     let AppState {
-        player_middleware, ..
+        player_middleware,
+        cache_middleware,
+        ..
     } = state;
     // Check if the user sent the credentials
     if payload.access_token.is_empty() || payload.nickname.is_empty() {
@@ -122,15 +220,110 @@ pub(crate) async fn logout(
         access_token,
     } = payload;
 
-    if let Err(_err) = player_middleware
-        .remove_player_session_token(nickname.as_str(), access_token.as_str())
+    // An unknown nickname has no session to end, like a wrong token.
+    let player_id = match player_middleware.get_player_id_by_nick(nickname).await {
+        Ok(player_id) => player_id as i64,
+        Err(AppError::PlayerNotFound(_)) => return Ok(Json(LogoutPlayerResponse { ok: false })),
+        Err(e) => return Err(e),
+    };
+
+    match cache_middleware
+        .end_session(player_id, access_token.as_str())
         .await
     {
-        warn!("Error during player logout: {:?}", _err);
-        return Ok(Json(LogoutPlayerResponse { ok: false }));
+        Ok(true) => {
+            if let Err(e) = session_ended(&app_state, player_id as i32).await {
+                warn!("Can't take player {} out of their zone: {:?}", player_id, e);
+            }
+            Ok(Json(LogoutPlayerResponse { ok: true }))
+        }
+        Ok(false) => Ok(Json(LogoutPlayerResponse { ok: false })),
+        Err(_err) => {
+            warn!("Error during player logout: {:?}", _err);
+            Ok(Json(LogoutPlayerResponse { ok: false }))
+        }
+    }
+}
+
+/// Exchanges a refresh token for a new pair of tokens. Every refresh token is good for one
+/// exchange only, presenting a used one revokes its whole family and the session with it.
+pub(crate) async fn refresh_token(
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<Json<RefreshTokenResponse>> {
+    let app_state = state.clone();
+    let AppState {
+        cache_middleware,
+        rate_limit_middleware,
+        key_ring,
+        ..
+    } = state;
+    rate_limit_middleware
+        .check(RateLimitedRoute::TokenRefresh, &ip)
+        .await?;
+
+    let refresh_token = cache_middleware
+        .get_refresh_token(&payload.refresh_token)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+    if refresh_token.used
+        || !cache_middleware
+            .consume_refresh_token(&payload.refresh_token, &refresh_token)
+            .await?
+    {
+        warn!(
+            "Refresh token reuse detected for player {}, revoking the token family",
+            refresh_token.player_id
+        );
+        if cache_middleware
+            .revoke_refresh_family(refresh_token.player_id, &refresh_token.family)
+            .await?
+        {
+            session_ended(&app_state, refresh_token.player_id as i32).await?;
+        }
+        return Err(AppError::InvalidToken);
     }
 
-    Ok(Json(LogoutPlayerResponse { ok: true }))
+    let session = cache_middleware
+        .get_session(refresh_token.player_id)
+        .await?
+        .filter(|session| session.refresh_family == refresh_token.family)
+        .ok_or(AppError::InvalidToken)?;
+
+    let access_token = issue_access_token(&key_ring, session.id as i32, &session.nickname)?;
+    let (player_id, family) = (refresh_token.player_id, refresh_token.family.clone());
+    let new_refresh_token = random_token();
+    cache_middleware
+        .save_refresh_token(
+            &new_refresh_token,
+            &RefreshToken {
+                used: false,
+                ..refresh_token
+            },
+        )
+        .await?;
+    cache_middleware
+        .update_session(player_id, |session| {
+            if session.refresh_family != family {
+                return false;
+            }
+            session.token = access_token.clone();
+            true
+        })
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+
+    Ok(Json(RefreshTokenResponse {
+        access_token,
+        refresh_token: new_refresh_token,
+        expires_in: ACCESS_TOKEN_TTL_SECONDS,
+    }))
+}
+
+/// Public keys the access tokens can be verified with.
+pub(crate) async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.key_ring.jwks())
 }
 
 pub(crate) async fn players_in_zone(
@@ -144,3 +337,13 @@ pub(crate) async fn players_in_zone(
 
     Ok(Json(PlayersInZoneResponse { list }))
 }
+
+fn issue_access_token(key_ring: &KeyRing, player_id: i32, nickname: &str) -> Result<String> {
+    let claims = Claims {
+        sub: player_id.to_string(),
+        nickname: nickname.to_owned(),
+        exp: (Utc::now().timestamp() + ACCESS_TOKEN_TTL_SECONDS) as usize,
+    };
+
+    key_ring.sign(&claims)
+}
@@ -0,0 +1,77 @@
+use crate::app::protos::messages::Travel;
+use crate::app::travel::{current_location, start_travel};
+use crate::app_state::{AppState, AuthenticatedPlayer};
+use crate::error::{AppError, Result};
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct TravelRequest {
+    pub location_id: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TravelResponse {
+    pub from_location: i32,
+    pub to_location: i32,
+    /// Unix timestamps in seconds.
+    pub started_at: i64,
+    pub arrives_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TravelStatusResponse {
+    /// Location the player is at, or travels from.
+    pub location_id: i32,
+    pub travel: Option<TravelResponse>,
+}
+
+impl From<Travel> for TravelResponse {
+    fn from(travel: Travel) -> Self {
+        TravelResponse {
+            from_location: travel.from_location,
+            to_location: travel.to_location,
+            started_at: travel.started_at,
+            arrives_at: travel.arrives_at,
+        }
+    }
+}
+
+pub fn travel_router() -> Router<AppState> {
+    Router::new().route("/travel", get(travel_status).post(travel))
+}
+
+pub(crate) async fn travel(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+    Json(payload): Json<TravelRequest>,
+) -> Result<Json<TravelResponse>> {
+    let travel = start_travel(&state, player.player_id, payload.location_id).await?;
+
+    Ok(Json(travel.into()))
+}
+
+pub(crate) async fn travel_status(
+    player: AuthenticatedPlayer,
+    State(state): State<AppState>,
+) -> Result<Json<TravelStatusResponse>> {
+    let AppState {
+        player_middleware,
+        cache_middleware,
+        ..
+    } = state;
+
+    let last_map_location = player_middleware
+        .get_player_by_id(player.player_id)
+        .await?
+        .ok_or(AppError::InvalidToken)?
+        .last_map_location;
+    let travel = cache_middleware.get_travel(player.player_id as i64).await?;
+
+    Ok(Json(TravelStatusResponse {
+        location_id: current_location(last_map_location),
+        travel: travel.map(Into::into),
+    }))
+}
@@ -24,10 +24,12 @@ diesel::table! {
 }
 
 diesel::table! {
-    battle_participant (battle_id) {
+    battle_participant (id) {
+        id -> Integer,
         battle_id -> Integer,
-        player_id -> Integer,
-        bot_id -> Integer,
+        player_id -> Nullable<Integer>,
+        bot_id -> Nullable<Integer>,
+        faction -> Integer,
         outcome_damage -> Integer,
         income_damage -> Integer,
         gained_exp -> Integer,
@@ -67,6 +69,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    guild_invite (id) {
+        id -> Integer,
+        guild_id -> Integer,
+        player_id -> Integer,
+        invited_by -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    guild_member (player_id) {
+        player_id -> Integer,
+        guild_id -> Integer,
+        role -> Integer,
+        joined_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     item (id) {
         id -> Integer,
@@ -92,6 +113,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    moderation_audit (id) {
+        id -> Integer,
+        admin_id -> Integer,
+        target_id -> Integer,
+        action -> Text,
+        reason -> Nullable<Text>,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     non_battle_consumable_item (id) {
         id -> Integer,
@@ -109,6 +142,12 @@ diesel::table! {
         last_login_time -> Nullable<Timestamp>,
         guild_id -> Nullable<Integer>,
         banned -> Integer,
+        email_verified -> Integer,
+        role -> Integer,
+        ban_reason -> Nullable<Text>,
+        banned_until -> Nullable<Timestamp>,
+        muted_until -> Nullable<Timestamp>,
+        last_map_location -> Nullable<Integer>,
     }
 }
 
@@ -125,6 +164,9 @@ diesel::table! {
         experience -> Integer,
         level -> Integer,
         valor -> Integer,
+        up -> Integer,
+        attribute_points -> Integer,
+        money -> Integer,
     }
 }
 
@@ -206,12 +248,17 @@ diesel::joinable!(battle_consumable_item -> item (item_id));
 diesel::joinable!(battle_log -> battle (id));
 diesel::joinable!(battle_participant -> battle (battle_id));
 diesel::joinable!(battle_participant -> bot (bot_id));
+diesel::joinable!(battle_participant -> factions (faction));
 diesel::joinable!(battle_participant -> player (player_id));
 diesel::joinable!(bot -> weapon_item (weapon_id));
 diesel::joinable!(gear_item -> item (item_id));
+diesel::joinable!(guild_invite -> guild (guild_id));
+diesel::joinable!(guild_member -> guild (guild_id));
+diesel::joinable!(guild_member -> player (player_id));
 diesel::joinable!(item -> player_class (class_req));
 diesel::joinable!(non_battle_consumable_item -> item (item_id));
 diesel::joinable!(player -> guild (guild_id));
+diesel::joinable!(player -> map_location (last_map_location));
 diesel::joinable!(player_attributes -> player (player_id));
 diesel::joinable!(player_attributes -> player_class (class_id));
 diesel::joinable!(player_attributes -> player_rank_table (rank_id));
@@ -228,8 +275,11 @@ diesel::allow_tables_to_appear_in_same_query!(
     factions,
     gear_item,
     guild,
+    guild_invite,
+    guild_member,
     item,
     map_location,
+    moderation_audit,
     non_battle_consumable_item,
     player,
     player_attributes,
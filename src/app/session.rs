@@ -0,0 +1,64 @@
+use crate::app::protos::messages::zone_event::Kind;
+use crate::app::travel::current_location;
+use crate::app_state::AppState;
+use crate::error::Result;
+
+/// Puts the player who has logged in into the zone they are at and tells the zone about it.
+pub async fn join_zone(
+    state: &AppState,
+    player_id: i32,
+    nickname: &str,
+    last_map_location: Option<i32>,
+) -> Result<()> {
+    let zone_id = current_location(last_map_location) as i64;
+    if state
+        .cache_middleware
+        .enter_zone(zone_id, player_id as i64)
+        .await?
+    {
+        state.gateway_middleware.publish_zone_event(
+            Kind::Join,
+            zone_id,
+            player_id as i64,
+            nickname,
+        );
+    }
+
+    Ok(())
+}
+
+/// Ends the session of the player whatever token it was started with, see [`session_ended`].
+pub async fn end_session(state: &AppState, player_id: i32) -> Result<()> {
+    state
+        .cache_middleware
+        .drop_session(player_id as i64)
+        .await?;
+
+    session_ended(state, player_id).await
+}
+
+/// Closes the WebSocket connections of the player whose session is over, takes them out of
+/// their zone and tells the zone about it.
+pub async fn session_ended(state: &AppState, player_id: i32) -> Result<()> {
+    state
+        .gateway_middleware
+        .publish_session_ended(player_id as i64);
+    let Some(player) = state.player_middleware.get_player_by_id(player_id).await? else {
+        return Ok(());
+    };
+    let zone_id = current_location(player.last_map_location) as i64;
+    if state
+        .cache_middleware
+        .leave_zone(zone_id, player_id as i64)
+        .await?
+    {
+        state.gateway_middleware.publish_zone_event(
+            Kind::Leave,
+            zone_id,
+            player_id as i64,
+            &player.nickname,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use crate::app::battle::{Weapon, BARE_HANDS};
+use crate::app::grid::Mobility;
+use crate::app::progression::MAX_SPEC_PROGRESS;
+use crate::model::item::InventoryEntry;
+use crate::model::player::{PlayerAttributes, PlayerClassProgress};
+
+pub const BASE_HEALTH: i32 = 50;
+pub const HEALTH_PER_PHYSIQUE: i32 = 10;
+pub const HEALTH_PER_LEVEL: i32 = 5;
+pub const BASE_STAMINA: i32 = 30;
+pub const STAMINA_PER_DEXTERITY: i32 = 5;
+pub const STAMINA_PER_PHYSIQUE: i32 = 3;
+pub const BASE_ACTION_POINTS: i32 = 6;
+/// Every 5 points of dexterity give one more action point.
+pub const DEXTERITY_PER_ACTION_POINT: i32 = 5;
+/// Every 5 points of strength give one more point of damage.
+pub const STRENGTH_PER_DAMAGE: i32 = 5;
+pub const MAX_CHANCE: f32 = 0.5;
+pub const BASE_CARRY_WEIGHT: f32 = 20.0;
+pub const CARRY_WEIGHT_PER_STRENGTH: f32 = 4.0;
+/// Action points lost while carrying more than the capacity.
+pub const OVERLOAD_ACTION_POINTS_PENALTY: i32 = 2;
+
+/// Bonuses of a fully mastered spec, smaller progress gives a proportional part of them.
+pub const MAX_SPEC_DAMAGE_BONUS: f32 = 3.0;
+pub const MAX_SPEC_HEALTH_BONUS: f32 = 30.0;
+pub const MAX_SPEC_STAMINA_BONUS: f32 = 30.0;
+pub const MAX_SPEC_ACTION_POINTS_BONUS: f32 = 1.0;
+pub const MAX_SPEC_CHANCE_BONUS: f32 = 0.05;
+
+pub const HEALTH_REGEN_PER_MINUTE: i32 = 5;
+pub const STAMINA_REGEN_PER_MINUTE: i32 = 10;
+
+/// Flat bonuses of a class on top of the attribute based values.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClassModifiers {
+    pub health: i32,
+    pub stamina: i32,
+    pub action_points: i32,
+    pub damage: i32,
+    pub dodge_chance: f32,
+    pub crit_chance: f32,
+}
+
+/// What a spec of a class improves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecBonus {
+    Damage,
+    Health,
+    Stamina,
+    ActionPoints,
+    Dodge,
+    Crit,
+}
+
+/// Bonuses of the three specs of a class, in the order of the `player_class` spec columns.
+pub fn spec_bonuses(class_id: i32) -> Option<[SpecBonus; 3]> {
+    use SpecBonus::*;
+
+    match class_id {
+        // One-handed weapons, Two-handed weapons, Shield
+        1 => Some([Damage, Damage, Health]),
+        // Bow, Crossbow, Dexterity
+        2 => Some([Damage, Damage, Dodge]),
+        // Therapy, Surgery, Buffing
+        3 => Some([Health, Stamina, ActionPoints]),
+        // Stealth, Traps, Throwing Weapons
+        4 => Some([Dodge, Crit, Damage]),
+        // Horseriding, Crushing Weapons, Impaling Weapons
+        5 => Some([ActionPoints, Damage, Crit]),
+        _ => None,
+    }
+}
+
+/// Class bonuses together with the bonuses of its specs, if the progress belongs to the class.
+pub fn class_modifiers(
+    class_id: i32,
+    class_progress: Option<&PlayerClassProgress>,
+) -> ClassModifiers {
+    let mut modifiers = base_class_modifiers(class_id);
+    let (Some(bonuses), Some(class_progress)) = (spec_bonuses(class_id), class_progress) else {
+        return modifiers;
+    };
+    if class_progress.class_id != class_id {
+        return modifiers;
+    }
+
+    let progress = [
+        class_progress.first_spec_progress,
+        class_progress.second_spec_progress,
+        class_progress.third_spec_progress.unwrap_or(0.0),
+    ];
+    for (bonus, progress) in bonuses.into_iter().zip(progress) {
+        let mastery = (progress / MAX_SPEC_PROGRESS).clamp(0.0, 1.0);
+        match bonus {
+            SpecBonus::Damage => modifiers.damage += (MAX_SPEC_DAMAGE_BONUS * mastery) as i32,
+            SpecBonus::Health => modifiers.health += (MAX_SPEC_HEALTH_BONUS * mastery) as i32,
+            SpecBonus::Stamina => modifiers.stamina += (MAX_SPEC_STAMINA_BONUS * mastery) as i32,
+            SpecBonus::ActionPoints => {
+                modifiers.action_points += (MAX_SPEC_ACTION_POINTS_BONUS * mastery) as i32
+            }
+            SpecBonus::Dodge => modifiers.dodge_chance += MAX_SPEC_CHANCE_BONUS * mastery,
+            SpecBonus::Crit => modifiers.crit_chance += MAX_SPEC_CHANCE_BONUS * mastery,
+        }
+    }
+
+    modifiers
+}
+
+/// The ids match the `player_class` table.
+fn base_class_modifiers(class_id: i32) -> ClassModifiers {
+    match class_id {
+        // Warrior
+        1 => ClassModifiers {
+            health: 30,
+            ..ClassModifiers::default()
+        },
+        // Archer
+        2 => ClassModifiers {
+            dodge_chance: 0.05,
+            ..ClassModifiers::default()
+        },
+        // Healer
+        3 => ClassModifiers {
+            stamina: 30,
+            ..ClassModifiers::default()
+        },
+        // Rogue
+        4 => ClassModifiers {
+            crit_chance: 0.05,
+            ..ClassModifiers::default()
+        },
+        // Lancer
+        5 => ClassModifiers {
+            action_points: 1,
+            ..ClassModifiers::default()
+        },
+        _ => ClassModifiers::default(),
+    }
+}
+
+/// Lancers fight on horseback, everyone else on foot. The ids match the `player_class` table.
+pub fn class_mobility(class_id: i32) -> Mobility {
+    match class_id {
+        5 => Mobility::Mounted,
+        _ => Mobility::Foot,
+    }
+}
+
+/// Stats derived from the attributes, class, level and the inventory of a player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CombatStats {
+    pub max_health: i32,
+    pub max_stamina: i32,
+    pub action_points: i32,
+    pub damage_bonus: i32,
+    pub dodge_chance: f32,
+    pub crit_chance: f32,
+    pub carry_weight: f32,
+    pub carried_weight: f32,
+    pub weapon: Weapon,
+    pub mobility: Mobility,
+}
+
+impl CombatStats {
+    /// `inventory` is the whole inventory of the player: all items count for the carried
+    /// weight, the equipped weapon defines the attack.
+    pub fn derive(
+        attributes: &PlayerAttributes,
+        class_progress: Option<&PlayerClassProgress>,
+        inventory: &[InventoryEntry],
+    ) -> Self {
+        let class = class_modifiers(attributes.class_id, class_progress);
+
+        let carry_weight =
+            BASE_CARRY_WEIGHT + CARRY_WEIGHT_PER_STRENGTH * attributes.strength as f32;
+        let carried_weight = inventory
+            .iter()
+            .map(|entry| entry.slot.weight * entry.slot.amount as f32)
+            .sum::<f32>();
+        let overload_penalty = if carried_weight > carry_weight {
+            OVERLOAD_ACTION_POINTS_PENALTY
+        } else {
+            0
+        };
+
+        let weapon = inventory
+            .iter()
+            .filter(|entry| entry.slot.equipped)
+            .find_map(|entry| entry.weapon.as_ref())
+            .map(Weapon::from)
+            .unwrap_or(BARE_HANDS);
+
+        CombatStats {
+            max_health: BASE_HEALTH
+                + HEALTH_PER_PHYSIQUE * attributes.physique
+                + HEALTH_PER_LEVEL * attributes.level
+                + class.health,
+            max_stamina: BASE_STAMINA
+                + STAMINA_PER_DEXTERITY * attributes.dexterity
+                + STAMINA_PER_PHYSIQUE * attributes.physique
+                + class.stamina,
+            action_points: (BASE_ACTION_POINTS
+                + attributes.dexterity / DEXTERITY_PER_ACTION_POINT
+                + class.action_points
+                - overload_penalty)
+                .max(1),
+            damage_bonus: attributes.strength / STRENGTH_PER_DAMAGE + class.damage,
+            dodge_chance: (0.01 * attributes.dexterity as f32
+                + 0.005 * attributes.luck as f32
+                + class.dodge_chance)
+                .min(MAX_CHANCE),
+            crit_chance: (0.01 * attributes.luck as f32
+                + 0.005 * attributes.dexterity as f32
+                + class.crit_chance)
+                .min(MAX_CHANCE),
+            carry_weight,
+            carried_weight,
+            weapon,
+            mobility: class_mobility(attributes.class_id),
+        }
+    }
+}
+
+/// Current health and stamina, kept in the player session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vitals {
+    pub health: i32,
+    pub stamina: i32,
+    /// Unix timestamp in seconds of the last change.
+    pub updated_at: i64,
+}
+
+impl Vitals {
+    pub fn full(stats: &CombatStats, now: i64) -> Self {
+        Vitals {
+            health: stats.max_health,
+            stamina: stats.max_stamina,
+            updated_at: now,
+        }
+    }
+
+    /// Regenerates health and stamina for the whole minutes passed since the last change,
+    /// never above the maximum.
+    pub fn regenerate(&self, stats: &CombatStats, now: i64) -> Self {
+        let minutes = ((now - self.updated_at) / 60).max(0);
+        if minutes == 0 {
+            return Vitals {
+                health: self.health.min(stats.max_health),
+                stamina: self.stamina.min(stats.max_stamina),
+                updated_at: self.updated_at,
+            };
+        }
+        let minutes = minutes.min(i32::MAX as i64) as i32;
+
+        Vitals {
+            health: self
+                .health
+                .saturating_add(HEALTH_REGEN_PER_MINUTE.saturating_mul(minutes))
+                .min(stats.max_health),
+            stamina: self
+                .stamina
+                .saturating_add(STAMINA_REGEN_PER_MINUTE.saturating_mul(minutes))
+                .min(stats.max_stamina),
+            // Keep the remainder, so frequent reads don't eat partial minutes.
+            updated_at: self.updated_at + minutes as i64 * 60,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::battle::BARE_HANDS;
+    use crate::app::stats::{CombatStats, Vitals};
+    use crate::model::item::{InventoryEntry, Item, PlayerInventory, WeaponItem};
+    use crate::model::player::{PlayerAttributes, PlayerClassProgress};
+    use crate::model::DefaultModel;
+
+    fn entry(id: i32, weight: f32, equipped: bool, weapon: Option<WeaponItem>) -> InventoryEntry {
+        InventoryEntry {
+            slot: PlayerInventory {
+                id,
+                player_id: 1,
+                item_id: id,
+                amount: 1,
+                weight,
+                equipped,
+            },
+            item: Item {
+                id,
+                name: format!("item {id}"),
+                level_req: 0,
+                strength_req: 0,
+                dexterity_req: 0,
+                physique_req: 0,
+                intellect_req: 0,
+                valor_req: 0,
+                class_req: None,
+            },
+            weapon,
+            gear: None,
+        }
+    }
+
+    #[test]
+    fn when_default_attributes_then_base_stats_derived() {
+        let stats = CombatStats::derive(&PlayerAttributes::default_model(1), None, &[]);
+
+        assert_eq!(stats.max_health, 85);
+        assert_eq!(stats.max_stamina, 54);
+        assert_eq!(stats.action_points, 6);
+        assert_eq!(stats.carry_weight, 32.0);
+        assert_eq!(stats.weapon, BARE_HANDS);
+    }
+
+    #[test]
+    fn when_overloaded_then_action_points_reduced_and_weapon_taken_from_equipped() {
+        let attributes = PlayerAttributes {
+            class_id: 5,
+            dexterity: 10,
+            ..PlayerAttributes::default_model(1)
+        };
+        let sword = WeaponItem {
+            id: 1,
+            item_id: 2,
+            action_points_to_use: 3,
+            basic_damage: 7,
+            range: 1,
+        };
+        let inventory = [
+            entry(1, 30.0, false, None),
+            entry(2, 5.0, true, Some(sword)),
+        ];
+
+        let stats = CombatStats::derive(&attributes, None, &inventory);
+
+        assert_eq!(stats.carried_weight, 35.0);
+        assert_eq!(stats.action_points, 7);
+        assert_eq!(stats.weapon.basic_damage, 7);
+    }
+
+    #[test]
+    fn when_specs_progressed_then_bonuses_added_proportionally() {
+        let attributes = PlayerAttributes {
+            class_id: 1,
+            ..PlayerAttributes::default_model(1)
+        };
+        let class_progress = PlayerClassProgress {
+            class_id: 1,
+            first_spec_progress: 100.0,
+            second_spec_progress: 50.0,
+            third_spec_progress: Some(50.0),
+            ..PlayerClassProgress::default()
+        };
+
+        let stats = CombatStats::derive(&attributes, Some(&class_progress), &[]);
+
+        assert_eq!(stats.damage_bonus, 4);
+        assert_eq!(stats.max_health, 85 + 30 + 15);
+    }
+
+    #[test]
+    fn when_time_passed_then_vitals_regenerated_up_to_max() {
+        let stats = CombatStats::derive(&PlayerAttributes::default_model(1), None, &[]);
+        let vitals = Vitals {
+            health: 10,
+            stamina: 50,
+            updated_at: 0,
+        };
+
+        let regenerated = vitals.regenerate(&stats, 150);
+
+        assert_eq!(regenerated.health, 20);
+        assert_eq!(regenerated.stamina, 54);
+        assert_eq!(regenerated.updated_at, 120);
+    }
+}
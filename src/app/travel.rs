@@ -0,0 +1,288 @@
+use crate::app::encounter::roll_encounter_for;
+use crate::app::protos::messages::Travel;
+use crate::app_state::AppState;
+use crate::error::{AppError, Result};
+use crate::model::r#static::MapLocation;
+use chrono::Utc;
+use std::time::Duration;
+use tracing::error;
+
+/// Location of the players who have never travelled.
+pub const START_MAP_LOCATION: i32 = 1;
+/// Seconds to cover one unit of the map distance at the movement acceleration of 1.
+pub const TRAVEL_SECS_PER_DISTANCE: f32 = 10.0;
+pub const MIN_TRAVEL_SECS: u64 = 5;
+pub const MAX_TRAVEL_SECS: u64 = 60 * 10;
+
+/// Seconds it takes to get from one location to the other. The movement acceleration of both
+/// locations counts, `None` if either of them can't be crossed.
+pub fn travel_secs(from: &MapLocation, to: &MapLocation) -> Option<u64> {
+    if from.movement_accel <= 0.0 || to.movement_accel <= 0.0 {
+        return None;
+    }
+    let distance = from.location.abs_diff(to.location).max(1) as f32;
+    let speed = (from.movement_accel + to.movement_accel) / 2.0;
+    let secs = (distance * TRAVEL_SECS_PER_DISTANCE / speed).ceil();
+    if !secs.is_finite() {
+        return None;
+    }
+
+    Some((secs as u64).clamp(MIN_TRAVEL_SECS, MAX_TRAVEL_SECS))
+}
+
+/// Locations the route passes by, from the origin on, with the seconds it takes to get to each
+/// of them. These are the locations lying between the origin and the destination on the map.
+pub fn waypoints(
+    origin: &MapLocation,
+    destination: &MapLocation,
+    locations: &[MapLocation],
+    secs: u64,
+) -> Vec<(MapLocation, u64)> {
+    let (low, high) = if origin.location < destination.location {
+        (origin.location, destination.location)
+    } else {
+        (destination.location, origin.location)
+    };
+    let route_distance = origin.location.abs_diff(destination.location) as u64;
+
+    let mut waypoints: Vec<(MapLocation, u64)> = locations
+        .iter()
+        .filter(|location| low < location.location && location.location < high)
+        .map(|location| {
+            let distance = origin.location.abs_diff(location.location) as u64;
+            (location.clone(), secs * distance / route_distance)
+        })
+        .collect();
+    waypoints.sort_by_key(|(location, secs)| (*secs, location.id));
+
+    waypoints
+}
+
+/// Location the player is at, the starting one if they've never travelled.
+pub fn current_location(last_map_location: Option<i32>) -> i32 {
+    last_map_location.unwrap_or(START_MAP_LOCATION)
+}
+
+/// Validates the destination and sends the player on the way. The travel finishes on its own
+/// once the travel time passes, a player travels one route at a time and not while in battle.
+/// Bots may attack the player at every location on the way, which ends the travel there.
+pub async fn start_travel(state: &AppState, player_id: i32, destination_id: i32) -> Result<Travel> {
+    let player = state
+        .player_middleware
+        .get_player_by_id(player_id)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+    if state
+        .cache_middleware
+        .get_session(player_id as i64)
+        .await?
+        .is_some_and(|session| state.battle_middleware.is_in_live_battle(&session))
+    {
+        return Err(AppError::TravelRejected(
+            "the player is in a battle".to_owned(),
+        ));
+    }
+
+    let origin_id = current_location(player.last_map_location);
+    if origin_id == destination_id {
+        return Err(AppError::TravelRejected(
+            "the player is at the destination already".to_owned(),
+        ));
+    }
+    let origin = map_location(state, origin_id).await?;
+    let destination = map_location(state, destination_id).await?;
+    let level = state
+        .player_middleware
+        .get_attributes(player_id)
+        .await?
+        .level;
+    if level < destination.location_difficulty {
+        return Err(AppError::TravelRejected(format!(
+            "{} requires level {}",
+            destination.name, destination.location_difficulty
+        )));
+    }
+    let secs = travel_secs(&origin, &destination).ok_or_else(|| {
+        AppError::TravelRejected(format!("there is no way to {}", destination.name))
+    })?;
+
+    let started_at = Utc::now().timestamp();
+    let travel = Travel {
+        player_id: player_id as i64,
+        nickname: player.nickname,
+        from_location: origin_id,
+        to_location: destination_id,
+        started_at,
+        arrives_at: started_at + secs as i64,
+    };
+    if !state.cache_middleware.start_travel(&travel).await? {
+        return Err(AppError::TravelRejected(
+            "the player is travelling already".to_owned(),
+        ));
+    }
+
+    let waypoints = waypoints(
+        &origin,
+        &destination,
+        &state.static_table_middleware.get_map_locations().await?,
+        secs,
+    );
+    let travel_state = state.clone();
+    let journey = travel.clone();
+    tokio::spawn(async move {
+        if let Err(e) = travel_route(&travel_state, journey, waypoints, secs).await {
+            error!("Can't finish the travel: {:?}", e);
+        }
+    });
+
+    Ok(travel)
+}
+
+/// Walks the player along the route, rolling encounters on the way and on arrival.
+async fn travel_route(
+    state: &AppState,
+    travel: Travel,
+    waypoints: Vec<(MapLocation, u64)>,
+    secs: u64,
+) -> Result<()> {
+    let player_id = travel.player_id as i32;
+    let mut elapsed = 0;
+    for (waypoint, reached_at) in waypoints {
+        tokio::time::sleep(Duration::from_secs(reached_at - elapsed)).await;
+        elapsed = reached_at;
+
+        match roll_encounter_for(state, player_id, waypoint.id).await {
+            Ok(Some(_)) => {
+                // The player is stopped where the bots attacked.
+                let interrupted = Travel {
+                    to_location: waypoint.id,
+                    arrives_at: Utc::now().timestamp(),
+                    ..travel
+                };
+                return finish_travel(state, interrupted).await;
+            }
+            Ok(None) => {}
+            Err(e) => error!(
+                "Can't roll an encounter for player {player_id} at {}: {:?}",
+                waypoint.name, e
+            ),
+        }
+    }
+
+    tokio::time::sleep(Duration::from_secs(secs - elapsed)).await;
+    let destination_id = travel.to_location;
+    finish_travel(state, travel).await?;
+    roll_encounter_for(state, player_id, destination_id).await?;
+
+    Ok(())
+}
+
+/// Moves the player to the destination zone and keeps the destination as the player location.
+pub async fn finish_travel(state: &AppState, travel: Travel) -> Result<()> {
+    state
+        .player_middleware
+        .set_last_map_location(travel.player_id as i32, travel.to_location)
+        .await?;
+    state
+        .cache_middleware
+        .move_player_to_zone(
+            travel.from_location as i64,
+            travel.to_location as i64,
+            travel.player_id,
+        )
+        .await?;
+    state
+        .cache_middleware
+        .finish_travel(travel.player_id)
+        .await?;
+
+    state.gateway_middleware.publish_zone_move(
+        travel.from_location as i64,
+        travel.to_location as i64,
+        travel.player_id,
+        &travel.nickname,
+    );
+    state.gateway_middleware.publish_travel_finished(travel);
+
+    Ok(())
+}
+
+async fn map_location(state: &AppState, location_id: i32) -> Result<MapLocation> {
+    state
+        .static_table_middleware
+        .get_map_location(location_id)
+        .await?
+        .ok_or(AppError::MapLocationNotFound(location_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::travel::{travel_secs, waypoints, MAX_TRAVEL_SECS, MIN_TRAVEL_SECS};
+    use crate::model::r#static::MapLocation;
+
+    fn location(location: i32, movement_accel: f32) -> MapLocation {
+        MapLocation {
+            id: location,
+            name: format!("Location {location}"),
+            location,
+            location_difficulty: 1,
+            movement_accel,
+            aggression_prob: 0.0,
+        }
+    }
+
+    #[test]
+    fn when_location_farther_or_slower_then_travel_takes_longer() {
+        let near = travel_secs(&location(1, 1.0), &location(3, 1.0)).unwrap();
+        let far = travel_secs(&location(1, 1.0), &location(9, 1.0)).unwrap();
+        let swamp = travel_secs(&location(1, 1.0), &location(3, 0.5)).unwrap();
+
+        assert_eq!(near, 20);
+        assert!(far > near);
+        assert!(swamp > near);
+    }
+
+    #[test]
+    fn when_travel_extreme_then_time_is_bounded() {
+        assert_eq!(
+            travel_secs(&location(1, 100.0), &location(2, 100.0)),
+            Some(MIN_TRAVEL_SECS)
+        );
+        assert_eq!(
+            travel_secs(&location(1, 0.01), &location(1000, 0.01)),
+            Some(MAX_TRAVEL_SECS)
+        );
+    }
+
+    #[test]
+    fn when_travelling_then_route_passes_by_the_locations_in_between() {
+        let locations: Vec<MapLocation> = [1, 3, 5, 7, 9]
+            .into_iter()
+            .map(|l| location(l, 1.0))
+            .collect();
+
+        let there = waypoints(&locations[0], &locations[3], &locations, 60);
+        let back = waypoints(&locations[3], &locations[0], &locations, 60);
+
+        assert_eq!(
+            there
+                .iter()
+                .map(|(location, secs)| (location.id, *secs))
+                .collect::<Vec<_>>(),
+            vec![(3, 20), (5, 40)]
+        );
+        assert_eq!(
+            back.iter()
+                .map(|(location, secs)| (location.id, *secs))
+                .collect::<Vec<_>>(),
+            vec![(5, 20), (3, 40)]
+        );
+        assert!(waypoints(&locations[0], &locations[1], &locations, 20).is_empty());
+    }
+
+    #[test]
+    fn when_location_impassable_then_no_travel() {
+        assert_eq!(travel_secs(&location(1, 1.0), &location(2, 0.0)), None);
+        assert_eq!(travel_secs(&location(1, -1.0), &location(2, 1.0)), None);
+    }
+}
@@ -0,0 +1,198 @@
+use serde::Serialize;
+
+pub const NICKNAME_MIN_LENGTH: usize = 3;
+pub const NICKNAME_MAX_LENGTH: usize = 16;
+pub const EMAIL_MAX_LENGTH: usize = 254;
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+// A password has to mix at least this many of lowercase, uppercase, digits and other symbols.
+pub const PASSWORD_MIN_CHARACTER_CLASSES: usize = 2;
+
+/// Nicknames players could use to pass themselves off as the staff, compared case-insensitively.
+const RESERVED_NICKNAMES: [&str; 10] = [
+    "admin",
+    "administrator",
+    "moderator",
+    "mod",
+    "gm",
+    "gamemaster",
+    "system",
+    "server",
+    "root",
+    "support",
+];
+
+/// A rejected request field with a human-readable reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks every field of a registration, reporting all the problems at once.
+pub fn validate_registration(
+    nickname: &str,
+    email: &str,
+    password: &str,
+) -> Result<(), Vec<FieldError>> {
+    let errors: Vec<FieldError> = [
+        validate_nickname(nickname),
+        validate_email(email),
+        validate_password(password, nickname),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn validate_nickname(nickname: &str) -> Result<(), FieldError> {
+    let error = |message: String| Err(FieldError::new("nickname", message));
+    let length = nickname.chars().count();
+
+    if !(NICKNAME_MIN_LENGTH..=NICKNAME_MAX_LENGTH).contains(&length) {
+        return error(format!(
+            "must be {NICKNAME_MIN_LENGTH} to {NICKNAME_MAX_LENGTH} characters long"
+        ));
+    }
+    if !nickname.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return error("must start with a latin letter".to_owned());
+    }
+    if !nickname
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return error("may contain latin letters, digits, '_' and '-' only".to_owned());
+    }
+    if RESERVED_NICKNAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(nickname))
+    {
+        return error("is reserved".to_owned());
+    }
+
+    Ok(())
+}
+
+/// A pragmatic check of `local@domain.tld`, only the verification mail proves the address.
+pub fn validate_email(email: &str) -> Result<(), FieldError> {
+    let error = |message: &str| Err(FieldError::new("email", message));
+
+    if email.len() > EMAIL_MAX_LENGTH {
+        return error("is too long");
+    }
+    let Some((local, domain)) = email.split_once('@') else {
+        return error("must be an email address");
+    };
+    let domain_labels_ok = domain.split('.').count() >= 2
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if local.is_empty() || local.chars().any(|c| c.is_whitespace() || c == '@') || !domain_labels_ok
+    {
+        return error("must be an email address");
+    }
+
+    Ok(())
+}
+
+pub fn validate_password(password: &str, nickname: &str) -> Result<(), FieldError> {
+    let error = |message: String| Err(FieldError::new("password", message));
+    let length = password.chars().count();
+
+    if !(PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH).contains(&length) {
+        return error(format!(
+            "must be {PASSWORD_MIN_LENGTH} to {PASSWORD_MAX_LENGTH} characters long"
+        ));
+    }
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .into_iter()
+    .filter(|present| *present)
+    .count();
+    if classes < PASSWORD_MIN_CHARACTER_CLASSES {
+        return error(
+            "must mix lowercase and uppercase letters, digits or other symbols".to_owned(),
+        );
+    }
+    if !nickname.is_empty() && password.to_lowercase().contains(&nickname.to_lowercase()) {
+        return error("must not contain the nickname".to_owned());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::validation::{
+        validate_email, validate_nickname, validate_password, validate_registration,
+    };
+
+    #[test]
+    fn when_registration_valid_then_it_passes() {
+        assert_eq!(
+            validate_registration("Sir_Lancelot-2", "lancelot@camelot.co.uk", "Holy grail 1"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn when_fields_invalid_then_every_one_is_reported() {
+        let errors = validate_registration("x", "not an email", "short").unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|error| error.field).collect();
+
+        assert_eq!(fields, ["nickname", "email", "password"]);
+    }
+
+    #[test]
+    fn when_nickname_breaks_rules_then_rejected() {
+        assert!(validate_nickname("ab").is_err());
+        assert!(validate_nickname("a_very_long_nickname").is_err());
+        assert!(validate_nickname("1player").is_err());
+        assert!(validate_nickname("play er").is_err());
+        assert!(validate_nickname("plаyer").is_err()); // cyrillic 'а'
+        assert!(validate_nickname("Admin").is_err());
+        assert!(validate_nickname("admin_2").is_ok());
+    }
+
+    #[test]
+    fn when_email_malformed_then_rejected() {
+        assert!(validate_email("player@example.com").is_ok());
+        assert!(validate_email("player@localhost").is_err());
+        assert!(validate_email("@example.com").is_err());
+        assert!(validate_email("player@@example.com").is_err());
+        assert!(validate_email("pla yer@example.com").is_err());
+        assert!(validate_email("player@-example.com").is_err());
+        assert!(validate_email("player@example..com").is_err());
+    }
+
+    #[test]
+    fn when_password_weak_then_rejected() {
+        assert!(validate_password("Sh0rt", "player").is_err());
+        assert!(validate_password("onlylowercase", "player").is_err());
+        assert!(validate_password("lowercase and spaces", "player").is_ok());
+        assert!(validate_password("MyPlayer123", "player").is_err());
+        assert!(validate_password(&"aA".repeat(65), "player").is_err());
+    }
+}
@@ -3,6 +3,7 @@ use deadpool_diesel::sqlite::Manager;
 use deadpool_diesel::Pool;
 use dotenvy::dotenv;
 use std::env;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tower_http::{
@@ -10,13 +11,35 @@ use tower_http::{
     trace::TraceLayer,
 };
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use warhundred_rs::app::combat::run_battle_sweeper;
+use warhundred_rs::app::encounter::run_encounter_roller;
+use warhundred_rs::app::key_ring::KeyRing;
+use warhundred_rs::app::mailer::OutboxMailer;
+use warhundred_rs::app::middleware::account_middleware::AccountMiddleware;
+use warhundred_rs::app::middleware::battle_middleware::BattleMiddleware;
 use warhundred_rs::app::middleware::cache_middleware::CacheMiddleware;
+use warhundred_rs::app::middleware::chat_middleware::ChatMiddleware;
+use warhundred_rs::app::middleware::gateway_middleware::GatewayMiddleware;
+use warhundred_rs::app::middleware::guild_middleware::GuildMiddleware;
+use warhundred_rs::app::middleware::inventory_middleware::InventoryMiddleware;
+use warhundred_rs::app::middleware::moderation_middleware::ModerationMiddleware;
 use warhundred_rs::app::middleware::player_middleware::PlayerMiddleware;
+use warhundred_rs::app::middleware::rate_limit_middleware::{
+    rate_limits_from_env, trusted_proxies_from_env, RateLimitMiddleware,
+};
 use warhundred_rs::app::middleware::static_tables_cache_middleware::StaticTablesCacheMiddleware;
 use warhundred_rs::app::redis::RedisConnectionManager;
 use warhundred_rs::app_state::AppState;
+use warhundred_rs::routes::account_routes::account_router;
+use warhundred_rs::routes::admin_routes::admin_router;
+use warhundred_rs::routes::battle_routes::battle_router;
+use warhundred_rs::routes::chat_routes::chat_router;
+use warhundred_rs::routes::gateway_routes::gateway_router;
+use warhundred_rs::routes::guild_routes::guild_router;
+use warhundred_rs::routes::inventory_routes::inventory_router;
 use warhundred_rs::routes::profile_routes::profile_router;
 use warhundred_rs::routes::root_routes::root_router;
+use warhundred_rs::routes::travel_routes::travel_router;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -32,6 +55,8 @@ async fn main() -> eyre::Result<()> {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let redis_uri = env::var("REDIS_URL").expect("REDIS_URL must be set");
 
+    let key_ring = Arc::new(KeyRing::from_env()?);
+
     let manager = Manager::new(database_url, deadpool_diesel::Runtime::Tokio1);
     let db_pool = Arc::new(Pool::builder(manager).build()?);
 
@@ -65,6 +90,59 @@ async fn main() -> eyre::Result<()> {
             .cache_pool(cache_pool.clone())
             .build(),
     );
+    static_table_middleware
+        .prefetch_all()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to prefetch static tables: {e}"))?;
+
+    let battle_middleware = Arc::new(BattleMiddleware::builder().db_pool(db_pool.clone()).build());
+    let gateway_middleware = Arc::new(GatewayMiddleware::builder().build());
+    let chat_middleware = Arc::new(
+        ChatMiddleware::builder()
+            .cache_pool(cache_pool.clone())
+            .build(),
+    );
+    let inventory_middleware = Arc::new(
+        InventoryMiddleware::builder()
+            .db_pool(db_pool.clone())
+            .build(),
+    );
+    let guild_middleware = Arc::new(
+        GuildMiddleware::builder()
+            .db_pool(db_pool.clone())
+            .cache_pool(cache_pool.clone())
+            .build(),
+    );
+    guild_middleware
+        .prefetch_clan_links()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to prefetch clan links: {e}"))?;
+
+    let rate_limit_middleware = Arc::new(
+        RateLimitMiddleware::builder()
+            .cache_pool(cache_pool.clone())
+            .limits(rate_limits_from_env()?)
+            .trusted_proxies(trusted_proxies_from_env()?)
+            .build(),
+    );
+
+    let account_middleware = Arc::new(
+        AccountMiddleware::builder()
+            .cache_pool(cache_pool.clone())
+            .mailer(Arc::new(OutboxMailer {
+                outbox: env::var("MAIL_OUTBOX").ok().map(Into::into),
+            }))
+            .require_verified_email(
+                env::var("REQUIRE_EMAIL_VERIFICATION").is_ok_and(|value| value == "true"),
+            )
+            .build(),
+    );
+
+    let moderation_middleware = Arc::new(
+        ModerationMiddleware::builder()
+            .db_pool(db_pool.clone())
+            .build(),
+    );
 
     let state = AppState {
         db_pool,
@@ -72,12 +150,32 @@ async fn main() -> eyre::Result<()> {
         player_middleware,
         cache_middleware,
         static_table_middleware,
+        battle_middleware,
+        gateway_middleware,
+        chat_middleware,
+        inventory_middleware,
+        guild_middleware,
+        rate_limit_middleware,
+        account_middleware,
+        moderation_middleware,
+        key_ring,
     };
 
+    tokio::spawn(run_encounter_roller(state.clone()));
+    tokio::spawn(run_battle_sweeper(state.clone()));
+
     // Setup HTTP server
     let app = Router::new()
         .merge(root_router())
         .merge(profile_router())
+        .merge(gateway_router())
+        .merge(chat_router())
+        .merge(inventory_router())
+        .merge(guild_router())
+        .merge(account_router())
+        .merge(admin_router())
+        .merge(travel_router())
+        .merge(battle_router())
         .with_state(state);
 
     // TODO: use nginx or similar for production to host static files
@@ -88,7 +186,8 @@ async fn main() -> eyre::Result<()> {
     axum::serve(
         listener,
         app.layer(CorsLayer::new().allow_origin(Any))
-            .layer(TraceLayer::new_for_http()),
+            .layer(TraceLayer::new_for_http())
+            .into_make_service_with_connect_info::<SocketAddr>(),
     )
     .await?;
 
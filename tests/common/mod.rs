@@ -1,11 +1,22 @@
 use deadpool_diesel::sqlite::Manager;
 use deadpool_diesel::Pool;
+use std::path::PathBuf;
 use std::sync::Arc;
 use testcontainers::runners::AsyncRunner;
 use testcontainers::ContainerAsync;
 use testcontainers_modules::redis::Redis;
+use warhundred_rs::app::key_ring::{JwtKey, KeyRing, HS512_MIN_SECRET_BYTES};
+use warhundred_rs::app::mailer::OutboxMailer;
+use warhundred_rs::app::middleware::account_middleware::AccountMiddleware;
+use warhundred_rs::app::middleware::battle_middleware::BattleMiddleware;
 use warhundred_rs::app::middleware::cache_middleware::CacheMiddleware;
+use warhundred_rs::app::middleware::chat_middleware::ChatMiddleware;
+use warhundred_rs::app::middleware::gateway_middleware::GatewayMiddleware;
+use warhundred_rs::app::middleware::guild_middleware::GuildMiddleware;
+use warhundred_rs::app::middleware::inventory_middleware::InventoryMiddleware;
+use warhundred_rs::app::middleware::moderation_middleware::ModerationMiddleware;
 use warhundred_rs::app::middleware::player_middleware::PlayerMiddleware;
+use warhundred_rs::app::middleware::rate_limit_middleware::RateLimitMiddleware;
 use warhundred_rs::app::middleware::static_tables_cache_middleware::StaticTablesCacheMiddleware;
 use warhundred_rs::app::redis::RedisConnectionManager;
 use warhundred_rs::app_state::AppState;
@@ -39,11 +50,56 @@ pub async fn ctx(sqlite_url: &str, redis_url: &str) -> eyre::Result<AppState> {
                 .cache_pool(cache_pool.clone())
                 .build(),
         ),
+        battle_middleware: Arc::new(BattleMiddleware::builder().db_pool(db_pool.clone()).build()),
+        gateway_middleware: Arc::new(GatewayMiddleware::builder().build()),
+        chat_middleware: Arc::new(
+            ChatMiddleware::builder()
+                .cache_pool(cache_pool.clone())
+                .build(),
+        ),
+        inventory_middleware: Arc::new(
+            InventoryMiddleware::builder()
+                .db_pool(db_pool.clone())
+                .build(),
+        ),
+        guild_middleware: Arc::new(
+            GuildMiddleware::builder()
+                .db_pool(db_pool.clone())
+                .cache_pool(cache_pool.clone())
+                .build(),
+        ),
+        rate_limit_middleware: Arc::new(
+            RateLimitMiddleware::builder()
+                .cache_pool(cache_pool.clone())
+                .build(),
+        ),
+        account_middleware: Arc::new(
+            AccountMiddleware::builder()
+                .cache_pool(cache_pool.clone())
+                .mailer(Arc::new(OutboxMailer {
+                    outbox: Some(test_outbox()),
+                }))
+                .build(),
+        ),
+        moderation_middleware: Arc::new(
+            ModerationMiddleware::builder()
+                .db_pool(db_pool.clone())
+                .build(),
+        ),
+        key_ring: Arc::new(KeyRing::new(vec![JwtKey::hs512(
+            "test",
+            &[7; HS512_MIN_SECRET_BYTES],
+        )?])?),
         db_pool,
         cache_pool,
     })
 }
 
+/// Mails sent during the tests land here.
+pub fn test_outbox() -> PathBuf {
+    std::env::temp_dir().join(format!("warhundred-test-outbox-{}.txt", std::process::id()))
+}
+
 pub async fn redis_conn_uri(c: &ContainerAsync<Redis>) -> eyre::Result<String> {
     Ok(format!(
         "redis://{host}:{port}",
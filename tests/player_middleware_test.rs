@@ -1,3 +1,5 @@
+#[cfg(feature = "it_test")]
+use crate::common::test_outbox;
 use crate::common::{ctx, redis_conn_uri, start_containers, STD_SQLITE_TEST_URL};
 use axum::{http, Router};
 use axum_test::TestServer;
@@ -6,15 +8,36 @@ use deadpool_diesel::Error;
 use diesel::{sql_types::Integer, QueryableByName, RunQueryDsl};
 use dotenvy::dotenv;
 use http::header::CONTENT_TYPE;
+#[cfg(feature = "it_test")]
 use redis::AsyncCommands;
 use rstest::{fixture, rstest};
 use serial_test::serial;
 use std::sync::Arc;
 use testcontainers::ContainerAsync;
 use testcontainers_modules::redis::Redis;
+#[cfg(feature = "it_test")]
+use tokio::sync::broadcast;
+#[cfg(feature = "it_test")]
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::middleware::gateway_middleware::{GatewayEvent, Topic};
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::middleware::rate_limit_middleware::{
+    LOGIN_BASE_LOCKOUT_SECS, LOGIN_FAILURES_BEFORE_LOCKOUT,
+};
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::protos::messages::server_frame::Payload;
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::protos::messages::zone_event::Kind;
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::protos::messages::ZoneEvent;
+#[cfg(feature = "it_test")]
 use warhundred_rs::app::redis::CacheKey;
+#[cfg(feature = "it_test")]
+use warhundred_rs::app::travel::START_MAP_LOCATION;
 use warhundred_rs::app_state::AppState;
+use warhundred_rs::routes::account_routes::account_router;
+use warhundred_rs::routes::admin_routes::admin_router;
 use warhundred_rs::routes::profile_routes::profile_router;
 use warhundred_rs::routes::root_routes::root_router;
 
@@ -59,7 +82,13 @@ pub async fn app() -> eyre::Result<App> {
             registration_time TIMESTAMP, \
             last_login_time TIMESTAMP, \
             guild_id INTEGER,\
-            banned INTEGER);",
+            banned INTEGER NOT NULL DEFAULT 0, \
+            email_verified INTEGER NOT NULL DEFAULT 0, \
+            role INTEGER NOT NULL DEFAULT 0, \
+            ban_reason TEXT, \
+            banned_until TIMESTAMP, \
+            muted_until TIMESTAMP, \
+            last_map_location INTEGER);",
         )
         .execute(conn)
         .expect("Player table creation failed");
@@ -76,11 +105,55 @@ pub async fn app() -> eyre::Result<App> {
                 intellect INTEGER NOT NULL DEFAULT 0,
                 experience INTEGER NOT NULL DEFAULT 0,
                 level INTEGER NOT NULL DEFAULT 0,
-                valor INTEGER NOT NULL DEFAULT 0);",
+                valor INTEGER NOT NULL DEFAULT 0,
+                up INTEGER NOT NULL DEFAULT 0,
+                attribute_points INTEGER NOT NULL DEFAULT 0,
+                money INTEGER NOT NULL DEFAULT 0);",
         )
         .execute(conn)
         .expect("Player table creation failed");
 
+        for ddl in [
+            "CREATE TABLE IF NOT EXISTS item (\
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                name TEXT NOT NULL,
+                level_req INTEGER NOT NULL DEFAULT 0,
+                strength_req INTEGER NOT NULL DEFAULT 0,
+                dexterity_req INTEGER NOT NULL DEFAULT 0,
+                physique_req INTEGER NOT NULL DEFAULT 0,
+                intellect_req INTEGER NOT NULL DEFAULT 0,
+                valor_req INTEGER NOT NULL DEFAULT 0,
+                class_req INTEGER);",
+            "CREATE TABLE IF NOT EXISTS weapon_item (\
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                item_id INTEGER NOT NULL,
+                action_points_to_use INTEGER NOT NULL DEFAULT 2,
+                basic_damage INTEGER NOT NULL DEFAULT 0,
+                range INTEGER NOT NULL DEFAULT 1);",
+            "CREATE TABLE IF NOT EXISTS gear_item (\
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                item_id INTEGER NOT NULL);",
+            "CREATE TABLE IF NOT EXISTS player_inventory (\
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                player_id INTEGER NOT NULL,
+                item_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL DEFAULT 1,
+                weight REAL NOT NULL DEFAULT 0,
+                equipped BOOLEAN NOT NULL DEFAULT FALSE);",
+            "CREATE TABLE IF NOT EXISTS moderation_audit (\
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                admin_id INTEGER NOT NULL,
+                target_id INTEGER NOT NULL,
+                action TEXT NOT NULL,
+                reason TEXT,
+                details TEXT,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP);",
+        ] {
+            diesel::sql_query(ddl)
+                .execute(conn)
+                .expect("Inventory table creation failed");
+        }
+
         Ok::<(), Error>(())
     })
     .await
@@ -90,6 +163,8 @@ pub async fn app() -> eyre::Result<App> {
         Router::new()
             .merge(root_router())
             .merge(profile_router())
+            .merge(account_router())
+            .merge(admin_router())
             .with_state(state.clone()),
     )
     .unwrap();
@@ -114,6 +189,18 @@ pub async fn after_test(pool: Arc<Pool>) -> eyre::Result<()> {
         .await
         .map_err(|e| eyre::eyre!("{:?}", e))??;
 
+    for table in [
+        "player_inventory",
+        "gear_item",
+        "weapon_item",
+        "item",
+        "moderation_audit",
+    ] {
+        conn.interact(move |conn| diesel::sql_query(format!("DROP TABLE {table};")).execute(conn))
+            .await
+            .map_err(|e| eyre::eyre!("{:?}", e))??;
+    }
+
     Ok(())
 }
 
@@ -132,14 +219,48 @@ async fn test_register_ok(#[future] app: eyre::Result<App>) -> eyre::Result<()>
         .post("/register")
         .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
         .json(&serde_json::json!({
-            "username": "a",
-            "email": "a@a.com",
-            "password": "pwd"
+            "username": "newbie",
+            "email": "newbie@example.com",
+            "password": "Secret password"
         }))
         .await;
 
     res.assert_status_ok();
 
+    let taken_res = server
+        .post("/register")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": "NEWBIE",
+            "email": "other@example.com",
+            "password": "Secret password"
+        }))
+        .await;
+    taken_res.assert_status(http::StatusCode::CONFLICT);
+    taken_res.assert_json(&serde_json::json!({
+        "code": "already_taken",
+        "message": "nickname is already taken",
+        "fields": [{ "field": "nickname", "message": "is already taken" }]
+    }));
+
+    let invalid_res = server
+        .post("/register")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": "a",
+            "email": "a@a",
+            "password": "pwd"
+        }))
+        .await;
+    invalid_res.assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<String> = invalid_res.json::<serde_json::Value>()["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(fields, ["nickname", "email", "password"]);
+
     after_test(state.db_pool.clone()).await?;
 
     Ok(())
@@ -158,7 +279,7 @@ async fn test_login_ok(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
 
     // First register a user
     let username = "testuser";
-    let password = "testpassword";
+    let password = "testpassword1";
 
     let register_res = server
         .post("/register")
@@ -189,14 +310,473 @@ async fn test_login_ok(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
     let access_token = login_response["access_token"].as_str().unwrap().to_string();
 
     // Verify the session exists in Redis
-    let session_exists = state
+    let player = state
         .player_middleware
-        .check_player_session_token(username, access_token)
+        .get_player_by_nick(username.to_string())
+        .await
+        .unwrap();
+    let player_id = player.id.unwrap() as i64;
+    let session_exists = state
+        .cache_middleware
+        .check_session_token(player_id, &access_token)
         .await
         .unwrap();
 
     assert!(session_exists, "Session should exist in Redis");
 
+    // Nicknames and emails are looked up regardless of case
+    let player = state
+        .player_middleware
+        .get_player_by_nick("TestUser".to_string())
+        .await
+        .unwrap();
+    assert_eq!(player.id, Some(player_id as i32));
+    assert_eq!(player.nickname, username);
+    let player = state
+        .player_middleware
+        .get_player_by_email("Test@Example.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(player.and_then(|player| player.id), Some(player_id as i32));
+
+    // A refresh token is exchanged for a new pair of tokens
+    let refresh_token = login_response["refresh_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let refresh_res = server
+        .post("/token/refresh")
+        .json(&serde_json::json!({ "refresh_token": refresh_token }))
+        .await;
+    refresh_res.assert_status_ok();
+    let refresh_response = refresh_res.json::<serde_json::Value>();
+    let access_token = refresh_response["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(state
+        .cache_middleware
+        .check_session_token(player_id, &access_token)
+        .await
+        .unwrap());
+
+    // Reusing a refresh token revokes the whole family with the session
+    server
+        .post("/token/refresh")
+        .json(&serde_json::json!({ "refresh_token": refresh_token }))
+        .await
+        .assert_status_unauthorized();
+    let session = state.cache_middleware.get_session(player_id).await.unwrap();
+    assert!(session.is_none(), "Session should be revoked");
+    server
+        .post("/token/refresh")
+        .json(&serde_json::json!({ "refresh_token": refresh_response["refresh_token"] }))
+        .await
+        .assert_status_unauthorized();
+
+    let login_res = server
+        .post("/login")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password
+        }))
+        .await;
+    let access_token = login_res.json::<serde_json::Value>()["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Logout ends the session
+    let logout_res = server
+        .post("/logout")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "nickname": username,
+            "access_token": access_token
+        }))
+        .await;
+
+    logout_res.assert_json_contains(&serde_json::json!({ "ok": true }));
+    let session = state.cache_middleware.get_session(player_id).await.unwrap();
+    assert!(session.is_none(), "Session should be removed from Redis");
+
+    after_test(state.db_pool.clone()).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "it_test")]
+async fn register_and_login(server: &TestServer, username: &str) -> serde_json::Value {
+    server
+        .post("/register")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "email": format!("{username}@example.com"),
+            "password": "testpassword1"
+        }))
+        .await
+        .assert_status_ok();
+    let login_res = server
+        .post("/login")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "password": "testpassword1"
+        }))
+        .await;
+    login_res.assert_status_ok();
+
+    login_res.json::<serde_json::Value>()
+}
+
+#[cfg(feature = "it_test")]
+fn zone_events(receiver: &mut broadcast::Receiver<GatewayEvent>) -> Vec<ZoneEvent> {
+    std::iter::from_fn(|| receiver.try_recv().ok())
+        .filter_map(|event| match event.frame.payload {
+            Some(Payload::ZoneEvent(zone_event)) => Some(zone_event),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "it_test")]
+fn ended_sessions(receiver: &mut broadcast::Receiver<GatewayEvent>) -> Vec<Topic> {
+    std::iter::from_fn(|| receiver.try_recv().ok())
+        .filter(|event| matches!(event.frame.payload, Some(Payload::SessionEnded(_))))
+        .map(|event| event.topic)
+        .collect()
+}
+
+#[cfg(all(test, feature = "it_test"))]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_login_joins_zone(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
+    let App {
+        _redis,
+        server,
+        state,
+    } = app.await?;
+    let mut receiver = state.gateway_middleware.subscribe();
+
+    register_and_login(&server, "walker").await;
+
+    let player_id = state
+        .player_middleware
+        .get_player_by_nick("walker".to_string())
+        .await
+        .unwrap()
+        .id
+        .unwrap() as i64;
+    let zone_id = START_MAP_LOCATION as i64;
+    assert!(state
+        .cache_middleware
+        .is_in_zone(zone_id, player_id)
+        .await
+        .unwrap());
+    let events = zone_events(&mut receiver);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, Kind::Join as i32);
+    assert_eq!(events[0].zone_id, zone_id);
+    assert_eq!(events[0].player_id, player_id);
+    assert_eq!(events[0].nickname, "walker");
+
+    after_test(state.db_pool.clone()).await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "it_test"))]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_logout_leaves_zone(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
+    let App {
+        _redis,
+        server,
+        state,
+    } = app.await?;
+    let login_response = register_and_login(&server, "walker").await;
+    let player_id = state
+        .player_middleware
+        .get_player_by_nick("walker".to_string())
+        .await
+        .unwrap()
+        .id
+        .unwrap() as i64;
+    let mut receiver = state.gateway_middleware.subscribe();
+
+    server
+        .post("/logout")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "nickname": "walker",
+            "access_token": login_response["access_token"]
+        }))
+        .await
+        .assert_json_contains(&serde_json::json!({ "ok": true }));
+
+    let zone_id = START_MAP_LOCATION as i64;
+    assert!(!state
+        .cache_middleware
+        .is_in_zone(zone_id, player_id)
+        .await
+        .unwrap());
+    let events = zone_events(&mut receiver);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, Kind::Leave as i32);
+    assert_eq!(events[0].zone_id, zone_id);
+    assert_eq!(events[0].player_id, player_id);
+
+    after_test(state.db_pool.clone()).await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "it_test"))]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_login_lockout(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
+    let App {
+        _redis,
+        server,
+        state,
+    } = app.await?;
+
+    let username = "lockeduser";
+    server
+        .post("/register")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "email": "locked@example.com",
+            "password": "testpassword1"
+        }))
+        .await
+        .assert_status_ok();
+
+    let login = |password: &'static str| {
+        server
+            .post("/login")
+            .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .json(&serde_json::json!({
+                "username": username,
+                "password": password
+            }))
+    };
+
+    for _ in 0..LOGIN_FAILURES_BEFORE_LOCKOUT {
+        login("wrong").await.assert_status_unauthorized();
+    }
+
+    // Even the right password is rejected during the lockout
+    let locked_res = login("testpassword1").await;
+    locked_res.assert_status(http::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = locked_res
+        .header(http::header::RETRY_AFTER)
+        .to_str()?
+        .parse::<u64>()?;
+    assert!(retry_after > 0 && retry_after <= LOGIN_BASE_LOCKOUT_SECS);
+
+    after_test(state.db_pool.clone()).await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "it_test"))]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_password_reset(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
+    let App {
+        _redis,
+        server,
+        state,
+    } = app.await?;
+
+    let username = "forgetful";
+    let email = "forgetful@example.com";
+    server
+        .post("/register")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "email": email,
+            "password": "old password"
+        }))
+        .await
+        .assert_status_ok();
+
+    server
+        .post("/password/forgot")
+        .json(&serde_json::json!({ "email": email }))
+        .await
+        .assert_json_contains(&serde_json::json!({ "ok": true }));
+    // Unknown emails get the same answer
+    server
+        .post("/password/forgot")
+        .json(&serde_json::json!({ "email": "nobody@example.com" }))
+        .await
+        .assert_json_contains(&serde_json::json!({ "ok": true }));
+
+    let outbox = std::fs::read_to_string(test_outbox())?;
+    let marker = "Your password reset token is ";
+    let token_start = outbox.rfind(marker).expect("Reset mail should be sent") + marker.len();
+    let token = &outbox[token_start..token_start + 64];
+
+    let reset = |token: &str| {
+        server.post("/password/reset").json(&serde_json::json!({
+            "token": token,
+            "password": "new password"
+        }))
+    };
+    // A weak password doesn't use the token up
+    server
+        .post("/password/reset")
+        .json(&serde_json::json!({
+            "token": token,
+            "password": "pwd"
+        }))
+        .await
+        .assert_status(http::StatusCode::UNPROCESSABLE_ENTITY);
+    reset(token).await.assert_status_ok();
+    // Tokens are single use
+    reset(token).await.assert_status_unauthorized();
+
+    server
+        .post("/login")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "password": "new password"
+        }))
+        .await
+        .assert_status_ok();
+
+    after_test(state.db_pool.clone()).await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "it_test"))]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_moderation(#[future] app: eyre::Result<App>) -> eyre::Result<()> {
+    let App {
+        _redis,
+        server,
+        state,
+    } = app.await?;
+
+    for (username, email) in [
+        ("warden", "warden@example.com"),
+        ("troll", "troll@example.com"),
+    ] {
+        server
+            .post("/register")
+            .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .json(&serde_json::json!({
+                "username": username,
+                "email": email,
+                "password": "Secret password"
+            }))
+            .await
+            .assert_status_ok();
+    }
+    // There is no route granting the admin role, it's given in the database.
+    state
+        .db_pool
+        .get()
+        .await?
+        .interact(|conn| {
+            diesel::sql_query("UPDATE player SET role = 1 WHERE nickname = 'warden';").execute(conn)
+        })
+        .await
+        .map_err(|e| eyre::eyre!("{:?}", e))??;
+
+    let login = |username: &'static str| {
+        server
+            .post("/login")
+            .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .json(&serde_json::json!({
+                "username": username,
+                "password": "Secret password"
+            }))
+    };
+    let admin_token = login("warden").await.json::<serde_json::Value>()["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let troll_token = login("troll").await.json::<serde_json::Value>()["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Players can't moderate
+    server
+        .post("/admin/player/warden/ban")
+        .authorization_bearer(&troll_token)
+        .json(&serde_json::json!({ "reason": "revenge" }))
+        .await
+        .assert_status_forbidden();
+
+    let troll_id = state
+        .player_middleware
+        .get_player_id_by_nick("troll".to_string())
+        .await
+        .unwrap() as i64;
+    let mut receiver = state.gateway_middleware.subscribe();
+    server
+        .post("/admin/player/troll/ban")
+        .authorization_bearer(&admin_token)
+        .json(&serde_json::json!({ "reason": "spam", "duration_secs": 3600 }))
+        .await
+        .assert_json_contains(&serde_json::json!({ "action": "ban", "reason": "spam" }));
+    // The sockets of the banned player are told to close
+    assert_eq!(ended_sessions(&mut receiver), [Topic::Player(troll_id)]);
+
+    // The ban applies to the running session and to the next login
+    server
+        .get("/profile/troll")
+        .authorization_bearer(&troll_token)
+        .await
+        .assert_status_forbidden();
+    login("troll").await.assert_status_forbidden();
+
+    server
+        .post("/admin/player/troll/unban")
+        .authorization_bearer(&admin_token)
+        .json(&serde_json::json!({ "reason": "appealed" }))
+        .await
+        .assert_status_ok();
+    login("troll").await.assert_status_ok();
+
+    server
+        .post("/admin/player/troll/logout")
+        .authorization_bearer(&admin_token)
+        .json(&serde_json::json!({ "reason": "cool down" }))
+        .await
+        .assert_status_ok();
+    assert_eq!(ended_sessions(&mut receiver), [Topic::Player(troll_id)]);
+
+    let audit = server
+        .get("/admin/audit")
+        .add_query_param("nickname", "troll")
+        .authorization_bearer(&admin_token)
+        .await
+        .json::<serde_json::Value>();
+    let actions: Vec<&str> = audit["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["force_logout", "unban", "ban"]);
+
     after_test(state.db_pool.clone()).await?;
 
     Ok(())
@@ -225,7 +805,7 @@ async fn test_player_profile(#[future] app: eyre::Result<App>) -> eyre::Result<(
     // Register a test player
     let username = "test1";
     let email = "profile@test.com";
-    let password = "password";
+    let password = "password1";
 
     let register_res = server
         .post("/register")
@@ -287,8 +867,29 @@ async fn test_player_profile(#[future] app: eyre::Result<App>) -> eyre::Result<(
         .hset::<&str, i32, String, ()>(CacheKey::RankTable.as_ref(), 1, "Recruit".to_string())
         .await?;
 
+    // The profile is available to logged in players only
+    let unauthorized_res = server.get(&format!("/profile/{}", username)).await;
+    unauthorized_res.assert_status_unauthorized();
+
+    let login_res = server
+        .post("/login")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password
+        }))
+        .await;
+    login_res.assert_status_ok();
+    let access_token = login_res.json::<serde_json::Value>()["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
     // Make a request to the profile endpoint
-    let profile_res = server.get(&format!("/profile/{}", username)).await;
+    let profile_res = server
+        .get(&format!("/profile/{}", username))
+        .authorization_bearer(&access_token)
+        .await;
 
     profile_res.assert_status_ok();
     profile_res.assert_json_contains(&serde_json::json!({
@@ -303,6 +904,22 @@ async fn test_player_profile(#[future] app: eyre::Result<App>) -> eyre::Result<(
         "intellect": 5
     }));
 
+    // A logged out token is revoked
+    server
+        .post("/logout")
+        .add_header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .json(&serde_json::json!({
+            "nickname": username,
+            "access_token": access_token
+        }))
+        .await
+        .assert_status_ok();
+    server
+        .get(&format!("/profile/{}", username))
+        .authorization_bearer(&access_token)
+        .await
+        .assert_status_unauthorized();
+
     // Clean up
     after_test(state.db_pool.clone()).await?;
 